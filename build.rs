@@ -0,0 +1,15 @@
+use std::process::Command;
+
+/// Passes the commit being built to the code as `TODO_CLI_GIT_COMMIT`, when
+/// building from a git checkout with git installed.
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+    let output = Command::new("git").args(["rev-parse", "HEAD"]).output();
+    if let Ok(output) = output
+        && output.status.success()
+        && let Ok(commit) = String::from_utf8(output.stdout)
+    {
+        println!("cargo:rustc-env=TODO_CLI_GIT_COMMIT={}", commit.trim());
+    }
+}
@@ -0,0 +1,115 @@
+use crate::task::Task;
+use std::collections::HashSet;
+use std::env;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+/// Used when neither `$VISUAL` nor `$EDITOR` is set.
+const DEFAULT_EDITOR: &str = if cfg!(windows) { "notepad" } else { "vi" };
+
+const HEADER: &str = "\
+# One task per line: its id, then its description. Edit a description to
+# change it, delete a line to delete the task, or add a line without an id to
+# add a task; start it with '+' if the description begins with a number.
+# Lines starting with '#' are ignored. Save an unchanged file to cancel.
+";
+
+/// What an edited `bulk-edit` file asks for.
+#[derive(Debug, Default)]
+pub struct BulkChanges {
+    /// Ids of tasks whose description changed, with the new description.
+    pub renamed: Vec<(usize, String)>,
+    /// Ids of tasks whose line was removed.
+    pub removed: Vec<usize>,
+    /// Descriptions of new tasks, in file order.
+    pub added: Vec<String>,
+}
+
+impl BulkChanges {
+    pub fn is_empty(&self) -> bool {
+        self.renamed.is_empty() && self.removed.is_empty() && self.added.is_empty()
+    }
+}
+
+/// The text `bulk-edit` opens: a short help header, then "<id> <description>"
+/// for every task, in file order.
+pub fn render(tasks: &[Task]) -> String {
+    let mut text = HEADER.to_string();
+    for task in tasks {
+        text.push_str(&format!("{} {}\n", task.id, task.description));
+    }
+    text
+}
+
+/// Compares an edited file with `tasks`. Fails, naming the line, on an id
+/// that isn't in `tasks`, an id listed twice or a line with an id but no
+/// description, so that a slip never deletes or renames the wrong task.
+pub fn parse(text: &str, tasks: &[Task]) -> Result<BulkChanges, String> {
+    let mut changes = BulkChanges::default();
+    let mut seen = HashSet::new();
+    for (number, line) in text.lines().enumerate() {
+        let number = number + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(description) = line.strip_prefix('+') {
+            let description = description.trim();
+            if description.is_empty() {
+                return Err(format!("line {}: the new task has no description", number));
+            }
+            changes.added.push(description.to_string());
+            continue;
+        }
+        let (head, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let Ok(id) = head.parse::<usize>() else {
+            changes.added.push(line.to_string());
+            continue;
+        };
+        let Some(task) = tasks.iter().find(|t| t.id == id) else {
+            return Err(format!(
+                "line {}: there is no task {}; start a new task with '+' if it begins \
+                 with a number",
+                number, id
+            ));
+        };
+        if !seen.insert(id) {
+            return Err(format!("line {}: task {} is listed twice", number, id));
+        }
+        let description = rest.trim();
+        if description.is_empty() {
+            return Err(format!(
+                "line {}: task {} has no description; delete the line to delete the task",
+                number, id
+            ));
+        }
+        if description != task.description {
+            changes.renamed.push((id, description.to_string()));
+        }
+    }
+    changes.removed = tasks.iter().map(|t| t.id).filter(|id| !seen.contains(id)).collect();
+    Ok(changes)
+}
+
+/// Opens `path` in `$VISUAL`, `$EDITOR` or a platform default and waits for
+/// it to exit. An editor that exits with an error counts as cancelling.
+pub fn edit(path: &Path) -> io::Result<()> {
+    let editor = ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_string());
+    // The path goes in as an argument rather than into the command string,
+    // so spaces in it need no quoting.
+    let status = if cfg!(windows) {
+        Command::new("cmd").args(["/C", &editor]).arg(path).status()?
+    } else {
+        let command = format!("{} \"$1\"", editor);
+        Command::new("sh").args(["-c", &command, "sh"]).arg(path).status()?
+    };
+    if !status.success() {
+        return Err(io::Error::other(format!("{} exited with {}", editor, status)));
+    }
+    Ok(())
+}
@@ -0,0 +1,182 @@
+use crate::display::{DatePattern, IdFormat};
+use crate::group::{self, WeekStart};
+use crate::sort::Nulls;
+use crate::urgency;
+use chrono::{Duration, NaiveTime};
+use serde::{Deserialize, Deserializer};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    #[serde(deserialize_with = "deserialize_hours")]
+    pub warn_within_hours: i64,
+    /// Replaces `warn_within_hours` when set, written like `--remind`, e.g.
+    /// "24h".
+    #[serde(deserialize_with = "deserialize_optional_duration")]
+    pub warn_within: Option<Duration>,
+    /// Pending tasks due within this long are shown in red like overdue
+    /// ones, e.g. "6h". Zero by default.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub urgent_within: Duration,
+    /// Shell command run after a task is marked done. `{id}` and
+    /// `{description}` are replaced with the task's values.
+    pub on_done: Option<String>,
+    /// What runs when no command is given, written as on the command line,
+    /// e.g. "list --status pending". `list` when unset.
+    pub default_command: Option<String>,
+    /// Keep the case of tag, context and project names as typed rather than
+    /// lowercasing them, so `Work` and `work` differ. Off by default.
+    pub case_sensitive_names: bool,
+    /// Where `backup` writes its copies; a `backups` directory next to the
+    /// task file when unset.
+    pub backup_dir: Option<PathBuf>,
+    /// How many of the newest backups `backup` keeps, deleting older ones;
+    /// 0 keeps them all. 10 by default.
+    pub keep_backups: usize,
+    /// Who `--assignee me` means; `$USER` (or `%USERNAME%`) when unset.
+    pub user: Option<String>,
+    /// Mark a task done automatically once all of its subtasks are checked.
+    pub auto_complete_parent: bool,
+    /// Older name for `retain_done_days`, which wins when both are set.
+    pub archive_done_after_days: Option<i64>,
+    /// Remove done tasks this many days after completion, as
+    /// `retention_action` says.
+    pub retain_done_days: Option<i64>,
+    /// Keep at most this many tasks, removing the ones `trim` would.
+    pub max_tasks: Option<usize>,
+    /// What the retention pass does with the tasks it removes.
+    pub retention_action: RetentionAction,
+    /// Coefficients of `--sort urgency` and the `urgency` column; any left
+    /// out keep their defaults.
+    pub urgency: urgency::Weights,
+    /// `list` marks pending tasks created more than this many days ago as
+    /// stale. Unset by default.
+    pub stale_after_days: Option<i64>,
+    /// Read `!`, `#tag` and `@context` markers in `add` descriptions, as with
+    /// `add --shorthand`. Off by default.
+    pub shorthand: bool,
+    /// Longest description, in characters, that `add` and `import` accept.
+    pub max_description_length: Option<usize>,
+    /// How long past its due time a task may be before it counts as overdue,
+    /// written like `--remind`, e.g. "2h". Zero by default.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub overdue_grace: Duration,
+    /// "decimal", "padded" or "hex"; see `--id-format`.
+    pub id_format: IdFormat,
+    /// "monday" (the default) or "sunday"; used by `--group-by week`.
+    pub week_starts_on: WeekStart,
+    /// Time of day, "HH:MM", given to `--due` dates written without one.
+    /// "23:59" by default, so a task due today isn't overdue all day.
+    #[serde(deserialize_with = "deserialize_time")]
+    pub default_due_time: NaiveTime,
+    /// Header for undated tasks under `--group-by`.
+    pub someday_label: String,
+    /// "first" or "last" (the default): where undated tasks go under
+    /// `--group-by` and, unless `--nulls` says otherwise, `--sort due`.
+    pub someday_position: Nulls,
+    /// How dates are shown; see `--locale`. ISO by default.
+    #[serde(rename = "locale", deserialize_with = "deserialize_locale")]
+    pub date_pattern: DatePattern,
+}
+
+fn deserialize_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    crate::parse_duration(&text).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_optional_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    match Option::<String>::deserialize(deserializer)? {
+        Some(text) => crate::parse_duration(&text).map(Some).map_err(serde::de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+/// A whole number of hours, rejected if it is too large for a `Duration`.
+fn deserialize_hours<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    let hours = i64::deserialize(deserializer)?;
+    match Duration::try_hours(hours) {
+        Some(_) => Ok(hours),
+        None => Err(serde::de::Error::custom(format!("{} hours is out of range", hours))),
+    }
+}
+
+fn deserialize_locale<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DatePattern, D::Error> {
+    let locale = String::deserialize(deserializer)?;
+    DatePattern::for_locale(&locale).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_time<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveTime, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    NaiveTime::parse_from_str(&text, "%H:%M").map_err(serde::de::Error::custom)
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            warn_within_hours: 24,
+            warn_within: None,
+            urgent_within: Duration::zero(),
+            on_done: None,
+            default_command: None,
+            case_sensitive_names: false,
+            user: None,
+            backup_dir: None,
+            keep_backups: 10,
+            auto_complete_parent: false,
+            archive_done_after_days: None,
+            retain_done_days: None,
+            max_tasks: None,
+            retention_action: RetentionAction::Archive,
+            urgency: urgency::Weights::default(),
+            stale_after_days: None,
+            shorthand: false,
+            max_description_length: None,
+            overdue_grace: Duration::zero(),
+            id_format: IdFormat::Decimal,
+            week_starts_on: WeekStart::Monday,
+            default_due_time: NaiveTime::from_hms_opt(23, 59, 0).expect("23:59 is a valid time"),
+            someday_label: group::SOMEDAY_LABEL.to_string(),
+            someday_position: Nulls::Last,
+            date_pattern: DatePattern::ISO,
+        }
+    }
+}
+
+/// "archive" (the default) moves tasks to the archive file, "delete" removes
+/// them, with `undo` able to bring them back.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RetentionAction {
+    #[default]
+    Archive,
+    Delete,
+}
+
+impl Config {
+    /// Reads the config at `path`; a missing file gives the defaults.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let data = fs::read_to_string(path)?;
+        let config: Config = serde_json::from_str(&data)?;
+        Ok(config)
+    }
+
+    pub fn retain_done_days(&self) -> Option<i64> {
+        self.retain_done_days.or(self.archive_done_after_days)
+    }
+
+    /// How close to its due time a pending task is highlighted in yellow.
+    pub fn warn_within(&self) -> Duration {
+        self.warn_within.unwrap_or_else(|| {
+            Duration::try_hours(self.warn_within_hours)
+                .expect("warn_within_hours is checked on load")
+        })
+    }
+}
@@ -0,0 +1,91 @@
+use crate::task::Task;
+
+/// Looks for a cycle that making task `id` depend on `new_dependencies`
+/// would create. Returns the cycle as a path of ids starting and ending at
+/// `id`, e.g. `[3, 5, 3]`.
+pub fn find_cycle(tasks: &[Task], id: usize, new_dependencies: &[usize]) -> Option<Vec<usize>> {
+    for &dependency in new_dependencies {
+        let mut path = vec![id];
+        let mut visited = Vec::new();
+        if reaches(tasks, dependency, id, &mut path, &mut visited) {
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// One row of `list --tree`.
+pub struct TreeRow<'a> {
+    pub depth: usize,
+    pub task: &'a Task,
+    /// Already shown earlier as the prerequisite of another task; its own
+    /// prerequisites are not repeated.
+    pub repeat: bool,
+}
+
+/// Orders `tasks` as a forest: each task is followed by its prerequisites
+/// (the tasks it depends on), indented one level deeper. Tasks nothing in
+/// `tasks` depends on are the roots, in the given order. Prerequisites
+/// outside `tasks` (filtered out, archived) are left out.
+pub fn tree<'a>(tasks: &[&'a Task]) -> Vec<TreeRow<'a>> {
+    let is_prerequisite = |id: usize| tasks.iter().any(|t| t.depends_on.contains(&id));
+    let mut rows = Vec::new();
+    let mut shown = Vec::new();
+    for task in tasks.iter().filter(|t| !is_prerequisite(t.id)) {
+        push_subtree(tasks, task, 0, &mut rows, &mut shown);
+    }
+    // Tasks caught in a cycle have no root; list them rather than hide them.
+    for task in tasks {
+        if !shown.contains(&task.id) {
+            push_subtree(tasks, task, 0, &mut rows, &mut shown);
+        }
+    }
+    rows
+}
+
+fn push_subtree<'a>(
+    tasks: &[&'a Task],
+    task: &'a Task,
+    depth: usize,
+    rows: &mut Vec<TreeRow<'a>>,
+    shown: &mut Vec<usize>,
+) {
+    let repeat = shown.contains(&task.id);
+    rows.push(TreeRow { depth, task, repeat });
+    if repeat {
+        return;
+    }
+    shown.push(task.id);
+    for id in &task.depends_on {
+        if let Some(prerequisite) = tasks.iter().find(|t| t.id == *id) {
+            push_subtree(tasks, prerequisite, depth + 1, rows, shown);
+        }
+    }
+}
+
+/// Depth-first search along `depends_on` from `from`, extending `path` on
+/// the way down and leaving it in place when `target` is found.
+fn reaches(
+    tasks: &[Task],
+    from: usize,
+    target: usize,
+    path: &mut Vec<usize>,
+    visited: &mut Vec<usize>,
+) -> bool {
+    path.push(from);
+    if from == target {
+        return true;
+    }
+    if !visited.contains(&from) {
+        visited.push(from);
+        if let Some(task) = tasks.iter().find(|t| t.id == from) {
+            for &next in &task.depends_on {
+                if reaches(tasks, next, target, path, visited) {
+                    return true;
+                }
+            }
+        }
+    }
+    path.pop();
+    false
+}
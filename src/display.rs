@@ -0,0 +1,507 @@
+use chrono::{DateTime, Duration, FixedOffset};
+use std::env;
+use std::io::{self, IsTerminal};
+use std::sync::OnceLock;
+
+pub const RED: &str = "\x1b[31m";
+pub const GREEN: &str = "\x1b[32m";
+pub const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+/// When to color output, as chosen with `--color`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Color even when stdout is not a terminal, e.g. for `less -R`
+    Always,
+    /// Color when stdout is a terminal and `NO_COLOR` is unset
+    #[default]
+    Auto,
+    /// Never color
+    Never,
+}
+
+/// Whether to emit ANSI colors. On Windows this also switches the console
+/// into virtual terminal mode, without which older consoles print the escape
+/// codes literally; colors stay off under `auto` if that fails.
+pub fn color_enabled(choice: ColorChoice) -> bool {
+    let console_ok = || anstyle_query::windows::enable_ansi_colors().unwrap_or(true);
+    match choice {
+        ColorChoice::Always => {
+            console_ok();
+            true
+        }
+        ColorChoice::Auto => {
+            io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none() && console_ok()
+        }
+        ColorChoice::Never => false,
+    }
+}
+
+/// The non-ASCII symbols used in list output, kept in one place so `--ascii`
+/// can swap all of them at once.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Markers {
+    /// Ends descriptions cut short to fit the terminal
+    pub ellipsis: &'static str,
+    /// Follows the description of a locked task
+    pub locked: &'static str,
+    /// Repeated to draw the bars of `stats --streak`
+    pub bar: &'static str,
+}
+
+pub const UNICODE_MARKERS: Markers = Markers {
+    ellipsis: "…",
+    locked: "🔒",
+    bar: "█",
+};
+pub const ASCII_MARKERS: Markers = Markers {
+    ellipsis: "...",
+    locked: "[locked]",
+    bar: "#",
+};
+
+impl Default for Markers {
+    fn default() -> Self {
+        UNICODE_MARKERS
+    }
+}
+
+/// Picks the markers for this terminal: ASCII when `ascii` is set, `TERM` is
+/// `dumb`, or the locale (`LC_ALL`, `LC_CTYPE`, `LANG`, first one set) isn't
+/// UTF-8.
+pub fn markers(ascii: bool) -> Markers {
+    let dumb_terminal = env::var("TERM").is_ok_and(|term| term == "dumb");
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty());
+    let non_utf8_locale = locale.is_some_and(|locale| {
+        let locale = locale.to_lowercase();
+        !locale.contains("utf-8") && !locale.contains("utf8")
+    });
+    if ascii || dumb_terminal || non_utf8_locale {
+        ASCII_MARKERS
+    } else {
+        UNICODE_MARKERS
+    }
+}
+
+/// How task ids are rendered in `list`, `show` and `recent`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum IdFormat {
+    /// 7
+    #[default]
+    Decimal,
+    /// 007, padded to the width of the largest id
+    Padded,
+    /// x1f, lowercase hexadecimal
+    Hex,
+}
+
+pub fn format_id(id: usize, format: IdFormat, max_id: usize) -> String {
+    match format {
+        IdFormat::Decimal => id.to_string(),
+        IdFormat::Padded => {
+            let width = max_id.to_string().len();
+            format!("{:0width$}", id, width = width)
+        }
+        IdFormat::Hex => format!("x{:x}", id),
+    }
+}
+
+/// Descriptions are never cut shorter than this, even on very narrow terminals.
+const MIN_TRUNCATED_WIDTH: usize = 10;
+
+/// Best-effort terminal width: `$COLUMNS` when stdout is a terminal, falling
+/// back to 80 columns. Returns `None` when stdout is not a terminal, so piped
+/// output is never truncated.
+pub fn terminal_width() -> Option<usize> {
+    if !io::stdout().is_terminal() {
+        return None;
+    }
+    let columns = env::var("COLUMNS").ok().and_then(|c| c.parse().ok());
+    Some(columns.unwrap_or(80))
+}
+
+/// Characters that take two terminal columns: East Asian wide and
+/// fullwidth forms, and emoji shown as pictures.
+const WIDE_RANGES: &[(u32, u32)] = &[
+    (0x1100, 0x115F),
+    (0x231A, 0x231B),
+    (0x23E9, 0x23EC),
+    (0x23F0, 0x23F0),
+    (0x23F3, 0x23F3),
+    (0x25FD, 0x25FE),
+    (0x2614, 0x2615),
+    (0x2648, 0x2653),
+    (0x267F, 0x267F),
+    (0x2693, 0x2693),
+    (0x26A1, 0x26A1),
+    (0x26AA, 0x26AB),
+    (0x26BD, 0x26BE),
+    (0x26C4, 0x26C5),
+    (0x26CE, 0x26CE),
+    (0x26D4, 0x26D4),
+    (0x26EA, 0x26EA),
+    (0x26F2, 0x26F3),
+    (0x26F5, 0x26F5),
+    (0x26FA, 0x26FA),
+    (0x26FD, 0x26FD),
+    (0x2705, 0x2705),
+    (0x270A, 0x270B),
+    (0x2728, 0x2728),
+    (0x274C, 0x274C),
+    (0x274E, 0x274E),
+    (0x2753, 0x2755),
+    (0x2757, 0x2757),
+    (0x2795, 0x2797),
+    (0x27B0, 0x27B0),
+    (0x27BF, 0x27BF),
+    (0x2B1B, 0x2B1C),
+    (0x2B50, 0x2B50),
+    (0x2B55, 0x2B55),
+    (0x2E80, 0x303E),
+    (0x3041, 0x33FF),
+    (0x3400, 0x4DBF),
+    (0x4E00, 0x9FFF),
+    (0xA000, 0xA4CF),
+    (0xA960, 0xA97F),
+    (0xAC00, 0xD7A3),
+    (0xF900, 0xFAFF),
+    (0xFE10, 0xFE19),
+    (0xFE30, 0xFE6F),
+    (0xFF00, 0xFF60),
+    (0xFFE0, 0xFFE6),
+    (0x1F004, 0x1F004),
+    (0x1F0CF, 0x1F0CF),
+    (0x1F18E, 0x1F18E),
+    (0x1F191, 0x1F19A),
+    (0x1F200, 0x1F251),
+    (0x1F300, 0x1F64F),
+    (0x1F680, 0x1F6FF),
+    (0x1F7E0, 0x1F7EB),
+    (0x1F900, 0x1F9FF),
+    (0x1FA70, 0x1FAFF),
+    (0x20000, 0x2FFFD),
+    (0x30000, 0x3FFFD),
+];
+
+/// Characters that take no column of their own: combining marks, zero-width
+/// spaces and joiners, and variation selectors.
+const ZERO_WIDTH_RANGES: &[(u32, u32)] = &[
+    (0x0300, 0x036F),
+    (0x1AB0, 0x1AFF),
+    (0x1DC0, 0x1DFF),
+    (0x200B, 0x200F),
+    (0x20D0, 0x20FF),
+    (0xFE00, 0xFE0F),
+    (0xFE20, 0xFE2F),
+    (0xE0100, 0xE01EF),
+];
+
+fn in_ranges(c: char, ranges: &[(u32, u32)]) -> bool {
+    let c = c as u32;
+    ranges
+        .binary_search_by(|&(start, end)| {
+            if end < c {
+                std::cmp::Ordering::Less
+            } else if start > c {
+                std::cmp::Ordering::Greater
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+        .is_ok()
+}
+
+/// Terminal columns taken by `c`.
+fn char_width(c: char) -> usize {
+    if c.is_control() || in_ranges(c, ZERO_WIDTH_RANGES) {
+        0
+    } else if in_ranges(c, WIDE_RANGES) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Terminal columns taken by `text`, counting CJK characters and emoji as
+/// two columns, unlike `chars().count()`.
+pub fn width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// `text` followed by enough spaces to fill `columns` terminal columns.
+/// `format!("{:<n$}")` can't be used, as it pads by character count.
+pub fn pad(text: &str, columns: usize) -> String {
+    let padding = columns.saturating_sub(width(text));
+    format!("{}{}", text, " ".repeat(padding))
+}
+
+/// Shortens `text` to at most `max_width` terminal columns, ending in
+/// `ellipsis` when anything was cut.
+pub fn truncate(text: &str, max_width: usize, ellipsis: &str) -> String {
+    let max_width = max_width.max(MIN_TRUNCATED_WIDTH);
+    if width(text) <= max_width {
+        return text.to_string();
+    }
+    let budget = max_width - width(ellipsis);
+    let mut truncated = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        used += char_width(c);
+        if used > budget {
+            break;
+        }
+        truncated.push(c);
+    }
+    truncated.push_str(ellipsis);
+    truncated
+}
+
+/// Breaks `text` into lines of at most `max_width` terminal columns, at
+/// spaces where possible. Words longer than a line are split.
+pub fn wrap(text: &str, max_width: usize) -> Vec<String> {
+    let max_width = max_width.max(MIN_TRUNCATED_WIDTH);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let line_width = width(&line);
+        let word_width = width(word);
+        if line_width > 0 && line_width + 1 + word_width <= max_width {
+            line.push(' ');
+            line.push_str(word);
+            continue;
+        }
+        if line_width > 0 {
+            lines.push(std::mem::take(&mut line));
+        }
+        let mut used = 0;
+        for c in word.chars() {
+            if used + char_width(c) > max_width {
+                lines.push(std::mem::take(&mut line));
+                used = 0;
+            }
+            used += char_width(c);
+            line.push(c);
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Makes `text` a clickable link to `url` with the OSC 8 escape sequence,
+/// which terminals without support print as plain `text`.
+pub fn hyperlink(text: &str, url: Option<&str>, enabled: bool) -> String {
+    match url {
+        Some(url) if enabled => format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text),
+        _ => text.to_string(),
+    }
+}
+
+pub fn paint(text: &str, color: &str, enabled: bool) -> String {
+    if enabled {
+        format!("{}{}{}", color, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Renders the magnitude of `duration` in its largest whole unit, e.g. "3h" or "2d".
+pub fn humanize(duration: Duration) -> String {
+    let duration = duration.abs();
+    if duration.num_days() > 0 {
+        format!("{}d", duration.num_days())
+    } else if duration.num_hours() > 0 {
+        format!("{}h", duration.num_hours())
+    } else {
+        format!("{}m", duration.num_minutes())
+    }
+}
+
+/// Renders `duration` in hours and minutes, e.g. "1h 25m", or just "25m"
+/// under an hour.
+pub fn hours_minutes(duration: Duration) -> String {
+    let minutes = duration.num_minutes().max(0);
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}h {}m", minutes / 60, minutes % 60)
+    }
+}
+
+/// Describes `dt` relative to `now` in words, e.g. "in 3 days" or
+/// "2 hours ago", using the largest whole unit.
+pub fn relative(dt: DateTime<FixedOffset>, now: DateTime<FixedOffset>) -> String {
+    let delta = dt - now;
+    let magnitude = delta.abs();
+    let (amount, unit) = if magnitude.num_days() > 0 {
+        (magnitude.num_days(), "day")
+    } else if magnitude.num_hours() > 0 {
+        (magnitude.num_hours(), "hour")
+    } else if magnitude.num_minutes() > 0 {
+        (magnitude.num_minutes(), "minute")
+    } else {
+        return "just now".to_string();
+    };
+    let plural = if amount == 1 { "" } else { "s" };
+    if delta > Duration::zero() {
+        format!("in {} {}{}", amount, unit, plural)
+    } else {
+        format!("{} {}{} ago", amount, unit, plural)
+    }
+}
+
+/// IST is the only zone tasks are created in, so it is the only offset shown
+/// by name. Anything else (e.g. hand-edited files) is shown numerically.
+const IST_OFFSET_SECS: i32 = 5 * 3600 + 30 * 60;
+
+/// How `format_datetime` writes dates and times, picked by `--locale` or the
+/// `locale` setting. There is no locale data to draw on, so a small table
+/// covers common conventions; month names only appear for English, the one
+/// language chrono names them in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DatePattern(&'static str);
+
+impl Default for DatePattern {
+    fn default() -> Self {
+        DatePattern::ISO
+    }
+}
+
+/// (language or language_REGION, pattern), most specific first.
+const LOCALE_PATTERNS: [(&str, &str); 12] = [
+    ("en_US", "%b %-d, %Y %-I:%M %p"),
+    ("en_CA", "%Y-%m-%d %H:%M"),
+    ("en", "%-d %b %Y %H:%M"),
+    ("de", "%d.%m.%Y %H:%M"),
+    ("ru", "%d.%m.%Y %H:%M"),
+    ("pl", "%d.%m.%Y %H:%M"),
+    ("fr", "%d/%m/%Y %H:%M"),
+    ("es", "%d/%m/%Y %H:%M"),
+    ("it", "%d/%m/%Y %H:%M"),
+    ("nl", "%d-%m-%Y %H:%M"),
+    ("ja", "%Y/%m/%d %H:%M"),
+    ("zh", "%Y/%m/%d %H:%M"),
+];
+
+impl DatePattern {
+    pub const ISO: DatePattern = DatePattern("%Y-%m-%d %H:%M");
+
+    /// The pattern for `locale`: `iso`, `system` (from `LC_ALL`, `LC_TIME`
+    /// or `LANG`, ISO when unset or not in the table), or a locale name such
+    /// as `de`, `en-GB` or `fr_FR.UTF-8`.
+    pub fn for_locale(locale: &str) -> Result<DatePattern, String> {
+        match locale {
+            "iso" => Ok(DatePattern::ISO),
+            "system" => {
+                let system = ["LC_ALL", "LC_TIME", "LANG"]
+                    .iter()
+                    .filter_map(|name| env::var(name).ok())
+                    .find(|value| !value.is_empty());
+                Ok(system.and_then(|name| lookup(&name)).unwrap_or(DatePattern::ISO))
+            }
+            _ => lookup(locale).ok_or_else(|| {
+                let known: Vec<&str> = LOCALE_PATTERNS.iter().map(|(name, _)| *name).collect();
+                format!(
+                    "Unknown locale '{}'. Use iso, system or one of: {}.",
+                    locale,
+                    known.join(", ")
+                )
+            }),
+        }
+    }
+}
+
+fn lookup(locale: &str) -> Option<DatePattern> {
+    // "fr_FR.UTF-8@euro" -> "fr_FR"; "en-GB" -> "en_GB".
+    let name = locale.split(['.', '@']).next()?.replace('-', "_");
+    let language = name.split('_').next()?;
+    LOCALE_PATTERNS
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(&name))
+        .or_else(|| LOCALE_PATTERNS.iter().find(|(known, _)| known.eq_ignore_ascii_case(language)))
+        .map(|(_, pattern)| DatePattern(pattern))
+}
+
+static DATE_PATTERN: OnceLock<DatePattern> = OnceLock::new();
+
+/// Chooses the pattern `format_datetime` uses from now on; only the first
+/// call counts.
+pub fn set_date_pattern(pattern: DatePattern) {
+    let _ = DATE_PATTERN.set(pattern);
+}
+
+/// The strftime pattern `format_datetime` uses.
+pub fn date_pattern() -> &'static str {
+    DATE_PATTERN.get().copied().unwrap_or_default().0
+}
+
+static DISPLAY_OFFSET: OnceLock<FixedOffset> = OnceLock::new();
+
+/// Chooses the offset `format_datetime` shows times in from now on, as with
+/// `--display-tz`; only the first call counts.
+pub fn set_display_offset(offset: FixedOffset) {
+    let _ = DISPLAY_OFFSET.set(offset);
+}
+
+/// The offset set with `set_display_offset`, if any; otherwise times are
+/// shown in the offset they are stored in.
+pub fn display_offset() -> Option<FixedOffset> {
+    DISPLAY_OFFSET.get().copied()
+}
+
+/// A date and time for people to read, in the chosen `DatePattern` and
+/// display offset. Input is always read as ISO, whatever this prints.
+pub fn format_datetime(dt: DateTime<FixedOffset>) -> String {
+    let pattern = date_pattern();
+    let dt = display_offset().map_or(dt, |offset| dt.with_timezone(&offset));
+    if dt.offset().local_minus_utc() == IST_OFFSET_SECS {
+        format!("{} IST", dt.format(pattern))
+    } else {
+        format!("{} {}", dt.format(pattern), dt.format("%:z"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn width_counts_cjk_and_emoji_as_two_columns() {
+        assert_eq!(width("plain"), 5);
+        assert_eq!(width("日本語"), 6);
+        assert_eq!(width("할 일"), 5);
+        assert_eq!(width("ship 🚀"), 7);
+        assert_eq!(width("done ✅"), 7);
+        // A combining accent and a variation selector take no column.
+        assert_eq!(width("cafe\u{301}"), 4);
+        assert_eq!(width("\u{2764}\u{FE0F}"), 1);
+    }
+
+    #[test]
+    fn pad_aligns_mixed_scripts_to_the_same_column() {
+        let rows = ["Buy milk", "牛乳を買う", "🚀 launch", "résumé"];
+        let padded: Vec<String> = rows.iter().map(|row| pad(row, 12) + "|").collect();
+        for line in &padded {
+            assert_eq!(width(line), 13, "{:?}", line);
+        }
+        assert_eq!(padded[1], "牛乳を買う  |");
+    }
+
+    #[test]
+    fn truncate_and_wrap_stay_within_the_width() {
+        let text = "日本語のタスクの説明はとても長い";
+        let cut = truncate(text, 11, "…");
+        assert_eq!(cut, "日本語のタ…");
+        assert!(width(&cut) <= 11);
+        assert_eq!(truncate("短い", 11, "…"), "短い");
+
+        for line in wrap("絵文字 🎉🎉🎉🎉🎉🎉 and 中文字符串很长很长", 10) {
+            assert!(width(&line) <= 10, "{:?}", line);
+        }
+    }
+}
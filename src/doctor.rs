@@ -0,0 +1,186 @@
+use crate::task::Task;
+use chrono::{DateTime, Duration, FixedOffset};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Due dates further than this from now are reported as suspicious.
+const FAR_DAYS: i64 = 10 * 365;
+
+const DATE_FIELDS: [&str; 4] = ["due_date", "completed_at", "created_at", "updated_at"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The file is wrong and commands may misbehave on it.
+    Error,
+    /// Worth a look, but nothing is broken.
+    Warning,
+    /// A change `repair` made.
+    Fixed,
+}
+
+/// One problem found in a task file, or one change made repairing it. The
+/// codes are stable, for `doctor --json` consumers.
+#[derive(Debug, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<u64>,
+    /// `repair` knows how to fix it.
+    #[serde(skip)]
+    pub fixable: bool,
+}
+
+fn finding(
+    (severity, code): (Severity, &'static str),
+    message: String,
+    task: &Value,
+    fixable: bool,
+) -> Finding {
+    Finding {
+        severity,
+        code,
+        message,
+        task_id: task_id(task),
+        fixable,
+    }
+}
+
+const NOT_A_TASK_LIST: (Severity, &str) = (Severity::Error, "NOT_A_TASK_LIST");
+const NOT_A_TASK: (Severity, &str) = (Severity::Error, "NOT_A_TASK");
+const MISSING_ID: (Severity, &str) = (Severity::Error, "MISSING_ID");
+const REUSED_ID: (Severity, &str) = (Severity::Error, "REUSED_ID");
+const UNREADABLE_DATE: (Severity, &str) = (Severity::Error, "UNREADABLE_DATE");
+const FAR_DUE_DATE: (Severity, &str) = (Severity::Warning, "FAR_DUE_DATE");
+const MISSING_DEPENDENCY: (Severity, &str) = (Severity::Warning, "MISSING_DEPENDENCY");
+const UNLOADABLE: (Severity, &str) = (Severity::Error, "UNLOADABLE");
+const RENUMBERED: (Severity, &str) = (Severity::Fixed, "RENUMBERED");
+const DROPPED_DEPENDENCY: (Severity, &str) = (Severity::Fixed, "DROPPED_DEPENDENCY");
+
+fn task_id(task: &Value) -> Option<u64> {
+    task.get("id").and_then(Value::as_u64)
+}
+
+fn label(position: usize, task: &Value) -> String {
+    match task_id(task) {
+        Some(id) => format!("task {} (entry {})", id, position),
+        None => format!("entry {}", position),
+    }
+}
+
+fn depends_on(task: &Value) -> Vec<u64> {
+    task.get("depends_on")
+        .and_then(Value::as_array)
+        .map(|ids| ids.iter().filter_map(Value::as_u64).collect())
+        .unwrap_or_default()
+}
+
+/// The finding for a file that isn't a JSON array at all, which `examine`
+/// can't look into.
+pub fn not_a_task_list(message: String) -> Finding {
+    finding(NOT_A_TASK_LIST, message, &Value::Null, false)
+}
+
+/// Checks the raw entries of a task file, working on JSON values rather than
+/// `Task`s so that files which no longer load can still be examined.
+pub fn examine(tasks: &[Value], now: DateTime<FixedOffset>) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let ids: HashSet<u64> = tasks.iter().filter_map(task_id).collect();
+    let mut seen = HashSet::new();
+    // Set once a problem is found that by itself stops the file loading.
+    let mut unloadable = false;
+    for (position, task) in tasks.iter().enumerate() {
+        let name = label(position, task);
+        if !task.is_object() {
+            let message = format!("{} is not a task object", name);
+            findings.push(finding(NOT_A_TASK, message, task, false));
+            unloadable = true;
+            continue;
+        }
+        match task_id(task) {
+            None => {
+                let message = format!("{} has no valid id", name);
+                findings.push(finding(MISSING_ID, message, task, false));
+                unloadable = true;
+            }
+            Some(id) if !seen.insert(id) => {
+                findings.push(finding(REUSED_ID, format!("{} reuses id {}", name, id), task, true));
+            }
+            Some(_) => {}
+        }
+        for field in DATE_FIELDS {
+            let Some(value) = task.get(field).filter(|v| !v.is_null()) else {
+                continue;
+            };
+            let parsed = value.as_str().map(DateTime::parse_from_rfc3339);
+            match (field, parsed) {
+                ("due_date", Some(Ok(due))) if (due - now).abs() > Duration::days(FAR_DAYS) => {
+                    let message = format!("{} is due on {}, far from today", name, due);
+                    findings.push(finding(FAR_DUE_DATE, message, task, false));
+                }
+                (_, Some(Ok(_))) => {}
+                _ => {
+                    let message = format!("{} has an unreadable {}", name, field);
+                    findings.push(finding(UNREADABLE_DATE, message, task, false));
+                    unloadable = true;
+                }
+            }
+        }
+        for dependency in depends_on(task).into_iter().filter(|id| !ids.contains(id)) {
+            let message = format!("{} depends on missing task {}", name, dependency);
+            findings.push(finding(MISSING_DEPENDENCY, message, task, true));
+        }
+    }
+    if !unloadable
+        && let Err(e) = serde_json::from_value::<Vec<Task>>(Value::from(tasks.to_vec()))
+    {
+        let message = format!("the file does not load: {}", e);
+        findings.push(finding(UNLOADABLE, message, &Value::Null, false));
+    }
+    findings
+}
+
+/// Fixes what `examine` marks fixable: gives tasks that reuse an id the next
+/// free ids and drops `depends_on` entries naming missing tasks. Returns the
+/// changes made, as `Fixed` findings.
+pub fn repair(tasks: &mut [Value]) -> Vec<Finding> {
+    let mut fixed = Vec::new();
+    // Dependencies are pruned first, so none can end up naming a fresh id.
+    let ids: HashSet<u64> = tasks.iter().filter_map(task_id).collect();
+    for (position, task) in tasks.iter_mut().enumerate() {
+        let missing: Vec<u64> =
+            depends_on(task).into_iter().filter(|id| !ids.contains(id)).collect();
+        if missing.is_empty() {
+            continue;
+        }
+        if let Some(dependencies) = task.get_mut("depends_on").and_then(Value::as_array_mut) {
+            dependencies.retain(|id| id.as_u64().is_some_and(|id| ids.contains(&id)));
+        }
+        for dependency in missing {
+            let message = format!(
+                "dropped the dependency of {} on missing task {}",
+                label(position, task),
+                dependency
+            );
+            fixed.push(finding(DROPPED_DEPENDENCY, message, task, false));
+        }
+    }
+    // `None` once ids run out; further duplicates are then left as they are.
+    let mut next_id = ids.iter().max().map_or(Some(0), |max| max.checked_add(1));
+    let mut seen = HashSet::new();
+    for (position, task) in tasks.iter_mut().enumerate() {
+        if let Some(id) = task_id(task)
+            && !seen.insert(id)
+            && let Some(fresh) = next_id
+        {
+            task["id"] = Value::from(fresh);
+            next_id = fresh.checked_add(1);
+            let message = format!("gave entry {}, which reused id {}, id {}", position, id, fresh);
+            fixed.push(finding(RENUMBERED, message, task, false));
+        }
+    }
+    fixed
+}
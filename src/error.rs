@@ -0,0 +1,225 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Everything that can go wrong while running a command. Each variant has a
+/// stable machine-readable code for `--error-format json`.
+#[derive(Debug)]
+pub enum TodoError {
+    InvalidIndex(usize),
+    InvalidId(usize),
+    InvalidSubtaskIndex { parent_id: usize, index: usize },
+    InvalidDate(String),
+    InvalidDuration(String),
+    DescriptionTooLong { length: usize, max: usize },
+    EmptyDescription,
+    /// `add` was run without any description to add.
+    MissingDescription,
+    EmptyNote,
+    DuplicateId(usize),
+    /// An `import`ed export lists two tasks under the same id.
+    DuplicateImportId(usize),
+    IdTaken(usize),
+    IdsExhausted,
+    DependencyCycle(Vec<usize>),
+    Locked(usize),
+    NoExactMatch(String),
+    AmbiguousMatch { description: String, ids: Vec<usize> },
+    UnsupportedFormat { format: String, commands: &'static str },
+    InvalidField(String),
+    /// A tag, context or project name that is empty or has characters
+    /// names can't hold.
+    InvalidName { kind: &'static str, name: String },
+    BulkEdit(String),
+    /// `--assignee me` was given, but neither the config nor the
+    /// environment says who that is.
+    UnknownUser,
+    ConfigNotFound(PathBuf),
+    /// `restore-backup` was given a name `backups` doesn't list.
+    BackupNotFound(String),
+    DoctorErrors(usize),
+    Parse(serde_json::Error),
+    Io(io::Error),
+}
+
+impl TodoError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            TodoError::InvalidIndex(_) => "INVALID_INDEX",
+            TodoError::InvalidId(_) => "INVALID_ID",
+            TodoError::InvalidSubtaskIndex { .. } => "INVALID_SUBTASK_INDEX",
+            TodoError::InvalidDate(_) => "INVALID_DATE",
+            TodoError::InvalidDuration(_) => "INVALID_DURATION",
+            TodoError::DescriptionTooLong { .. } => "DESCRIPTION_TOO_LONG",
+            TodoError::EmptyDescription => "EMPTY_DESCRIPTION",
+            TodoError::MissingDescription => "MISSING_DESCRIPTION",
+            TodoError::EmptyNote => "EMPTY_NOTE",
+            TodoError::DuplicateId(_) => "DUPLICATE_ID",
+            TodoError::DuplicateImportId(_) => "DUPLICATE_IMPORT_ID",
+            TodoError::IdTaken(_) => "ID_TAKEN",
+            TodoError::IdsExhausted => "IDS_EXHAUSTED",
+            TodoError::DependencyCycle(_) => "DEPENDENCY_CYCLE",
+            TodoError::Locked(_) => "TASK_LOCKED",
+            TodoError::NoExactMatch(_) => "NO_MATCH",
+            TodoError::AmbiguousMatch { .. } => "AMBIGUOUS_MATCH",
+            TodoError::UnsupportedFormat { .. } => "UNSUPPORTED_FORMAT",
+            TodoError::InvalidField(_) => "INVALID_FIELD",
+            TodoError::InvalidName { .. } => "INVALID_NAME",
+            TodoError::BulkEdit(_) => "BULK_EDIT_INVALID",
+            TodoError::UnknownUser => "UNKNOWN_USER",
+            TodoError::ConfigNotFound(_) => "CONFIG_NOT_FOUND",
+            TodoError::BackupNotFound(_) => "BACKUP_NOT_FOUND",
+            TodoError::DoctorErrors(_) => "DOCTOR_ERRORS",
+            TodoError::Parse(_) => "PARSE_ERROR",
+            TodoError::Io(_) => "IO_ERROR",
+        }
+    }
+}
+
+impl fmt::Display for TodoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TodoError::InvalidIndex(index) => write!(
+                f,
+                "Invalid task index: {}. Use `list` to see available tasks.",
+                index
+            ),
+            TodoError::InvalidId(id) => write!(
+                f,
+                "Invalid task id: {}. Use `list` to see available tasks.",
+                id
+            ),
+            TodoError::InvalidSubtaskIndex { parent_id, index } => write!(
+                f,
+                "Invalid subtask index: {}. Use `show {}` to see its checklist.",
+                index, parent_id
+            ),
+            TodoError::InvalidDate(message)
+            | TodoError::InvalidDuration(message)
+            | TodoError::InvalidField(message) => {
+                write!(f, "{}", message)
+            }
+            TodoError::DescriptionTooLong { length, max } => write!(
+                f,
+                "Description is {} characters long; the limit is {}. Shorten it or pass --truncate.",
+                length, max
+            ),
+            TodoError::InvalidName { kind, name } if name.is_empty() => {
+                write!(f, "{} name can't be empty.", kind)
+            }
+            TodoError::InvalidName { kind, name } => write!(
+                f,
+                "{} name '{}' isn't allowed. Use letters, digits, '-', '_', '.' and '/' only.",
+                kind, name
+            ),
+            TodoError::EmptyDescription => write!(f, "Description can't be empty."),
+            TodoError::MissingDescription => write!(
+                f,
+                "No task description given. Put it in quotes before or after the options, \
+                 e.g. `add \"Buy milk\" --due 2024-06-01`."
+            ),
+            TodoError::EmptyNote => write!(f, "Note can't be empty."),
+            TodoError::DuplicateId(id) => write!(
+                f,
+                "Several tasks share id {}. Fix the task file, or run without --strict to renumber them.",
+                id
+            ),
+            TodoError::DuplicateImportId(id) => write!(
+                f,
+                "Several tasks in the imported file share id {}, so it's unclear which one \
+                 their dependencies mean. Give each task its own id and import again.",
+                id
+            ),
+            TodoError::IdTaken(id) => write!(
+                f,
+                "Id {} already belongs to another task. Pick an unused one.",
+                id
+            ),
+            TodoError::IdsExhausted => write!(
+                f,
+                "No task ids are left; the task file already uses the highest ones. \
+                 Renumber its tasks to free some up."
+            ),
+            TodoError::DependencyCycle(path) => {
+                let path: Vec<String> = path.iter().map(|id| id.to_string()).collect();
+                write!(
+                    f,
+                    "That would create a dependency cycle: {}.",
+                    path.join(" -> ")
+                )
+            }
+            TodoError::Locked(id) => write!(
+                f,
+                "Task {} is locked. Run `unlock {}` first, or pass --force.",
+                id, id
+            ),
+            TodoError::NoExactMatch(description) => {
+                write!(f, "No pending task is described exactly \"{}\".", description)
+            }
+            TodoError::AmbiguousMatch { description, ids } => {
+                let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+                write!(
+                    f,
+                    "Several pending tasks are described \"{}\": ids {}. Use `done --id` instead.",
+                    description,
+                    ids.join(", ")
+                )
+            }
+            TodoError::UnsupportedFormat { format, commands } => {
+                write!(f, "--format {} is only supported by {}.", format, commands)
+            }
+            TodoError::BulkEdit(message) => {
+                write!(f, "Could not apply the edit, {}. Nothing was changed.", message)
+            }
+            TodoError::UnknownUser => write!(
+                f,
+                "Can't tell who 'me' is. Set \"user\" in the config, or $USER."
+            ),
+            TodoError::ConfigNotFound(path) => {
+                write!(f, "Config file {} does not exist.", path.display())
+            }
+            TodoError::BackupNotFound(name) => {
+                write!(f, "No backup named '{}'. Run `backups` to see them.", name)
+            }
+            TodoError::DoctorErrors(count) => {
+                write!(f, "{} error(s) in the task file still need fixing.", count)
+            }
+            TodoError::Parse(e) => write!(f, "Could not parse JSON: {}", e),
+            TodoError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for TodoError {}
+
+impl From<io::Error> for TodoError {
+    fn from(e: io::Error) -> Self {
+        TodoError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for TodoError {
+    fn from(e: serde_json::Error) -> Self {
+        TodoError::Parse(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum ErrorFormat {
+    /// Plain "Error: ..." lines
+    #[default]
+    Human,
+    /// One `{"error": ..., "code": ...}` object per error
+    Json,
+}
+
+/// Writes `error` to stderr in the requested format.
+pub fn report(error: &TodoError, format: ErrorFormat) {
+    match format {
+        ErrorFormat::Human => eprintln!("Error: {}", error),
+        ErrorFormat::Json => eprintln!(
+            "{}",
+            serde_json::json!({ "error": error.to_string(), "code": error.code() })
+        ),
+    }
+}
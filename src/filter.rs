@@ -0,0 +1,108 @@
+use crate::regex::Regex;
+use crate::task::{self, Task, TaskStatus};
+use chrono::{DateTime, FixedOffset, NaiveDate};
+
+/// Criteria shared by every command that selects a subset of tasks. An empty
+/// filter matches everything.
+#[derive(Debug, Default, Clone)]
+pub struct TaskFilter {
+    pub status: Option<TaskStatus>,
+    /// Only pending tasks that have no due date and aren't marked someday.
+    pub no_due: bool,
+    /// Only pending tasks marked someday.
+    pub someday: bool,
+    /// Tasks must carry every one of these tags.
+    pub tags: Vec<String>,
+    /// Tasks must carry none of these tags.
+    pub not_tags: Vec<String>,
+    /// Tasks must have every one of these contexts.
+    pub contexts: Vec<String>,
+    /// Only tasks in this project.
+    pub project: Option<String>,
+    /// Only tasks assigned to this person.
+    pub assignee: Option<String>,
+    /// Tasks created strictly before this moment. Tasks without a creation
+    /// time never match while this is set.
+    pub created_before: Option<DateTime<FixedOffset>>,
+    /// Tasks created at or after this moment, likewise.
+    pub created_after: Option<DateTime<FixedOffset>>,
+    /// Only tasks with an id greater than this, i.e. added since a sync
+    /// client last saw this id.
+    pub since_id: Option<usize>,
+    /// Only tasks whose description matches this pattern.
+    pub regex: Option<Regex>,
+    /// Only tasks completed on this day, in IST.
+    pub completed_on: Option<NaiveDate>,
+    /// Only pending tasks due strictly before this moment.
+    pub overdue_before: Option<DateTime<FixedOffset>>,
+    /// `Some(true)` keeps only the tasks still waiting on their `--wait`
+    /// date, `Some(false)` leaves them out.
+    pub waiting: Option<bool>,
+}
+
+impl TaskFilter {
+    pub fn matches(&self, task: &Task) -> bool {
+        if let Some(status) = &self.status
+            && task.status != *status
+        {
+            return false;
+        }
+        if self.no_due && (!task.is_pending() || task.due_date.is_some() || task.someday) {
+            return false;
+        }
+        if self.someday && (!task.is_pending() || !task.someday) {
+            return false;
+        }
+        if !self.tags.iter().all(|tag| task.has_tag(tag)) {
+            return false;
+        }
+        if self.not_tags.iter().any(|tag| task.has_tag(tag)) {
+            return false;
+        }
+        if !self.contexts.iter().all(|context| task.has_context(context)) {
+            return false;
+        }
+        if self.project.is_some() && task.project != self.project {
+            return false;
+        }
+        if self.assignee.is_some() && task.assignee != self.assignee {
+            return false;
+        }
+        if let Some(before) = self.created_before
+            && task.created_at.is_none_or(|created| created >= before)
+        {
+            return false;
+        }
+        if let Some(after) = self.created_after
+            && task.created_at.is_none_or(|created| created < after)
+        {
+            return false;
+        }
+        if self.since_id.is_some_and(|since| task.id <= since) {
+            return false;
+        }
+        if self.regex.as_ref().is_some_and(|regex| !regex.is_match(&task.description)) {
+            return false;
+        }
+        if let Some(day) = self.completed_on
+            && task.completed_at.is_none_or(|at| at.with_timezone(&task::ist()).date_naive() != day)
+        {
+            return false;
+        }
+        if let Some(cutoff) = self.overdue_before
+            && (!task.is_pending() || task.due_date.is_none_or(|due| due >= cutoff))
+        {
+            return false;
+        }
+        if let Some(waiting) = self.waiting
+            && task.is_waiting(task::now_ist()) != waiting
+        {
+            return false;
+        }
+        true
+    }
+
+    pub fn apply<'a>(&self, tasks: &'a [Task]) -> Vec<&'a Task> {
+        tasks.iter().filter(|t| self.matches(t)).collect()
+    }
+}
@@ -0,0 +1,39 @@
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The task `focus` is working on and since when, kept next to the task file
+/// so it survives between runs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Focus {
+    pub id: usize,
+    pub since: DateTime<FixedOffset>,
+}
+
+impl Focus {
+    pub fn path_for(task_file: &Path) -> PathBuf {
+        task_file.with_extension("focus.json")
+    }
+
+    /// The recorded focus, or `None` when nothing is focused.
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let data = fs::read_to_string(path)?;
+        Ok(Some(serde_json::from_str(&data)?))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn clear(path: &Path) -> io::Result<()> {
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use crate::sort::Nulls;
+use crate::task::Task;
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate};
+use serde::Deserialize;
+
+/// Default label for tasks that have no due date and so fall outside every
+/// bucket.
+pub const SOMEDAY_LABEL: &str = "Someday";
+
+/// Buckets of `--group-by due-relative`, in display order. "Earlier" holds
+/// done tasks that were due before today, which are not overdue.
+const RELATIVE_LABELS: [&str; 6] =
+    ["Earlier", "Overdue", "Today", "Tomorrow", "This Week", "Later"];
+
+/// First day of the week for `--group-by week`, set with `week_starts_on`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekStart {
+    /// ISO weeks
+    #[default]
+    Monday,
+    Sunday,
+}
+
+impl WeekStart {
+    /// Days from the start of the week to `date`.
+    fn days_into_week(self, date: NaiveDate) -> i64 {
+        let weekday = date.weekday();
+        let days = match self {
+            WeekStart::Monday => weekday.num_days_from_monday(),
+            WeekStart::Sunday => weekday.num_days_from_sunday(),
+        };
+        days as i64
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum GroupBy {
+    /// Calendar week of the due date, starting on `week_starts_on`
+    Week,
+    /// Calendar month of the due date
+    Month,
+    /// Agenda buckets relative to now: Overdue, Today, Tomorrow, This Week
+    /// (the rest of the current week) and Later
+    DueRelative,
+}
+
+impl GroupBy {
+    /// The first day of the bucket `date` falls into.
+    fn bucket_start(self, date: NaiveDate, week_start: WeekStart) -> NaiveDate {
+        match self {
+            GroupBy::Week => date - Duration::days(week_start.days_into_week(date)),
+            GroupBy::Month => date.with_day(1).unwrap_or(date),
+            GroupBy::DueRelative => unreachable!("due-relative buckets aren't date ranges"),
+        }
+    }
+
+    fn label(self, start: NaiveDate) -> String {
+        match self {
+            GroupBy::Week => format!("Week of {}", start.format("%Y-%m-%d")),
+            GroupBy::Month => start.format("%B %Y").to_string(),
+            GroupBy::DueRelative => unreachable!("due-relative buckets aren't date ranges"),
+        }
+    }
+}
+
+/// Index into `RELATIVE_LABELS` of the bucket for a task due at `due`.
+fn relative_bucket(
+    task: &Task,
+    due: DateTime<FixedOffset>,
+    now: DateTime<FixedOffset>,
+    grace: Duration,
+    week_start: WeekStart,
+) -> usize {
+    let today = now.date_naive();
+    let date = due.date_naive();
+    let week_end = today + Duration::days(7 - week_start.days_into_week(today));
+    if task.is_overdue(now, grace) {
+        1
+    } else if date < today && !task.is_pending() {
+        0
+    } else if date <= today {
+        2
+    } else if date == today + Duration::days(1) {
+        3
+    } else if date < week_end {
+        4
+    } else {
+        5
+    }
+}
+
+/// The non-empty `RELATIVE_LABELS` buckets for `tasks`, all of which have a
+/// due date. Tasks more than `grace` past due are overdue.
+fn relative_groups<'a>(
+    tasks: &[&'a Task],
+    now: DateTime<FixedOffset>,
+    grace: Duration,
+    week_start: WeekStart,
+) -> Vec<(String, Vec<&'a Task>)> {
+    let mut buckets: [Vec<&'a Task>; 6] = Default::default();
+    for task in tasks {
+        if let Some(due) = task.due_date {
+            buckets[relative_bucket(task, due, now, grace, week_start)].push(*task);
+        }
+    }
+    RELATIVE_LABELS
+        .iter()
+        .zip(buckets)
+        .filter(|(_, bucket)| !bucket.is_empty())
+        .map(|(label, bucket)| (label.to_string(), bucket))
+        .collect()
+}
+
+/// Calendar buckets (weeks or months) for `tasks`, all of which have a due
+/// date, ordered chronologically.
+fn calendar_groups<'a>(
+    tasks: &[&'a Task],
+    group_by: GroupBy,
+    week_start: WeekStart,
+) -> Vec<(String, Vec<&'a Task>)> {
+    let mut dated: Vec<(NaiveDate, Vec<&'a Task>)> = Vec::new();
+    for task in tasks {
+        let Some(due) = task.due_date else {
+            continue;
+        };
+        let start = group_by.bucket_start(due.date_naive(), week_start);
+        match dated.iter_mut().find(|(s, _)| *s == start) {
+            Some((_, bucket)) => bucket.push(*task),
+            None => dated.push((start, vec![*task])),
+        }
+    }
+    dated.sort_by_key(|(start, _)| *start);
+    dated
+        .into_iter()
+        .map(|(start, bucket)| (group_by.label(start), bucket))
+        .collect()
+}
+
+/// Splits `tasks` into labelled buckets ordered chronologically, keeping the
+/// original task order inside each bucket. Tasks without a due date are
+/// collected in a bucket named `someday_label`, placed first or last.
+/// `now` and `grace` place tasks in due-relative buckets.
+pub fn group_tasks<'a>(
+    tasks: &[&'a Task],
+    group_by: GroupBy,
+    now: DateTime<FixedOffset>,
+    grace: Duration,
+    week_start: WeekStart,
+    someday_label: &str,
+    someday_position: Nulls,
+) -> Vec<(String, Vec<&'a Task>)> {
+    let (dated, someday): (Vec<&'a Task>, Vec<&'a Task>) =
+        tasks.iter().partition(|task| task.due_date.is_some());
+    let mut groups = match group_by {
+        GroupBy::DueRelative => relative_groups(&dated, now, grace, week_start),
+        GroupBy::Week | GroupBy::Month => calendar_groups(&dated, group_by, week_start),
+    };
+    if !someday.is_empty() {
+        let bucket = (someday_label.to_string(), someday);
+        match someday_position {
+            Nulls::First => groups.insert(0, bucket),
+            Nulls::Last => groups.push(bucket),
+        }
+    }
+    groups
+}
@@ -0,0 +1,166 @@
+use crate::task::{now_ist, Task};
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Oldest entries are dropped once the log grows past this many operations.
+const MAX_ENTRIES: usize = 100;
+
+/// A change to the task list, recorded with enough data to undo and redo it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Operation {
+    /// Tasks appended to the end of the list.
+    Add { tasks: Vec<Task> },
+    /// Tasks removed from the list, with the position each one occupied,
+    /// in ascending order of position.
+    Delete { removed: Vec<(usize, Task)> },
+    /// Tasks changed in place, matched by id.
+    Update { before: Vec<Task>, after: Vec<Task> },
+    /// Several operations undone and redone as one, applied in order.
+    Batch { operations: Vec<Operation> },
+    /// A task's id changed from `from` to `to`, along with the dependencies
+    /// naming it.
+    Renumber { from: usize, to: usize },
+}
+
+impl Operation {
+    pub fn apply(&self, tasks: &mut Vec<Task>) {
+        match self {
+            Operation::Add { tasks: added } => tasks.extend(added.iter().cloned()),
+            Operation::Delete { removed } => {
+                let ids: Vec<usize> = removed.iter().map(|(_, t)| t.id).collect();
+                tasks.retain(|t| !ids.contains(&t.id));
+            }
+            Operation::Update { after, .. } => replace_by_id(tasks, after),
+            Operation::Batch { operations } => {
+                for operation in operations {
+                    operation.apply(tasks);
+                }
+            }
+            Operation::Renumber { from, to } => renumber(tasks, *from, *to),
+        }
+    }
+
+    pub fn revert(&self, tasks: &mut Vec<Task>) {
+        match self {
+            Operation::Add { tasks: added } => {
+                let ids: Vec<usize> = added.iter().map(|t| t.id).collect();
+                tasks.retain(|t| !ids.contains(&t.id));
+            }
+            Operation::Delete { removed } => {
+                for (position, task) in removed {
+                    let position = (*position).min(tasks.len());
+                    tasks.insert(position, task.clone());
+                }
+            }
+            Operation::Update { before, .. } => replace_by_id(tasks, before),
+            Operation::Batch { operations } => {
+                for operation in operations.iter().rev() {
+                    operation.revert(tasks);
+                }
+            }
+            Operation::Renumber { from, to } => renumber(tasks, *to, *from),
+        }
+    }
+}
+
+/// Gives the task numbered `from` the id `to`, and points dependencies on it
+/// at the new id.
+pub fn renumber(tasks: &mut [Task], from: usize, to: usize) {
+    for task in tasks {
+        if task.id == from {
+            task.id = to;
+        }
+        for id in task.depends_on.iter_mut().filter(|id| **id == from) {
+            *id = to;
+        }
+    }
+}
+
+fn replace_by_id(tasks: &mut [Task], replacements: &[Task]) {
+    for replacement in replacements {
+        if let Some(task) = tasks.iter_mut().find(|t| t.id == replacement.id) {
+            *task = replacement.clone();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub at: DateTime<FixedOffset>,
+    pub summary: String,
+    pub operation: Operation,
+}
+
+/// The operation log kept next to the task file. Entries before `cursor` have
+/// been applied and can be undone; entries from `cursor` on were undone and
+/// can be redone until a new operation is recorded.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct History {
+    entries: Vec<Entry>,
+    cursor: usize,
+}
+
+impl History {
+    pub fn path_for(task_file: &Path) -> PathBuf {
+        task_file.with_extension("history.json")
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        if !path.exists() {
+            return Ok(History::default());
+        }
+        let data = fs::read_to_string(path)?;
+        let mut history: History = serde_json::from_str(&data)?;
+        history.cursor = history.cursor.min(history.entries.len());
+        Ok(history)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn record(&mut self, summary: String, operation: Operation) {
+        self.entries.truncate(self.cursor);
+        self.entries.push(Entry {
+            at: now_ist(),
+            summary,
+            operation,
+        });
+        if self.entries.len() > MAX_ENTRIES {
+            let excess = self.entries.len() - MAX_ENTRIES;
+            self.entries.drain(..excess);
+        }
+        self.cursor = self.entries.len();
+    }
+
+    /// The last `limit` entries, newest first, each with whether it is
+    /// currently undone.
+    pub fn recent(&self, limit: usize) -> Vec<(&Entry, bool)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .rev()
+            .take(limit)
+            .map(|(i, entry)| (entry, i >= self.cursor))
+            .collect()
+    }
+
+    /// Steps back over the most recent applied entry and returns it.
+    pub fn undo(&mut self) -> Option<&Entry> {
+        if self.cursor == 0 {
+            return None;
+        }
+        self.cursor -= 1;
+        self.entries.get(self.cursor)
+    }
+
+    /// Steps forward over the next undone entry and returns it.
+    pub fn redo(&mut self) -> Option<&Entry> {
+        let entry = self.entries.get(self.cursor)?;
+        self.cursor += 1;
+        Some(entry)
+    }
+}
@@ -0,0 +1,84 @@
+use crate::display;
+use crate::task::{Priority, Task};
+use chrono::{DateTime, Duration, FixedOffset};
+
+/// Inlined so the page stands alone, e.g. as an email attachment.
+const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+h1 { font-size: 1.4em; margin-bottom: 0.2em; }
+p.generated { color: #777; margin-top: 0; }
+table { border-collapse: collapse; width: 100%; }
+th, td { text-align: left; padding: 0.4em 0.8em; border-bottom: 1px solid #ddd; }
+th { background: #f4f4f4; }
+tr.done td.description { text-decoration: line-through; color: #888; }
+.priority { font-weight: bold; border-radius: 3px; padding: 0.1em 0.4em; color: #fff; }
+.priority.high { background: #c0392b; }
+.priority.medium { background: #d68910; }
+.priority.low { background: #2e86c1; }
+td.overdue { color: #c0392b; font-weight: bold; }
+";
+
+/// `text` with the characters that are markup in HTML replaced by entities.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn priority_cell(priority: Option<Priority>) -> String {
+    let Some(priority) = priority else {
+        return String::new();
+    };
+    let name = format!("{:?}", priority);
+    format!("<span class=\"priority {}\">{}</span>", name.to_lowercase(), name)
+}
+
+fn row(task: &Task, now: DateTime<FixedOffset>, grace: Duration) -> String {
+    let done = !task.is_pending();
+    let checkbox = format!(
+        "<input type=\"checkbox\" disabled{}>",
+        if done { " checked" } else { "" }
+    );
+    let due_class = if task.is_overdue(now, grace) { " class=\"overdue\"" } else { "" };
+    let due = task.due_date.map(display::format_datetime).unwrap_or_default();
+    let tags: Vec<String> = task.tags.iter().map(|tag| format!("#{}", tag)).collect();
+    format!(
+        "<tr{}><td>{}</td><td>{}</td><td class=\"description\">{}</td><td>{}</td>\
+         <td{}>{}</td><td>{}</td><td>{}</td></tr>",
+        if done { " class=\"done\"" } else { "" },
+        checkbox,
+        task.id,
+        escape(&task.description),
+        priority_cell(task.priority),
+        due_class,
+        escape(&due),
+        escape(&tags.join(" ")),
+        escape(task.project.as_deref().unwrap_or("")),
+    )
+}
+
+/// A standalone HTML page with `tasks` in a table, for sharing a snapshot
+/// with people who don't use the tool. Due dates more than `grace` past are
+/// marked overdue.
+pub fn page(tasks: &[&Task], now: DateTime<FixedOffset>, grace: Duration) -> String {
+    let rows: Vec<String> = tasks.iter().map(|task| row(task, now, grace)).collect();
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Tasks</title>\n<style>\n{}</style>\n</head>\n<body>\n<h1>Tasks</h1>\n\
+         <p class=\"generated\">Generated {}</p>\n<table>\n\
+         <tr><th>Done</th><th>ID</th><th>Description</th><th>Priority</th><th>Due</th>\
+         <th>Tags</th><th>Project</th></tr>\n{}\n</table>\n</body>\n</html>\n",
+        STYLE,
+        escape(&display::format_datetime(now)),
+        rows.join("\n"),
+    )
+}
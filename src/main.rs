@@ -1,83 +1,2489 @@
+mod bulk;
+mod config;
+mod deps;
+mod doctor;
+mod display;
+mod error;
+mod filter;
+mod focus;
+mod group;
+mod history;
+mod html;
 mod manager;
+mod output;
+mod pager;
+mod prompt;
+mod recurrence;
+mod regex;
+mod repl;
+mod schema;
+mod sort;
+mod streak;
+mod table;
 mod task;
+mod todotxt;
+mod urgency;
 
 use clap::{Parser, Subcommand};
-use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone};
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Weekday,
+};
+use std::ffi::OsString;
+use std::io::{self, Read};
+use std::num::IntErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
-use manager::TaskManager;
+use config::Config;
+use display::{ColorChoice, DatePattern, IdFormat};
+use error::{ErrorFormat, TodoError};
+use filter::TaskFilter;
+use group::GroupBy;
+use sort::{Nulls, SortKey, SortSpec};
+use table::Separator;
+use manager::{ListField, ListOptions, SearchQuery, TaskChanges, TaskManager};
+use output::OutputFormat;
+use regex::Regex;
+use recurrence::RepeatRule;
+use task::{Priority, Task, TaskStatus};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
+    /// Left out, `default_command` from the config runs, or `list`
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
+    /// Suppress informational messages such as "Task added successfully."
+    #[arg(long, global = true)]
+    quiet: bool,
+    /// Scripting mode: implies --quiet, prints machine-readable output where
+    /// available and never prompts for input
+    #[arg(long, global = true)]
+    batch: bool,
+    /// Print diagnostics (database path, tasks loaded, file writes) to stderr
+    #[arg(long, global = true)]
+    verbose: bool,
+    /// How to print errors on stderr
+    #[arg(long, value_enum, global = true, default_value_t = ErrorFormat::Human)]
+    error_format: ErrorFormat,
+    /// Output format for list, search, stats and show
+    #[arg(long, value_enum, global = true, default_value_t = OutputFormat::Human)]
+    format: OutputFormat,
+    /// Reject input instead of repairing it: --due without a time, or tasks
+    /// sharing an id in the task file
+    #[arg(long, global = true)]
+    strict: bool,
+    /// Save even if another process changed the task file since it was
+    /// read, discarding that change
+    #[arg(long, global = true)]
+    overwrite: bool,
+    /// Fail with a nonzero exit, changing nothing, when any task index or id
+    /// given doesn't exist, instead of reporting it and going on
+    #[arg(long, global = true)]
+    strict_index: bool,
+    /// When to color output
+    #[arg(long, value_enum, global = true, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+    /// Use plain ASCII markers (e.g. `...` instead of `…`). Also chosen
+    /// automatically when TERM is dumb or the locale isn't UTF-8
+    #[arg(long, global = true)]
+    ascii: bool,
+    /// How to show task ids: decimal, padded (007) or hex (x1f). Overrides
+    /// `id_format` in config.json
+    #[arg(long, value_enum, global = true)]
+    id_format: Option<IdFormat>,
+    /// Task database to use; missing parent directories are created on save
+    #[arg(long, global = true, default_value = "tasks.json")]
+    file: PathBuf,
+    /// Config file to use instead of config.json in the current directory;
+    /// unlike that one, it must exist
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// Show dates the way this locale writes them, e.g. de, en-GB or
+    /// system; `iso` (the default) for YYYY-MM-DD. Input is always ISO
+    #[arg(long, global = true, value_parser = DatePattern::for_locale)]
+    locale: Option<DatePattern>,
+    /// Show times at this UTC offset, e.g. -04:00, rather than in IST.
+    /// Dates are still typed in IST unless they give their own offset
+    #[arg(
+        long,
+        global = true,
+        value_name = "OFFSET",
+        value_parser = parse_offset,
+        allow_hyphen_values = true
+    )]
+    display_tz: Option<FixedOffset>,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
+    /// Add a new task
+    #[command(visible_alias = "a")]
     Add {
-        description: String,
+        /// What to do; give several to add one task for each, sharing the
+        /// options below. Asked for when left out and stdin is a terminal;
+        /// `-` reads one per line from stdin
+        #[arg(value_name = "DESCRIPTION")]
+        descriptions: Vec<String>,
+        /// 'YYYY-MM-DD [HH:MM]', or relative to now like '+3d' or '+2h'
         #[arg(long)]
         due: Option<String>,
+        /// Resolve a relative --due from this date ('YYYY-MM-DD [HH:MM]')
+        /// instead of now
+        #[arg(long, requires = "due", value_parser = parse_timestamp)]
+        relative_to: Option<DateTime<FixedOffset>>,
+        /// Leave the task undated on purpose, as backlog (see `list --someday`)
+        #[arg(long, conflicts_with = "due")]
+        someday: bool,
+        /// Remind this long before the due time, e.g. 30m, 1h, 2d
+        #[arg(long, requires = "due")]
+        remind: Option<String>,
+        /// How long the task should take, e.g. 45m or 2h (see `estimates`)
+        #[arg(long)]
+        estimate: Option<String>,
+        /// Hide the task from `list` and `peek` until this date, in any form
+        /// --due takes
+        #[arg(long, value_name = "DATE")]
+        wait: Option<String>,
+        /// Tag the task (repeat for several tags)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Where or how it gets done, e.g. phone or home (repeat for several)
+        #[arg(long = "context")]
+        contexts: Vec<String>,
+        /// The project the task belongs to (see `projects`)
+        #[arg(long)]
+        project: Option<String>,
+        /// Who is to do it; `me` for yourself (the config's `user` or $USER)
+        #[arg(long)]
+        assignee: Option<String>,
+        #[arg(long, value_enum)]
+        priority: Option<Priority>,
+        /// Id of a task that must be done first (repeat for several)
+        #[arg(long = "depends-on", visible_alias = "after", value_parser = parse_id)]
+        depends_on: Vec<usize>,
+        /// Repeat the task: completing it moves the due date forward. One of
+        /// daily, weekly, monthly or weekdays:mon,wed,fri (plain weekdays is
+        /// Monday to Friday)
+        #[arg(long, value_name = "RULE", value_parser = RepeatRule::parse, requires = "due")]
+        repeat: Option<RepeatRule>,
+        /// Print the first few due dates of the repeating task once added
+        #[arg(long, requires = "repeat")]
+        preview: bool,
+        /// Link the task to a ticket or document, e.g. https://example.com/123
+        #[arg(long, value_parser = parse_url)]
+        url: Option<String>,
+        /// Shorten a description over `max_description_length` instead of
+        /// rejecting it
+        #[arg(long)]
+        truncate: bool,
+        /// Print only the new task's id (also the behavior under --quiet)
+        #[arg(long)]
+        porcelain: bool,
+        /// Print the new task as a JSON object
+        #[arg(long, conflicts_with = "porcelain")]
+        json: bool,
+        /// Add the task even if a pending task has the same description
+        #[arg(long)]
+        force: bool,
+        /// Skip adding, without asking, when a pending task has the same
+        /// description; safe to re-run from setup scripts
+        #[arg(long, conflicts_with = "force")]
+        if_not_exists: bool,
+        /// Record a task that is already finished
+        #[arg(long, conflicts_with = "repeat")]
+        done: bool,
+        /// When it was finished (default now), as an RFC 3339 timestamp or
+        /// 'YYYY-MM-DD [HH:MM]'
+        #[arg(long, requires = "done", value_parser = parse_timestamp)]
+        completed_at: Option<DateTime<FixedOffset>>,
+        /// Take '!' (medium) or '!!' (high) priority, '#tag' tags and
+        /// '@context' contexts out of the description
+        #[arg(long)]
+        shorthand: bool,
+        /// Keep the description as typed even when the `shorthand` setting is
+        /// on
+        #[arg(long, conflicts_with = "shorthand")]
+        literal: bool,
+        /// With `-`, read all of stdin as one description rather than one
+        /// per line
+        #[arg(long)]
+        single: bool,
+    },
+    /// List tasks
+    #[command(visible_alias = "ls")]
+    List {
+        /// Print the tasks as a pretty-printed JSON array
+        #[arg(long)]
+        json: bool,
+        /// Print one JSON object per task per line (newline-delimited JSON)
+        #[arg(long, conflicts_with = "json")]
+        ndjson: bool,
+        /// Comma-separated columns to show, in order, e.g. id,due,description;
+        /// with --json or --ndjson, the task keys to keep
+        #[arg(long, value_enum, value_delimiter = ',')]
+        fields: Option<Vec<ListField>>,
+        /// How to separate the --fields columns
+        #[arg(long, value_enum, default_value_t, requires = "fields")]
+        separator: Separator,
+        /// Fit each line into this many columns instead of the terminal width
+        #[arg(long)]
+        width: Option<usize>,
+        /// Never shorten descriptions (use `show` to see the full text otherwise)
+        #[arg(long, conflicts_with = "width")]
+        no_truncate: bool,
+        /// Wrap long descriptions onto indented lines instead of shortening them
+        #[arg(long, conflicts_with = "no_truncate")]
+        wrap: bool,
+        /// Only show tasks with this status
+        #[arg(long, value_enum)]
+        status: Option<TaskStatus>,
+        /// Hide completed tasks (same as --status pending)
+        #[arg(long, conflicts_with = "status")]
+        no_done: bool,
+        /// Group tasks under headers by the week or month they are due, or
+        /// in agenda buckets such as Today and Tomorrow
+        #[arg(long, value_enum)]
+        group_by: Option<GroupBy>,
+        /// Only show pending tasks without a due date, leaving out ones marked
+        /// someday
+        #[arg(long)]
+        no_due: bool,
+        /// Only show the someday backlog: pending tasks undated on purpose
+        #[arg(long, conflicts_with_all = ["no_due", "overdue_days"])]
+        someday: bool,
+        /// Only show tasks completed today, in the order they were done
+        #[arg(long, conflicts_with_all = ["no_done", "status", "someday", "no_due"])]
+        done_today: bool,
+        /// Only show tasks waiting on their --wait date, which are hidden
+        /// otherwise
+        #[arg(long)]
+        waiting: bool,
+        /// Only show pending tasks more than N days past due
+        #[arg(long, value_name = "N", conflicts_with = "no_due")]
+        overdue_days: Option<u32>,
+        /// Mark pending tasks created more than N days ago as stale and count
+        /// them in the footer [default: stale_after_days from the config]
+        #[arg(long, value_name = "N")]
+        max_age: Option<u32>,
+        /// Count stale tasks in the footer without marking each one
+        #[arg(long)]
+        no_stale_marker: bool,
+        /// Order tasks by these comma-separated keys instead of file order,
+        /// later keys breaking ties: id, priority, due, created, updated,
+        /// completed, tag, smart or urgency. Add `-` to reverse one, e.g.
+        /// priority-,due
+        #[arg(long, value_delimiter = ',', value_name = "KEYS")]
+        sort: Vec<SortSpec>,
+        /// With --sort due, tag or priority, put tasks without one first or last
+        /// [default: someday_position from the config, else last]
+        #[arg(long, value_enum, requires = "sort")]
+        nulls: Option<Nulls>,
+        /// Show at most N tasks, after filtering and sorting
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+        /// Leave out the "N tasks: ... pending, ... done" footer
+        #[arg(long)]
+        no_summary: bool,
+        /// Print only the footer's counts for the matching tasks, no tasks
+        #[arg(
+            long,
+            conflicts_with_all = [
+                "no_summary", "json", "ndjson", "fields", "group_by", "tree", "ids_only", "plain"
+            ]
+        )]
+        summary_only: bool,
+        /// Leave out the "--- Your ToDo Tasks ---" header and the separator
+        /// line closing the list
+        #[arg(long)]
+        no_header: bool,
+        /// Only show tasks with this tag (repeat to require several)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Hide tasks with this tag (repeat to exclude several)
+        #[arg(long = "not-tag")]
+        not_tags: Vec<String>,
+        /// Only show tasks with this context (repeat to require several)
+        #[arg(long = "context")]
+        contexts: Vec<String>,
+        /// Only show tasks in this project
+        #[arg(long)]
+        project: Option<String>,
+        /// Only show tasks assigned to this person; `me` for yourself
+        #[arg(long)]
+        assignee: Option<String>,
+        /// Only show tasks created before this date ('YYYY-MM-DD [HH:MM]')
+        #[arg(long)]
+        created_before: Option<String>,
+        /// Only show tasks created on or after this date ('YYYY-MM-DD [HH:MM]')
+        #[arg(long)]
+        created_after: Option<String>,
+        /// Only show tasks with an id greater than N, i.e. those added since
+        /// a sync saw task N; edits to older tasks aren't picked up
+        #[arg(long, value_name = "N")]
+        since_id: Option<usize>,
+        /// Only tasks whose description matches this regular expression,
+        /// e.g. '^call' or '(?i)\bjira-\d+'
+        #[arg(long, value_name = "PATTERN", value_parser = Regex::new)]
+        regex: Option<Regex>,
+        /// Nest each task's prerequisites (its --depends-on tasks) under it
+        #[arg(long, conflicts_with_all = ["fields", "group_by"])]
+        tree: bool,
+        /// Show tasks one per line without nesting (the default)
+        #[arg(long, conflicts_with = "tree")]
+        flat: bool,
+        /// Print straight to the terminal even when the list is taller than it
+        #[arg(long)]
+        no_pager: bool,
+        /// Print the JSON Schema of the --json output instead of any tasks
+        #[arg(long)]
+        json_schema: bool,
+        /// Print only the ids of matching tasks, one per line
+        #[arg(long, conflicts_with_all = ["json", "ndjson", "fields", "group_by"])]
+        ids_only: bool,
+        /// Print just `id. description` lines: no header, footer, color or
+        /// truncation, for pasting into notes
+        #[arg(
+            long,
+            conflicts_with_all = ["json", "ndjson", "fields", "group_by", "tree", "ids_only"]
+        )]
+        plain: bool,
+    },
+    /// Find pending tasks whose description contains the query (case-insensitive)
+    Search {
+        #[arg(required_unless_present = "regex")]
+        query: Option<String>,
+        /// Match descriptions against this regular expression instead; see
+        /// `list --regex`
+        #[arg(long, value_name = "PATTERN", value_parser = Regex::new, conflicts_with = "query")]
+        regex: Option<Regex>,
+        /// Also search completed tasks
+        #[arg(long)]
+        include_done: bool,
+        /// Print only the number of matching tasks
+        #[arg(long)]
+        count_only: bool,
+        /// Show at most N matches
+        #[arg(long, value_name = "N", conflicts_with = "count_only")]
+        limit: Option<usize>,
+        /// Skip the first N matches, for paging with --limit
+        #[arg(long, value_name = "N", default_value_t = 0, conflicts_with = "count_only")]
+        offset: usize,
+    },
+    /// Show a task's details, including its subtask checklist
+    Show {
+        #[arg(value_parser = parse_id)]
+        id: usize,
+        /// Show times relative to now, e.g. "in 3 days" or "2 hours ago"
+        #[arg(long)]
+        relative: bool,
+        /// Print the task as a JSON object (same as --format json)
+        #[arg(long, conflicts_with = "relative")]
+        json: bool,
+    },
+    /// Add a checklist item to a task
+    AddSubtask {
+        #[arg(value_parser = parse_id)]
+        parent_id: usize,
+        text: String,
+    },
+    /// Toggle a checklist item of a task
+    Check {
+        #[arg(value_parser = parse_id)]
+        parent_id: usize,
+        sub_index: usize,
+    },
+    /// Change a task's description, due date, priority, tags or dependencies
+    Modify {
+        #[arg(value_parser = parse_id)]
+        id: usize,
+        #[arg(long)]
+        description: Option<String>,
+        /// 'YYYY-MM-DD [HH:MM]', or relative to now like '+3d' or '+2h'
+        #[arg(long)]
+        due: Option<String>,
+        /// Resolve a relative --due from this date instead of now
+        #[arg(long, requires = "due", value_parser = parse_timestamp)]
+        relative_to: Option<DateTime<FixedOffset>>,
+        /// Move the task to the someday backlog, clearing its due date
+        #[arg(long, conflicts_with = "due")]
+        someday: bool,
+        #[arg(long, value_enum)]
+        priority: Option<Priority>,
+        /// Make the task wait for this task (repeat for several)
+        #[arg(long, value_parser = parse_id)]
+        after: Vec<usize>,
+        #[arg(long = "add-tag")]
+        add_tags: Vec<String>,
+        #[arg(long = "remove-tag")]
+        remove_tags: Vec<String>,
+        /// Move the task to this project
+        #[arg(long)]
+        project: Option<String>,
+        /// Take the task out of its project
+        #[arg(long, conflicts_with = "project")]
+        no_project: bool,
+        /// Assign the task to this person; `me` for yourself
+        #[arg(long)]
+        assignee: Option<String>,
+        /// Leave the task unassigned
+        #[arg(long, conflicts_with = "assignee")]
+        no_assignee: bool,
+        /// How long the task should take, e.g. 45m or 2h
+        #[arg(long)]
+        estimate: Option<String>,
+        /// Drop the task's estimate
+        #[arg(long, conflicts_with = "estimate")]
+        no_estimate: bool,
+        /// Hide the task from `list` and `peek` until this date
+        #[arg(long, value_name = "DATE")]
+        wait: Option<String>,
+        /// Show the task again now, dropping its --wait date
+        #[arg(long, conflicts_with = "wait")]
+        no_wait: bool,
+    },
+    /// Add a tag to one or more tasks
+    Tag {
+        #[arg(required = true, value_parser = parse_id)]
+        ids: Vec<usize>,
+        tag: String,
+    },
+    /// Remove a tag from one or more tasks
+    Untag {
+        #[arg(required = true, value_parser = parse_id)]
+        ids: Vec<usize>,
+        tag: String,
+    },
+    /// Print the next due dates of a repeating task
+    RecurPreview {
+        #[arg(value_parser = parse_id)]
+        id: usize,
+        #[arg(default_value_t = 5)]
+        count: usize,
+    },
+    /// Protect a task from `delete` and `done` (unless --force)
+    Lock {
+        #[arg(value_parser = parse_id)]
+        id: usize,
+    },
+    /// Remove the protection added by `lock`
+    Unlock {
+        #[arg(value_parser = parse_id)]
+        id: usize,
+    },
+    /// Add a timestamped note to a task's log
+    Annotate {
+        #[arg(value_parser = parse_id)]
+        id: usize,
+        text: String,
+    },
+    /// Mark a task as recently updated without changing it
+    Touch {
+        #[arg(value_parser = parse_id)]
+        id: usize,
+    },
+    /// Add a pending copy of a task, keeping its description, tags, project,
+    /// priority and notes but not its due date, history or dependencies
+    Clone {
+        #[arg(value_parser = parse_id)]
+        id: usize,
+        /// Due date of the copy: 'YYYY-MM-DD [HH:MM]', or relative to now
+        /// like '+3d' or '+2h'
+        #[arg(long)]
+        due: Option<String>,
+    },
+    /// Give a task a different, unused id, updating tasks that depend on it
+    Renumber {
+        #[arg(value_parser = parse_id)]
+        old_id: usize,
+        #[arg(value_parser = parse_id)]
+        new_id: usize,
+    },
+    /// Renumber all tasks 0, 1, 2, ... in id order, closing the gaps left by
+    /// deletions
+    CompactIds {
+        /// Don't ask for confirmation first
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Break a task into new tasks that keep its tags and priority
+    Split {
+        #[arg(value_parser = parse_id)]
+        id: usize,
+        #[arg(required = true)]
+        parts: Vec<String>,
+        /// Mark the original task done afterwards
+        #[arg(long)]
+        done: bool,
+        /// Delete the original task afterwards
+        #[arg(long, conflicts_with = "done")]
+        delete: bool,
+    },
+    /// Show the most recently completed tasks, newest first
+    Recent {
+        #[arg(default_value_t = 5)]
+        n: usize,
+    },
+    /// Move all overdue tasks to the end of today (or N days later)
+    RescheduleOverdue {
+        /// Days after today to move them to
+        #[arg(long, default_value_t = 0)]
+        days: u32,
+        /// Don't ask for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Print, as a JSON array, the tasks created or changed since a time,
+    /// for incremental sync
+    Changes {
+        /// RFC 3339 timestamp (as in `list --json`) or 'YYYY-MM-DD [HH:MM]'
+        #[arg(long, value_parser = parse_timestamp)]
+        since: DateTime<FixedOffset>,
+    },
+    /// Move all completed tasks to the archive file (tasks.archive.json)
+    Archive,
+    /// Delete all completed tasks, without keeping them in the archive
+    ClearDone {
+        /// Don't list the tasks and ask for confirmation first
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Delete every task, locked ones included, after you type PURGE
+    Purge {
+        /// Don't ask; only together with --i-understand
+        #[arg(long, requires = "i_understand")]
+        yes: bool,
+        /// Confirm that --yes really should delete every task
+        #[arg(long, requires = "yes")]
+        i_understand: bool,
+    },
+    /// Cap the task list at the N most recently created tasks, pending ones
+    /// first, removing the rest (locked tasks are always kept)
+    Trim {
+        /// How many tasks to keep
+        #[arg(long, value_name = "N")]
+        keep: usize,
+        /// Move the removed tasks to the archive file instead of deleting them
+        #[arg(long)]
+        archive: bool,
+        /// Don't list the tasks and ask for confirmation first
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Edit all task descriptions at once in $EDITOR: change lines to rename
+    /// tasks, remove them to delete tasks, add lines to add tasks
+    BulkEdit {
+        /// Allow renaming and deleting locked tasks
+        #[arg(long)]
+        force: bool,
+        /// Apply the changes without listing them and asking first
+        #[arg(long, conflicts_with = "dry_run")]
+        yes: bool,
+        /// Sum up what the edit would change without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Print the whole task list, unfiltered and with every field, as JSON
+    /// for backups; `import --replace` restores it
+    Export {
+        /// Comma-separated task keys (CSV columns with --format csv) to keep,
+        /// e.g. id,description,due
+        #[arg(long, value_delimiter = ',')]
+        fields: Vec<String>,
+        /// Start with a UTF-8 byte order mark, which Excel needs to read CSV
+        /// as UTF-8
+        #[arg(long)]
+        bom: bool,
+        /// End lines with CRLF, as on Windows, where that is the default
+        #[arg(long)]
+        crlf: bool,
+        /// Write to this file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Open the written file in its default application, such as a
+        /// browser for --format html
+        #[arg(long, requires = "output")]
+        open: bool,
+    },
+    /// Add one task per line of a plain-text file (skipping `#` comments),
+    /// or the tasks of an `export` file; with --format todotxt, read todo.txt
+    /// lines
+    Import {
+        path: PathBuf,
+        /// Delete all existing tasks first, so the file becomes the new list
+        #[arg(long)]
+        replace: bool,
+        /// With --replace, don't ask for confirmation
+        #[arg(long, requires = "replace", conflicts_with = "dry_run")]
+        yes: bool,
+        /// Sum up what the import would add, remove and edit without writing
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Revert the most recent change (repeat to go further back)
+    Undo,
+    /// Re-apply the most recently undone change
+    Redo,
+    /// Show recent changes from the undo log, newest first
+    History {
+        /// How many entries to show
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+        /// Print the entries as a JSON array
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the settings in effect: version, database and config paths,
+    /// timezone, colors, default order and date format
+    Info {
+        /// Print them as a JSON object
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the version, the git commit it was built from and the task file
+    /// format version, for tools that check what they talk to
+    Version {
+        /// Print them as a JSON object
+        #[arg(long)]
+        json: bool,
+    },
+    /// Save a timestamped copy of the task file to the backup directory,
+    /// deleting the oldest beyond the config's `keep_backups`
+    Backup,
+    /// List the copies `backup` has saved, newest first
+    Backups,
+    /// Replace the task file with the named `backup` copy, or without one
+    /// with its newest automatic backup (.bak or .corrupt)
+    RestoreBackup {
+        /// A name from `backups`, e.g. tasks-20240601-120000.json
+        name: Option<String>,
+        /// Don't ask for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Check the task file for problems such as reused ids or dependencies
+    /// on missing tasks
+    Doctor {
+        /// Repair what can be fixed safely: renumber reused ids and drop
+        /// dependencies on missing tasks
+        #[arg(long)]
+        fix: bool,
+        /// Print the findings (and fixes) as JSON objects with a severity,
+        /// a stable code, a message and the task id when there is one
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print descriptions of pending tasks starting with a prefix, one per
+    /// line, for shell completion scripts
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        #[arg(default_value = "")]
+        prefix: String,
+    },
+    /// Show each project's progress, least complete first
+    Projects,
+    /// Compare estimates with the time spent under `focus`
+    Estimates,
+    /// Print task counts
+    Stats {
+        /// Break the counts down per tag
+        #[arg(long)]
+        by_tag: bool,
+        /// Print the counts on one line, e.g. 'total=12 pending=7 done=5
+        /// overdue=2', for prompts and status bars
+        #[arg(long, conflicts_with = "by_tag")]
+        compact: bool,
+        /// How to separate the --by-tag columns
+        #[arg(long, value_enum, default_value_t, requires = "by_tag")]
+        separator: Separator,
+        /// Show the current and longest runs of days with a task completed,
+        /// and a bar per day of how many were
+        #[arg(long, conflicts_with_all = ["by_tag", "compact"])]
+        streak: bool,
+        /// How many days of bars --streak shows
+        #[arg(long, value_name = "N", default_value_t = 14, requires = "streak")]
+        days: usize,
+    },
+    /// Print just the description of the next due pending task, for status
+    /// bars and shell prompts
+    Peek,
+    /// Keep the task list on screen, redrawing it whenever the task file
+    /// changes, until Ctrl-C
+    Watch {
+        /// Seconds between checks of the task file for changes
+        #[arg(long, value_name = "SECONDS", default_value_t = 2, value_parser = parse_interval)]
+        watch_interval: u64,
     },
-    List,
+    /// Print reminders for tasks that are due soon or overdue (each fires once)
+    Notify,
+    /// Read commands from stdin in a loop, keeping the tasks loaded, until
+    /// `quit` or end of input
+    #[command(visible_alias = "repl")]
+    Shell,
+    /// Walk through pending tasks one by one, choosing an action for each
+    Review {
+        /// How far `s` (snooze) pushes the due date, e.g. 1d, 12h
+        #[arg(long, default_value = "1d", value_parser = parse_duration)]
+        snooze: Duration,
+    },
+    /// Show the task being worked on, focus on task <ID>, or `focus clear`
+    /// to stop; the time is added to the task's time spent
+    Focus {
+        /// A task id, or `clear`
+        #[arg(value_parser = parse_focus_target)]
+        target: Option<FocusTarget>,
+    },
+    /// Go through pending tasks without a due date, asking for one for each
+    Plan {
+        /// Save each due date as it is entered, so stopping early (Ctrl-C
+        /// included) keeps them; by default all are saved together at the end
+        #[arg(long)]
+        save_partial: bool,
+    },
+    /// Mark one or more tasks done by index
+    #[command(visible_aliases = ["do", "complete"])]
     Done {
-        index: usize,
+        #[arg(
+            required_unless_present_any = ["exact", "Targets"],
+            conflicts_with = "Targets",
+            value_parser = parse_id
+        )]
+        indices: Vec<usize>,
+        /// Treat the arguments as task ids (as shown by `list`) instead of
+        /// positions
+        #[arg(long)]
+        id: bool,
+        /// Record why or how the task was finished; shown by `show` and
+        /// `recent`
+        #[arg(long)]
+        note: Option<String>,
+        /// Complete the one pending task with exactly this description
+        /// (case-sensitive); fails if none or several match
+        #[arg(
+            long,
+            value_name = "DESCRIPTION",
+            conflicts_with_all = ["indices", "id", "Targets"]
+        )]
+        exact: Option<String>,
+        /// Report tasks in the order given instead of file order
+        #[arg(long)]
+        keep_order: bool,
+        /// Include locked tasks
+        #[arg(long)]
+        force: bool,
+        #[command(flatten)]
+        targets: Targets,
+        /// Complete the tasks a filter matches without listing them and
+        /// asking first
+        #[arg(long, requires = "Targets")]
+        yes: bool,
     },
+    /// Delete one or more tasks by index
+    #[command(visible_alias = "rm")]
     Delete {
-        index: usize,
+        #[arg(
+            required_unless_present_any = ["done", "Targets"],
+            conflicts_with = "Targets",
+            value_parser = parse_id
+        )]
+        indices: Vec<usize>,
+        /// Delete every completed task that isn't locked instead, the same
+        /// as `clear-done`
+        #[arg(
+            long,
+            conflicts_with_all = ["indices", "id", "keep_order", "force", "Targets"]
+        )]
+        done: bool,
+        /// Treat the arguments as task ids (as shown by `list`) instead of
+        /// positions
+        #[arg(long)]
+        id: bool,
+        /// Report tasks in the order given instead of file order
+        #[arg(long)]
+        keep_order: bool,
+        /// Include locked tasks
+        #[arg(long)]
+        force: bool,
+        #[command(flatten)]
+        targets: Targets,
+        /// Delete several tasks, or any a filter matches, without listing
+        /// them and asking first
+        #[arg(long)]
+        yes: bool,
     },
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// Filters that pick the tasks `done` and `delete` act on instead of
+/// indices, named as in `list`. Locked tasks are left out unless --force.
+#[derive(clap::Args, Debug)]
+struct Targets {
+    /// Act on every pending task that is past due
+    #[arg(long, conflicts_with_all = ["id", "keep_order"])]
+    overdue: bool,
+    /// Act on tasks with this tag (repeat to require several)
+    #[arg(long = "tag", conflicts_with_all = ["id", "keep_order"])]
+    tags: Vec<String>,
+    /// Leave out tasks with this tag (repeat to exclude several)
+    #[arg(long = "not-tag", conflicts_with_all = ["id", "keep_order"])]
+    not_tags: Vec<String>,
+    /// Act on tasks with this context (repeat to require several)
+    #[arg(long = "context", conflicts_with_all = ["id", "keep_order"])]
+    contexts: Vec<String>,
+    /// Act on tasks in this project
+    #[arg(long, conflicts_with_all = ["id", "keep_order"])]
+    project: Option<String>,
+    /// Act on pending tasks without a due date, leaving out someday ones
+    #[arg(long, conflicts_with_all = ["id", "keep_order"])]
+    no_due: bool,
+    /// Act on pending tasks marked someday
+    #[arg(long, conflicts_with_all = ["id", "keep_order", "no_due"])]
+    someday: bool,
+}
+
+impl Targets {
+    /// The filter the flags describe, or `None` when none was given and
+    /// the command goes by indices instead.
+    fn filter(self, task_manager: &TaskManager) -> Result<Option<TaskFilter>, TodoError> {
+        let given = self.overdue
+            || !self.tags.is_empty()
+            || !self.not_tags.is_empty()
+            || !self.contexts.is_empty()
+            || self.project.is_some()
+            || self.no_due
+            || self.someday;
+        if !given {
+            return Ok(None);
+        }
+        Ok(Some(TaskFilter {
+            no_due: self.no_due,
+            someday: self.someday,
+            tags: normalize_names(task_manager, NameKind::Tag, self.tags)?,
+            not_tags: normalize_names(task_manager, NameKind::Tag, self.not_tags)?,
+            contexts: normalize_names(task_manager, NameKind::Context, self.contexts)?,
+            project: normalize_project(task_manager, self.project)?,
+            overdue_before: self
+                .overdue
+                .then(|| task::now_ist() - task_manager.overdue_grace()),
+            ..TaskFilter::default()
+        }))
+    }
+}
+
+/// How `confirm_targets` asks: `prompt::confirm_removal` or
+/// `prompt::confirm_listed`.
+type Confirm = fn(&str, &[&str], bool) -> bool;
+
+/// Positions of the tasks `filter` matches, skipping locked ones unless
+/// `force`, once `confirm` has shown them and the user agreed to `question`
+/// (or `yes` is set). `None` when there are none or the user declines.
+fn confirm_targets(
+    task_manager: &TaskManager,
+    filter: &TaskFilter,
+    force: bool,
+    (question, confirm): (&str, Confirm),
+    yes: bool,
+    batch: bool,
+) -> Option<Vec<usize>> {
+    let tasks = task_manager.tasks.borrow();
+    let positions: Vec<usize> = tasks
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| filter.matches(t) && (force || !t.locked))
+        .map(|(position, _)| position)
+        .collect();
+    if positions.is_empty() {
+        if !task_manager.quiet {
+            println!("No tasks match.");
+        }
+        return None;
+    }
+    let descriptions: Vec<&str> =
+        positions.iter().map(|&p| tasks[p].description.as_str()).collect();
+    if !yes && !confirm(question, &descriptions, batch) {
+        println!("Nothing changed.");
+        return None;
+    }
+    Some(positions)
+}
+
+fn main() -> ExitCode {
     let cli = Cli::parse();
+    let error_format = cli.error_format;
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            error::report(&e, error_format);
+            ExitCode::FAILURE
+        }
+    }
+}
 
-    let mut task_manager = TaskManager::new("tasks.json")?;
+/// Prints what `version` reports. The commit is missing from builds made
+/// outside a git checkout.
+fn print_version(json: bool) -> Result<(), TodoError> {
+    let commit = option_env!("TODO_CLI_GIT_COMMIT");
+    if json {
+        let version = serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "git_commit": commit,
+            "schema_version": schema::SCHEMA_VERSION,
+        });
+        println!("{}", serde_json::to_string_pretty(&version)?);
+        return Ok(());
+    }
+    println!("todo_cli {}", env!("CARGO_PKG_VERSION"));
+    println!("commit:         {}", commit.unwrap_or("unknown"));
+    println!("schema version: {}", schema::SCHEMA_VERSION);
+    Ok(())
+}
+
+/// Read from the working directory when `--config` isn't given.
+const DEFAULT_CONFIG: &str = "config.json";
+
+fn load_config(cli: &Cli) -> Result<Config, TodoError> {
+    Ok(match &cli.config {
+        Some(path) if !path.exists() => return Err(TodoError::ConfigNotFound(path.clone())),
+        Some(path) => Config::load(path)?,
+        None => Config::load(Path::new(DEFAULT_CONFIG))?,
+    })
+}
 
-    match cli.command {
-        Commands::Add { description, due } => {
+/// `args` parsed again with the command to run when they name none:
+/// `default` (the config's `default_command`) read as if typed after them,
+/// or `list` when that is unset or doesn't parse.
+fn with_default_command(args: Vec<OsString>, default: Option<&str>) -> Cli {
+    if let Some(default) = default {
+        let parsed = repl::split_words(default).and_then(|words| {
+            let line = args.iter().cloned().chain(words.into_iter().map(OsString::from));
+            Cli::try_parse_from(line).map_err(|e| {
+                let message = e.render().to_string();
+                let first = message.lines().next().unwrap_or_default();
+                first.trim_start_matches("error: ").to_string()
+            })
+        });
+        match parsed {
+            Ok(cli) if cli.command.is_some() => return cli,
+            Ok(_) => eprintln!("Warning: default_command \"{}\" names no command.", default),
+            Err(e) => eprintln!("Warning: ignoring default_command \"{}\": {}", default, e),
+        }
+    }
+    Cli::parse_from(args.into_iter().chain([OsString::from("list")]))
+}
+
+fn run(cli: Cli) -> Result<(), TodoError> {
+    // Loaded this early only to find out what to run.
+    let mut early_config = None;
+    let cli = if cli.command.is_none() {
+        let config = load_config(&cli)?;
+        let args = std::env::args_os().collect();
+        let cli = with_default_command(args, config.default_command.as_deref());
+        early_config = Some(config);
+        cli
+    } else {
+        cli
+    };
+    match &cli.command {
+        Some(Commands::RestoreBackup { name, yes }) => {
+            let backup = match name {
+                Some(name) => {
+                    let config = match &early_config {
+                        Some(config) => config.clone(),
+                        None => load_config(&cli)?,
+                    };
+                    Some(TaskManager::named_backup(&cli.file, &config, name)?)
+                }
+                None => None,
+            };
+            return TaskManager::restore_backup(&cli.file, backup.as_deref(), *yes, cli.batch);
+        }
+        Some(Commands::Doctor { fix, json }) => {
+            return TaskManager::doctor(&cli.file, *fix, *json, cli.overwrite);
+        }
+        Some(Commands::Version { json }) => return print_version(*json),
+        _ => {}
+    }
+    let mut config = match early_config {
+        Some(config) => config,
+        None => load_config(&cli)?,
+    };
+    if let Some(id_format) = cli.id_format {
+        config.id_format = id_format;
+    }
+    display::set_date_pattern(cli.locale.unwrap_or(config.date_pattern));
+    if let Some(offset) = cli.display_tz {
+        display::set_display_offset(offset);
+    }
+    let mut task_manager = TaskManager::new(cli.file.clone(), config)?;
+    task_manager.quiet = cli.quiet || cli.batch;
+    task_manager.verbose = cli.verbose;
+    task_manager.overwrite = cli.overwrite;
+    task_manager.error_format = cli.error_format;
+    task_manager.color = cli.color;
+    task_manager.markers = display::markers(cli.ascii);
+    task_manager.report_load();
+    task_manager.fix_duplicate_ids(cli.strict)?;
+    task_manager.apply_retention()?;
+    execute(cli, &mut task_manager)
+}
+
+/// Runs an interactive loop over the already loaded `task_manager`. Each line
+/// is parsed like a command line without the program name; errors are
+/// reported and the loop carries on.
+fn shell(cli: &Cli, task_manager: &mut TaskManager) -> Result<(), TodoError> {
+    let mut lines = repl::Lines::new();
+    while let Some(line) = lines.next("todo> ")? {
+        let words = match repl::split_words(&line) {
+            Ok(words) => words,
+            Err(message) => {
+                eprintln!("Error: {}", message);
+                continue;
+            }
+        };
+        match words.first().map(String::as_str) {
+            None => continue,
+            Some("quit" | "exit") => break,
+            _ => {}
+        }
+        let args: Vec<OsString> =
+            std::iter::once("todo_cli".to_string()).chain(words).map(OsString::from).collect();
+        let line_cli = match Cli::try_parse_from(&args) {
+            Ok(line_cli) if line_cli.command.is_none() => {
+                with_default_command(args, task_manager.default_command())
+            }
+            Ok(line_cli) => line_cli,
+            Err(e) => {
+                let _ = e.print();
+                continue;
+            }
+        };
+        let result = match line_cli.command {
+            Some(Commands::Shell) => {
+                println!("Already in the shell.");
+                Ok(())
+            }
+            Some(Commands::RestoreBackup { .. } | Commands::Doctor { .. }) => {
+                println!("Run this outside the shell; it works on the file, not the loaded tasks.");
+                Ok(())
+            }
+            _ => execute(line_cli, task_manager),
+        };
+        if let Err(e) = result {
+            error::report(&e, cli.error_format);
+        }
+    }
+    Ok(())
+}
+
+/// Shows each pending task in turn and applies the action read for it:
+/// (d)one, (e)dit, (s)nooze, (x) delete, (k)eep or (q)uit. Each action is
+/// saved as it is taken, so quitting halfway keeps the work done so far.
+/// Actions are read a line at a time; only the first character counts.
+fn review(task_manager: &TaskManager, snooze: Duration) -> Result<(), TodoError> {
+    let ids: Vec<usize> = task_manager
+        .tasks
+        .borrow()
+        .iter()
+        .filter(|t| t.is_pending())
+        .map(|t| t.id)
+        .collect();
+    if ids.is_empty() {
+        println!("Nothing to review.");
+        return Ok(());
+    }
+    let mut lines = repl::Lines::new();
+    let mut reviewed = 0;
+    'tasks: for (n, &id) in ids.iter().enumerate() {
+        println!("\n({}/{})", n + 1, ids.len());
+        task_manager.show_task(id, false)?;
+        loop {
+            let prompt = "[d]one [e]dit [s]nooze [x] delete [k]eep [q]uit? ";
+            let Some(answer) = lines.next(prompt)? else {
+                break 'tasks;
+            };
+            let result = match answer.trim().to_lowercase().chars().next() {
+                Some('d') => {
+                    let positions = task_manager.positions_of(&[id]);
+                    task_manager.mark_tasks_done(&positions, false, false, None)
+                }
+                Some('e') => {
+                    let Some(text) = lines.next("New description: ")? else {
+                        break 'tasks;
+                    };
+                    if text.trim().is_empty() {
+                        println!("Description unchanged.");
+                        Ok(())
+                    } else {
+                        task_manager.check_description(text, false).and_then(|description| {
+                            let changes = TaskChanges {
+                                description: Some(description),
+                                ..TaskChanges::default()
+                            };
+                            task_manager.modify_task(id, changes)
+                        })
+                    }
+                }
+                Some('s') => task_manager.snooze_task(id, snooze),
+                Some('x') => {
+                    let positions = task_manager.positions_of(&[id]);
+                    task_manager.delete_tasks(&positions, false, false, true, false)
+                }
+                Some('k') => Ok(()),
+                Some('q') => break 'tasks,
+                _ => {
+                    println!("Unknown action; choose one of d, e, s, x, k or q.");
+                    continue;
+                }
+            };
+            if let Err(e) = result {
+                error::report(&e, task_manager.error_format);
+                continue;
+            }
+            break;
+        }
+        reviewed += 1;
+    }
+    println!("Reviewed {} of {} pending task(s).", reviewed, ids.len());
+    Ok(())
+}
+
+/// Asks for a due date for each pending task that has none (someday tasks
+/// are left alone), in any form `--due` takes; a blank answer skips the task
+/// and `q` stops. The dates are saved together once all tasks are done or
+/// `q` is given, so Ctrl-C or the end of input discards them, unless
+/// `save_partial`, which saves each one as it is entered.
+fn plan(task_manager: &TaskManager, strict: bool, save_partial: bool) -> Result<(), TodoError> {
+    let tasks: Vec<(usize, String)> = task_manager
+        .tasks
+        .borrow()
+        .iter()
+        .filter(|t| t.is_pending() && t.due_date.is_none() && !t.someday)
+        .map(|t| (t.id, t.description.clone()))
+        .collect();
+    if tasks.is_empty() {
+        println!("No pending tasks without a due date.");
+        return Ok(());
+    }
+    let mut lines = repl::Lines::new();
+    let mut planned = Vec::new();
+    let mut ended = false;
+    'tasks: for (n, (id, description)) in tasks.iter().enumerate() {
+        println!("\n({}/{}) {}. {}", n + 1, tasks.len(), id, description);
+        loop {
+            let prompt = "Due ('YYYY-MM-DD [HH:MM]', '+3d'; blank to skip, q to stop): ";
+            let Some(answer) = lines.next(prompt)? else {
+                ended = true;
+                break 'tasks;
+            };
+            let answer = answer.trim();
+            if answer.is_empty() {
+                break;
+            }
+            if answer.eq_ignore_ascii_case("q") {
+                break 'tasks;
+            }
+            match parse_due_arg(task_manager, answer, strict, None) {
+                Ok(due) if save_partial => {
+                    let changes = TaskChanges {
+                        due_date: Some(due),
+                        ..TaskChanges::default()
+                    };
+                    task_manager.modify_task(*id, changes)?;
+                    planned.push((*id, due));
+                }
+                Ok(due) => planned.push((*id, due)),
+                Err(e) => {
+                    error::report(&e, task_manager.error_format);
+                    continue;
+                }
+            }
+            break;
+        }
+    }
+    if ended && !save_partial {
+        println!("Input ended; {} due date(s) discarded.", planned.len());
+        return Ok(());
+    }
+    if !save_partial {
+        task_manager.schedule_tasks(&planned)?;
+    }
+    println!("Planned {} of {} task(s).", planned.len(), tasks.len());
+    Ok(())
+}
+
+fn execute(mut cli: Cli, task_manager: &mut TaskManager) -> Result<(), TodoError> {
+    let command = cli.command.take().expect("run and shell fill in the default command");
+    // Set per command rather than once in `run`, as each shell line has its own.
+    task_manager.format = cli.format;
+    let (supports_format, commands) = match cli.format {
+        OutputFormat::Human => (true, ""),
+        OutputFormat::Json | OutputFormat::Csv => (
+            matches!(
+                command,
+                Commands::List { .. }
+                    | Commands::Search { .. }
+                    | Commands::Stats { .. }
+                    | Commands::Show { .. }
+                    | Commands::Export { .. }
+            ),
+            "list, search, stats, show and export",
+        ),
+        OutputFormat::Todotxt => (
+            matches!(
+                command,
+                Commands::List { .. }
+                    | Commands::Search { .. }
+                    | Commands::Show { .. }
+                    | Commands::Export { .. }
+                    | Commands::Import { .. }
+            ),
+            "list, search, show, export and import",
+        ),
+        OutputFormat::Html => (matches!(command, Commands::Export { .. }), "export"),
+    };
+    if !supports_format {
+        let format = format!("{:?}", cli.format).to_lowercase();
+        return Err(TodoError::UnsupportedFormat { format, commands });
+    }
+    if cli.strict_index {
+        check_references(&command, task_manager)?;
+    }
+    match command {
+        Commands::Add {
+            descriptions,
+            due,
+            relative_to,
+            someday,
+            remind,
+            estimate,
+            wait,
+            tags,
+            contexts,
+            project,
+            assignee,
+            priority,
+            depends_on,
+            repeat,
+            preview,
+            url,
+            truncate,
+            porcelain,
+            json,
+            force,
+            if_not_exists,
+            done,
+            completed_at,
+            shorthand,
+            literal,
+            single: whole_stdin,
+        } => {
+            let from_stdin = descriptions.iter().any(|d| d == "-");
+            let descriptions = if descriptions.is_empty() {
+                let asked = prompt::ask("Description:", cli.batch);
+                vec![asked.ok_or(TodoError::MissingDescription)?]
+            } else if from_stdin {
+                stdin_descriptions(descriptions, whole_stdin)?
+            } else {
+                descriptions
+            };
+            let single = descriptions.len() == 1;
+            // Each description ends up as an existing task's id (under
+            // --if-not-exists) or a new task, in the order given.
+            let mut outcomes: Vec<Option<usize>> = Vec::new();
+            let mut new_tasks = Vec::new();
             let due_date_ist = match due {
-                Some(date_str) => Some(parse_due_date(&date_str)?),
+                Some(date_str) => {
+                    Some(parse_due_arg(task_manager, &date_str, cli.strict, relative_to)?)
+                }
+                None => None,
+            };
+            let remind_before = match remind {
+                Some(duration_str) => {
+                    Some(parse_duration(&duration_str).map_err(TodoError::InvalidDuration)?)
+                }
+                None => None,
+            };
+            let estimate = estimate
+                .map(|text| parse_duration(&text).map_err(TodoError::InvalidDuration))
+                .transpose()?;
+            let wait_until = wait
+                .map(|date_str| parse_due_arg(task_manager, &date_str, cli.strict, None))
+                .transpose()?;
+            task_manager.check_ids(&depends_on)?;
+            let tags = normalize_names(task_manager, NameKind::Tag, tags)?;
+            let contexts = normalize_names(task_manager, NameKind::Context, contexts)?;
+            let project = normalize_project(task_manager, project)?;
+            let assignee = normalize_assignee(task_manager, assignee)?;
+            for description in descriptions {
+                let marked = if shorthand || (task_manager.shorthand() && !literal) {
+                    split_shorthand(&description)
+                } else {
+                    Shorthand {
+                        description,
+                        priority: None,
+                        tags: Vec::new(),
+                        contexts: Vec::new(),
+                    }
+                };
+                let marked_tags = normalize_names(task_manager, NameKind::Tag, marked.tags)?;
+                let marked_contexts =
+                    normalize_names(task_manager, NameKind::Context, marked.contexts)?;
+                let description = task_manager.check_description(marked.description, truncate)?;
+                if if_not_exists && let Some(id) = task_manager.find_duplicate(&description) {
+                    if !json && !porcelain && !task_manager.quiet {
+                        println!("Task already exists (id {}); skipped.", id);
+                    }
+                    outcomes.push(Some(id));
+                    continue;
+                }
+                // A duplicate loses no data, so --batch adds it without asking,
+                // as does reading from stdin, which leaves nothing to answer.
+                if !force
+                    && !cli.batch
+                    && !from_stdin
+                    && let Some(id) = task_manager.find_duplicate(&description)
+                {
+                    let question = if single {
+                        format!(
+                            "A pending task with this description already exists (id {}). \
+                             Add anyway?",
+                            id
+                        )
+                    } else {
+                        format!(
+                            "A pending task described \"{}\" already exists (id {}). Add anyway?",
+                            description, id
+                        )
+                    };
+                    if !prompt::confirm(&question, false) {
+                        println!("Task not added.");
+                        continue;
+                    }
+                }
+                let mut task = task_manager.new_task(description, due_date_ist)?;
+                task.remind_before = remind_before;
+                task.estimate = estimate;
+                task.wait_until = wait_until;
+                task.tags = tags.clone();
+                for tag in marked_tags {
+                    if !task.tags.contains(&tag) {
+                        task.tags.push(tag);
+                    }
+                }
+                task.contexts = contexts.clone();
+                for context in marked_contexts {
+                    if !task.contexts.contains(&context) {
+                        task.contexts.push(context);
+                    }
+                }
+                task.project = project.clone();
+                task.assignee = assignee.clone();
+                task.priority = priority.or(marked.priority);
+                task.depends_on = depends_on.clone();
+                task.recurrence =
+                    repeat.as_ref().zip(due_date_ist).map(|(rule, due)| rule.starting_at(due));
+                task.url = url.clone();
+                task.someday = someday;
+                if done {
+                    task.mark_done();
+                    if completed_at.is_some() {
+                        task.completed_at = completed_at;
+                    }
+                }
+                outcomes.push(None);
+                new_tasks.push(task);
+            }
+            let quiet = task_manager.quiet;
+            if porcelain || json {
+                task_manager.quiet = true;
+            }
+            let mut added = match new_tasks.len() {
+                0 => Vec::new(),
+                1 if single => vec![task_manager.add_task(new_tasks.remove(0))?],
+                _ => task_manager.add_new_tasks(new_tasks)?,
+            }
+            .into_iter();
+            task_manager.quiet = quiet;
+            let tasks = task_manager.tasks.borrow();
+            let results: Vec<Task> = outcomes
+                .into_iter()
+                .filter_map(|outcome| match outcome {
+                    Some(id) => tasks.iter().find(|t| t.id == id).cloned(),
+                    None => added.next(),
+                })
+                .collect();
+            if json && single {
+                if let Some(task) = results.first() {
+                    println!("{}", serde_json::to_string_pretty(task)?);
+                }
+            } else if json {
+                println!("{}", serde_json::to_string_pretty(&results)?);
+            } else if porcelain || quiet {
+                for task in &results {
+                    println!("{}", task.id);
+                }
+            } else if preview {
+                let ellipsis = task_manager.markers.ellipsis;
+                for line in results.iter().filter_map(|task| recurrence_preview(task, ellipsis)) {
+                    println!("{}", line);
+                }
+            }
+        }
+        Commands::List {
+            json,
+            ndjson,
+            fields,
+            separator,
+            width,
+            no_truncate,
+            wrap,
+            status,
+            no_done,
+            group_by,
+            no_due,
+            someday,
+            done_today,
+            waiting,
+            overdue_days,
+            max_age,
+            no_stale_marker,
+            limit,
+            sort,
+            nulls,
+            no_summary,
+            summary_only,
+            no_header,
+            tags,
+            not_tags,
+            contexts,
+            project,
+            assignee,
+            created_before,
+            created_after,
+            since_id,
+            regex,
+            tree,
+            flat: _,
+            no_pager,
+            json_schema,
+            ids_only,
+            plain,
+        } => {
+            if json_schema {
+                println!("{}", serde_json::to_string_pretty(&schema::task_list_schema())?);
+                return Ok(());
+            }
+            let parse_created = |date: Option<String>| match date {
+                Some(date_str) => parse_due_date(&date_str, false, NaiveTime::MIN)
+                    .map(Some)
+                    .map_err(TodoError::InvalidDate),
+                None => Ok(None),
+            };
+            let width = if no_truncate {
+                None
+            } else {
+                width.or_else(display::terminal_width)
+            };
+            let options = ListOptions {
+                filter: TaskFilter {
+                    status: if no_done { Some(TaskStatus::Pending) } else { status },
+                    no_due,
+                    someday,
+                    tags: normalize_names(task_manager, NameKind::Tag, tags)?,
+                    not_tags: normalize_names(task_manager, NameKind::Tag, not_tags)?,
+                    contexts: normalize_names(task_manager, NameKind::Context, contexts)?,
+                    project: normalize_project(task_manager, project)?,
+                    assignee: normalize_assignee(task_manager, assignee)?,
+                    created_before: parse_created(created_before)?,
+                    created_after: parse_created(created_after)?,
+                    since_id,
+                    regex,
+                    completed_on: done_today.then(|| task::now_ist().date_naive()),
+                    overdue_before: overdue_days
+                        .map(|days| task::now_ist() - Duration::days(days.into())),
+                    waiting: Some(waiting),
+                },
+                fields,
+                width,
+                group_by,
+                sort: if done_today && sort.is_empty() {
+                    vec![SortSpec { key: SortKey::Completed, descending: false }]
+                } else {
+                    sort
+                },
+                nulls: nulls.unwrap_or(task_manager.someday_position()),
+                urgency: task_manager.urgency_weights(),
+                max_age: max_age
+                    .map(i64::from)
+                    .or(task_manager.stale_after_days())
+                    .map(Duration::days),
+                stale_marker: !no_stale_marker,
+                limit,
+                summary: !no_summary,
+                header: !no_header,
+                pager: !no_pager,
+                wrap,
+                tree,
+                separator,
+            };
+            if summary_only {
+                task_manager.list_summary(&options);
+            } else if ids_only {
+                task_manager.list_task_ids(&options)?;
+            } else if plain {
+                task_manager.list_tasks_plain(&options)?;
+            } else if json || cli.batch || cli.format == OutputFormat::Json {
+                task_manager.list_tasks_json(&options)?;
+            } else if ndjson {
+                task_manager.list_tasks_ndjson(&options)?;
+            } else if cli.format != OutputFormat::Human {
+                task_manager.list_tasks_formatted(&options)?;
+            } else {
+                task_manager.list_tasks(&options)?;
+            }
+        }
+        Commands::Search {
+            query,
+            regex,
+            include_done,
+            count_only,
+            limit,
+            offset,
+        } => {
+            let width = display::terminal_width();
+            let query = match regex {
+                Some(regex) => SearchQuery::Regex(regex),
+                None => SearchQuery::Text(query.expect("clap requires a query or --regex")),
+            };
+            task_manager.search_tasks(&query, include_done, width, count_only, (offset, limit))?;
+        }
+        Commands::Show { id, relative, json } => {
+            if json {
+                task_manager.format = OutputFormat::Json;
+            }
+            task_manager.show_task(id, relative)?;
+        }
+        Commands::AddSubtask { parent_id, text } => {
+            task_manager.add_subtask(parent_id, text)?;
+        }
+        Commands::Check { parent_id, sub_index } => {
+            task_manager.toggle_subtask(parent_id, sub_index)?;
+        }
+        Commands::Modify {
+            id,
+            description,
+            due,
+            relative_to,
+            someday,
+            priority,
+            after,
+            add_tags,
+            remove_tags,
+            project,
+            no_project,
+            assignee,
+            no_assignee,
+            estimate,
+            no_estimate,
+            wait,
+            no_wait,
+        } => {
+            let description = match description {
+                Some(description) => Some(task_manager.check_description(description, false)?),
+                None => None,
+            };
+            let due_date = match due {
+                Some(date_str) => {
+                    Some(parse_due_arg(task_manager, &date_str, cli.strict, relative_to)?)
+                }
+                None => None,
+            };
+            let changes = TaskChanges {
+                description,
+                due_date,
+                priority,
+                after,
+                add_tags: normalize_names(task_manager, NameKind::Tag, add_tags)?,
+                remove_tags: normalize_names(task_manager, NameKind::Tag, remove_tags)?,
+                someday,
+                project: normalize_project(task_manager, project)?,
+                clear_project: no_project,
+                assignee: normalize_assignee(task_manager, assignee)?,
+                clear_assignee: no_assignee,
+                estimate: estimate
+                    .map(|text| parse_duration(&text).map_err(TodoError::InvalidDuration))
+                    .transpose()?,
+                clear_estimate: no_estimate,
+                wait_until: wait
+                    .map(|date_str| parse_due_arg(task_manager, &date_str, cli.strict, None))
+                    .transpose()?,
+                clear_wait: no_wait,
+            };
+            task_manager.modify_task(id, changes)?;
+        }
+        Commands::Tag { ids, tag } => {
+            let tag = normalize_name(task_manager, NameKind::Tag, &tag)?;
+            task_manager.retag_tasks(&ids, &tag, true)?;
+        }
+        Commands::Untag { ids, tag } => {
+            let tag = normalize_name(task_manager, NameKind::Tag, &tag)?;
+            task_manager.retag_tasks(&ids, &tag, false)?;
+        }
+        Commands::RecurPreview { id, count } => {
+            task_manager.recur_preview(id, count);
+        }
+        Commands::Lock { id } => {
+            task_manager.set_locked(id, true)?;
+        }
+        Commands::Unlock { id } => {
+            task_manager.set_locked(id, false)?;
+        }
+        Commands::Annotate { id, text } => {
+            task_manager.annotate_task(id, &text)?;
+        }
+        Commands::Touch { id } => {
+            task_manager.touch_task(id)?;
+        }
+        Commands::Clone { id, due } => {
+            let due_date = match due {
+                Some(date_str) => Some(parse_due_arg(task_manager, &date_str, cli.strict, None)?),
                 None => None,
             };
-            task_manager.add_task(description, due_date_ist);
+            task_manager.clone_task(id, due_date)?;
+        }
+        Commands::Renumber { old_id, new_id } => {
+            task_manager.renumber_task(old_id, new_id)?;
+        }
+        Commands::CompactIds { yes } => {
+            task_manager.compact_ids(yes, cli.batch)?;
+        }
+        Commands::Split {
+            id,
+            parts,
+            done,
+            delete,
+        } => {
+            let parts = parts
+                .into_iter()
+                .map(|part| task_manager.check_description(part, false))
+                .collect::<Result<_, _>>()?;
+            task_manager.split_task(id, parts, done, delete)?;
+        }
+        Commands::Recent { n } => {
+            task_manager.recent_tasks(n);
+        }
+        Commands::RescheduleOverdue { days, yes } => {
+            task_manager.reschedule_overdue(days, yes, cli.batch)?;
+        }
+        Commands::Changes { since } => {
+            task_manager.changes_since(since)?;
+        }
+        Commands::Archive => {
+            task_manager.archive_done()?;
+        }
+        Commands::BulkEdit { force, yes, dry_run } => {
+            task_manager.bulk_edit(force, yes, cli.batch, dry_run)?;
+        }
+        Commands::ClearDone { yes } => {
+            task_manager.clear_done(yes, cli.batch)?;
+        }
+        Commands::Purge { yes, i_understand: _ } => {
+            task_manager.purge(yes, cli.batch)?;
+        }
+        Commands::Trim { keep, archive, yes } => {
+            task_manager.trim(keep, archive, yes, cli.batch)?;
+        }
+        Commands::Export { fields, bom, crlf, output, open } => {
+            let encoding = output::Encoding {
+                bom,
+                crlf: crlf || cfg!(windows),
+            };
+            task_manager.export_tasks(&fields, encoding, output.as_deref(), open)?;
+        }
+        Commands::Import { path, replace, yes, dry_run } => {
+            task_manager.import_tasks(&path, replace, yes, cli.batch, dry_run)?;
+        }
+        Commands::Undo => {
+            task_manager.undo()?;
+        }
+        Commands::History { limit, json } => {
+            task_manager.show_history(limit, json)?;
+        }
+        Commands::Info { json } => {
+            let config_path = cli.config.as_deref().unwrap_or(Path::new(DEFAULT_CONFIG));
+            task_manager.show_info(config_path, json)?;
+        }
+        Commands::Redo => {
+            task_manager.redo()?;
+        }
+        Commands::Backup => task_manager.backup()?,
+        Commands::Backups => task_manager.list_backups()?,
+        Commands::RestoreBackup { .. } | Commands::Doctor { .. } => {
+            unreachable!("handled before loading tasks")
+        }
+        Commands::Version { json } => print_version(json)?,
+        Commands::Complete { prefix } => {
+            task_manager.complete_descriptions(&prefix);
+        }
+        Commands::Projects => {
+            task_manager.list_projects();
+        }
+        Commands::Estimates => {
+            task_manager.estimates_report();
+        }
+        Commands::Stats { by_tag, compact, separator, streak, days } => {
+            if streak {
+                task_manager.streak_report(days)?;
+            } else {
+                task_manager.stats(by_tag, compact, separator)?;
+            }
+        }
+        Commands::Peek => {
+            task_manager.peek();
+        }
+        Commands::Watch { watch_interval } => {
+            task_manager.watch(std::time::Duration::from_secs(watch_interval))?;
+        }
+        Commands::Notify => {
+            task_manager.notify()?;
+        }
+        Commands::Shell => {
+            shell(&cli, task_manager)?;
+        }
+        Commands::Review { snooze } => {
+            review(task_manager, snooze)?;
         }
-        Commands::List => {
-            task_manager.list_tasks();
+        Commands::Focus { target: None } => {
+            task_manager.show_focus();
         }
-        Commands::Done { index } => {
-            task_manager.mark_task_done(index);
+        Commands::Focus {
+            target: Some(FocusTarget::Clear),
+        } => {
+            task_manager.stop_focus()?;
         }
-        Commands::Delete { index } => {
-            task_manager.delete_task(index);
+        Commands::Focus {
+            target: Some(FocusTarget::Task(id)),
+        } => {
+            task_manager.start_focus(id)?;
+        }
+        Commands::Plan { save_partial } => {
+            plan(task_manager, cli.strict, save_partial)?;
+        }
+        Commands::Done {
+            indices,
+            id,
+            note,
+            exact,
+            keep_order,
+            force,
+            targets,
+            yes,
+        } => {
+            let note = note.map(|note| note.trim().to_string());
+            if note.as_deref() == Some("") {
+                return Err(TodoError::EmptyNote);
+            }
+            if let Some(mut filter) = targets.filter(task_manager)? {
+                filter.status = Some(TaskStatus::Pending);
+                let question = ("Mark them done?", prompt::confirm_listed as Confirm);
+                if let Some(positions) =
+                    confirm_targets(task_manager, &filter, force, question, yes, cli.batch)
+                {
+                    task_manager.mark_tasks_done(&positions, false, force, note.as_deref())?;
+                }
+                return Ok(());
+            }
+            let indices = match exact {
+                Some(description) => vec![task_manager.position_of_description(&description)?],
+                None if id => task_manager.positions_of(&indices),
+                None => indices,
+            };
+            task_manager.mark_tasks_done(&indices, keep_order, force, note.as_deref())?;
+        }
+        Commands::Delete { done: true, yes, .. } => {
+            task_manager.clear_done(yes, cli.batch)?;
+        }
+        Commands::Delete {
+            indices,
+            id,
+            keep_order,
+            force,
+            targets,
+            yes,
+            ..
+        } => {
+            if let Some(filter) = targets.filter(task_manager)? {
+                let question = ("Delete them?", prompt::confirm_removal as Confirm);
+                if let Some(positions) =
+                    confirm_targets(task_manager, &filter, force, question, yes, cli.batch)
+                {
+                    task_manager.delete_tasks(&positions, false, force, true, cli.batch)?;
+                }
+                return Ok(());
+            }
+            let indices = if id { task_manager.positions_of(&indices) } else { indices };
+            task_manager.delete_tasks(&indices, keep_order, force, yes, cli.batch)?;
         }
     }
 
     Ok(())
 }
 
-fn parse_due_date(date_str: &str) -> Result<DateTime<FixedOffset>, String> {
-    let naive_datetime = NaiveDateTime::parse_from_str(date_str, "%Y-%m-%d %H:%M")
+/// For `--strict-index`: fails on the first task index or id `command`
+/// names that doesn't exist, before anything is changed.
+fn check_references(command: &Commands, task_manager: &TaskManager) -> Result<(), TodoError> {
+    match command {
+        Commands::Done {
+            indices, id: false, ..
+        }
+        | Commands::Delete {
+            indices, id: false, ..
+        } => task_manager.check_indices(indices),
+        Commands::Done { indices, .. } | Commands::Delete { indices, .. } => {
+            task_manager.check_ids(indices)
+        }
+        Commands::Add { depends_on, .. } => task_manager.check_ids(depends_on),
+        Commands::Modify { id, after, .. } => {
+            task_manager.check_ids(&[*id])?;
+            task_manager.check_ids(after)
+        }
+        Commands::Tag { ids, .. } | Commands::Untag { ids, .. } => task_manager.check_ids(ids),
+        Commands::Show { id, .. }
+        | Commands::AddSubtask { parent_id: id, .. }
+        | Commands::Check { parent_id: id, .. }
+        | Commands::RecurPreview { id, .. }
+        | Commands::Lock { id }
+        | Commands::Unlock { id }
+        | Commands::Annotate { id, .. }
+        | Commands::Touch { id }
+        | Commands::Clone { id, .. }
+        | Commands::Renumber { old_id: id, .. }
+        | Commands::Split { id, .. } => task_manager.check_ids(&[*id]),
+        _ => Ok(()),
+    }
+}
+
+/// How many dates after the first `add --preview` shows.
+const PREVIEW_OCCURRENCES: usize = 3;
+
+/// Whole seconds, at least one.
+fn parse_interval(text: &str) -> Result<u64, String> {
+    match text.parse::<u64>() {
+        Ok(0) | Err(_) => Err(format!("'{}' isn't a whole number of seconds above 0", text)),
+        Ok(seconds) => Ok(seconds),
+    }
+}
+
+/// `descriptions` with `-` replaced by what stdin holds: one description per
+/// non-empty line, or all of it as one when `whole`. Fails when that leaves
+/// no description at all.
+fn stdin_descriptions(descriptions: Vec<String>, whole: bool) -> Result<Vec<String>, TodoError> {
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+    let read: Vec<String> = if whole {
+        vec![input.trim().to_string()]
+    } else {
+        input.lines().map(|line| line.trim().to_string()).collect()
+    };
+    let read: Vec<String> = read.into_iter().filter(|text| !text.is_empty()).collect();
+    let mut read = Some(read);
+    let descriptions: Vec<String> = descriptions
+        .into_iter()
+        .flat_map(|d| if d == "-" { read.take().unwrap_or_default() } else { vec![d] })
+        .collect();
+    if descriptions.is_empty() {
+        return Err(TodoError::MissingDescription);
+    }
+    Ok(descriptions)
+}
+
+/// "Recurs weekly: due <first>, then <next>, <after that>, …" for a
+/// repeating task with a due date.
+fn recurrence_preview(task: &Task, ellipsis: &str) -> Option<String> {
+    let (recurrence, due) = (task.recurrence.as_ref()?, task.due_date?);
+    let next: Vec<String> = recurrence
+        .occurrences(due, PREVIEW_OCCURRENCES)
+        .into_iter()
+        .map(display::format_datetime)
+        .collect();
+    Some(format!(
+        "Recurs {}: due {}, then {}, {}",
+        recurrence.describe(),
+        display::format_datetime(due),
+        next.join(", "),
+        ellipsis
+    ))
+}
+
+/// Parses 'YYYY-MM-DD HH:MM' or, unless `strict`, 'YYYY-MM-DD' at
+/// `date_only_time`, in IST. 'YYYY-MM-DD HH:MM +HH:MM' names the offset
+/// explicitly and is converted to IST.
+fn parse_due_date(
+    date_str: &str,
+    strict: bool,
+    date_only_time: NaiveTime,
+) -> Result<DateTime<FixedOffset>, String> {
+    if let Ok(datetime) = DateTime::parse_from_str(date_str, "%Y-%m-%d %H:%M %:z") {
+        return Ok(task::whole_minute(datetime.with_timezone(&task::ist())));
+    }
+    if let Some((datetime, zone)) = date_str.trim().rsplit_once(' ')
+        && looks_like_zone_name(zone)
+        && NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M").is_ok()
+    {
+        return Err(format!(
+            "Time zone '{}' needs the IANA time zone database, which this build doesn't \
+             include. Pass a fixed offset instead, e.g. '{} +05:30'.",
+            zone, datetime
+        ));
+    }
+    let full = NaiveDateTime::parse_from_str(date_str, "%Y-%m-%d %H:%M");
+    if strict {
+        full.as_ref().map_err(|e| {
+            format!(
+                "Invalid date format '{}'. Strict mode requires 'YYYY-MM-DD HH:MM'. Error: {}",
+                date_str, e
+            )
+        })?;
+    }
+    let naive_datetime = full
         .or_else(|_| {
             NaiveDate::parse_from_str(date_str, "%Y-%m-%d")
                 .map_err(|e| format!("Invalid date: {}. Error: {}", date_str, e))
-                .and_then(|date| {
-                    date.and_hms_opt(0, 0, 0)
-                        .ok_or_else(|| format!("Could not create time 00:00:00 for date: {}", date_str))
-                })
+                .map(|date| date.and_time(date_only_time))
         })
         .map_err(|e| format!(
-            "Invalid date format '{}'. Expected 'YYYY-MM-DD HH:MM' or 'YYYY-MM-DD'. Error: {}",
+            "Invalid date format '{}'. Expected 'YYYY-MM-DD HH:MM', 'YYYY-MM-DD' or \
+             'YYYY-MM-DD HH:MM +HH:MM'. Error: {}",
             date_str, e
         ))?;
 
-    let ist_offset = FixedOffset::east_opt(5 * 3600 + 30 * 60)
-        .ok_or_else(|| "Failed to create IST offset.".to_string())?;
+    // IST has no daylight-saving changes, so every local time maps to exactly
+    // one instant; this only fails at the very edge of the dates chrono handles.
+    let datetime = task::local_time(&task::ist(), naive_datetime).ok_or_else(|| {
+        format!("Local time '{}' is outside the range of dates IST can represent.", date_str)
+    })?;
+
+    Ok(task::whole_minute(datetime))
+}
+
+/// Parses a UTC offset such as '+05:30' or '-04:00', or 'IST'.
+fn parse_offset(text: &str) -> Result<FixedOffset, String> {
+    let text = text.trim();
+    if text.eq_ignore_ascii_case("ist") {
+        return Ok(task::ist());
+    }
+    // Parsed as part of a date so chrono's offset syntax applies exactly.
+    let dated = format!("2000-01-01 00:00 {}", text);
+    if let Ok(datetime) = DateTime::parse_from_str(&dated, "%Y-%m-%d %H:%M %:z") {
+        return Ok(*datetime.offset());
+    }
+    if looks_like_zone_name(text) {
+        return Err(format!(
+            "Time zone '{}' needs the IANA time zone database, which this build doesn't \
+             include. Pass a fixed offset instead, e.g. +05:30.",
+            text
+        ));
+    }
+    Err(format!("Invalid offset '{}'. Expected '+HH:MM' or '-HH:MM', e.g. -04:00.", text))
+}
+
+/// Whether `word` reads as an IANA zone such as 'Europe/London' or an
+/// abbreviation such as 'UTC', neither of which dates can name without zone
+/// data; only fixed offsets work.
+fn looks_like_zone_name(word: &str) -> bool {
+    let area_location = word.contains('/')
+        && word.split('/').all(|part| {
+            part.starts_with(|c: char| c.is_ascii_alphabetic())
+                && part.chars().all(|c| c.is_ascii_alphanumeric() || "_-+".contains(c))
+        });
+    let abbreviation =
+        (2..=5).contains(&word.len()) && word.chars().all(|c| c.is_ascii_uppercase());
+    area_location || abbreviation
+}
+
+/// A time of day as people write it: '9am', '5:30pm', '17:30', 'noon' or
+/// 'midnight' (the start of the day).
+fn parse_clock(word: &str) -> Option<NaiveTime> {
+    match word {
+        "noon" => return NaiveTime::from_hms_opt(12, 0, 0),
+        "midnight" => return Some(NaiveTime::MIN),
+        _ => {}
+    }
+    let (clock, pm) = match word.strip_suffix("am") {
+        Some(clock) => (clock, Some(false)),
+        None => match word.strip_suffix("pm") {
+            Some(clock) => (clock, Some(true)),
+            None => (word, None),
+        },
+    };
+    let (hour, minute) = clock.split_once(':').unwrap_or((clock, "00"));
+    if minute.len() != 2 || hour.is_empty() || hour.len() > 2 {
+        return None;
+    }
+    let (mut hour, minute): (u32, u32) = (hour.parse().ok()?, minute.parse().ok()?);
+    if let Some(pm) = pm {
+        if !(1..=12).contains(&hour) {
+            return None;
+        }
+        hour = hour % 12 + if pm { 12 } else { 0 };
+    } else if !clock.contains(':') {
+        // A bare number is too easily a typo for a date to read as an hour.
+        return None;
+    }
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+/// The day part of a due date in words.
+#[derive(Debug, Clone, Copy)]
+enum DayWord {
+    Today,
+    Tomorrow,
+    Weekday(Weekday),
+}
+
+impl DayWord {
+    fn parse(word: &str) -> Option<DayWord> {
+        match word {
+            "today" => Some(DayWord::Today),
+            "tomorrow" => Some(DayWord::Tomorrow),
+            // At least "mon", so a stray letter isn't taken for a day.
+            word if word.len() >= 3 => word.parse().ok().map(DayWord::Weekday),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a due date written in words, relative to `now`: a day ('today',
+/// 'tomorrow' or a weekday such as 'fri'), a time as `parse_clock` reads it,
+/// or both, e.g. 'tomorrow 9am' or 'friday noon'. A weekday is the next one
+/// whose time is still ahead, today included, and a time alone is today's
+/// unless it has passed, then tomorrow's. A day alone gets `default_time`,
+/// which `strict` refuses. `None` when `text` isn't written this way.
+fn parse_natural_due(
+    text: &str,
+    now: DateTime<FixedOffset>,
+    default_time: NaiveTime,
+    strict: bool,
+) -> Option<Result<DateTime<FixedOffset>, String>> {
+    let text = text.trim().to_lowercase();
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let (day, time) = match words[..] {
+        [word] => match (DayWord::parse(word), parse_clock(word)) {
+            (None, None) => return None,
+            (day, time) => (day, time),
+        },
+        [first, second] => {
+            let day = DayWord::parse(first)?;
+            let Some(time) = parse_clock(second) else {
+                return Some(Err(format!(
+                    "Invalid time '{}'. Use e.g. 9am, 5:30pm, 17:30, noon or midnight.",
+                    second
+                )));
+            };
+            (Some(day), Some(time))
+        }
+        _ => return None,
+    };
+    if strict && time.is_none() {
+        return Some(Err(format!(
+            "'{}' has no time; strict mode requires one, e.g. '{} 9am'.",
+            text, text
+        )));
+    }
+    let time = time.unwrap_or(default_time);
+    let today = now.date_naive();
+    let at = |date: NaiveDate| task::local_time(&now.timezone(), date.and_time(time));
+    let still_ahead = |date: &NaiveDate| at(*date).is_some_and(|due| due > now);
+    let date = match day {
+        Some(DayWord::Today) => Some(today),
+        Some(DayWord::Tomorrow) => today.succ_opt(),
+        None => [Some(today), today.succ_opt()].into_iter().flatten().find(still_ahead),
+        Some(DayWord::Weekday(weekday)) => (0..=7)
+            .filter_map(|ahead| today.checked_add_signed(Duration::days(ahead)))
+            .filter(|date| date.weekday() == weekday)
+            .find(still_ahead),
+    };
+    Some(date.and_then(at).ok_or_else(|| format!("'{}' is out of range.", text)))
+}
+
+/// Parses a `--due` value: the absolute formats of `parse_due_date`, a day
+/// and time in words as `parse_natural_due` reads them, or `+` and a
+/// duration counted from `relative_to` (default now). A duration written in
+/// days or weeks only, e.g. `+3d`, lands on that date at the default due
+/// time, like a date written without one; one with hours or minutes, e.g.
+/// `+2h` or `+24h`, is added to the exact time.
+fn parse_due_arg(
+    task_manager: &TaskManager,
+    date_str: &str,
+    strict: bool,
+    relative_to: Option<DateTime<FixedOffset>>,
+) -> Result<DateTime<FixedOffset>, TodoError> {
+    let time = task_manager.default_due_time();
+    let base = relative_to.unwrap_or_else(task::now_ist);
+    if let Some(due) = parse_natural_due(date_str, base, time, strict) {
+        return due.map(task::whole_minute).map_err(TodoError::InvalidDate);
+    }
+    let Some(offset) = date_str.trim().strip_prefix('+') else {
+        return parse_due_date(date_str, strict, time).map_err(TodoError::InvalidDate);
+    };
+    let by = parse_duration(offset).map_err(TodoError::InvalidDuration)?;
+    let out_of_range =
+        || TodoError::InvalidDate(format!("Due date '{}' is out of range.", date_str));
+    let in_days = offset.chars().filter(char::is_ascii_alphabetic).all(|c| "dw".contains(c));
+    let due = if in_days {
+        let date = base.date_naive().checked_add_signed(by).ok_or_else(out_of_range)?;
+        task::local_time(&base.timezone(), date.and_time(time)).ok_or_else(out_of_range)?
+    } else {
+        base.checked_add_signed(by).ok_or_else(out_of_range)?
+    };
+    Ok(task::whole_minute(due))
+}
+
+/// Parses an exact RFC 3339 timestamp, falling back to the `--due` formats.
+fn parse_timestamp(text: &str) -> Result<DateTime<FixedOffset>, String> {
+    DateTime::parse_from_rfc3339(text).or_else(|_| parse_due_date(text, false, NaiveTime::MIN))
+}
+
+/// The names tasks are grouped by, which all follow the same rules.
+#[derive(Debug, Clone, Copy)]
+enum NameKind {
+    Tag,
+    Context,
+    Project,
+    Assignee,
+}
+
+impl NameKind {
+    /// What may be typed before the name, as in `#work` or `@phone`.
+    fn marker(self) -> Option<char> {
+        match self {
+            NameKind::Tag => Some('#'),
+            NameKind::Context => Some('@'),
+            NameKind::Project | NameKind::Assignee => None,
+        }
+    }
+}
+
+/// Trims `name` and drops a leading marker, so `--tag "#work"` means `work`
+/// and `--context @phone` means `phone`, then lowercases it unless the
+/// `case_sensitive_names` setting is on. Names are letters, digits, `-`, `_`,
+/// `.` and `/`, so `Work` and `work ` can't end up as two tags.
+fn normalize_name(
+    task_manager: &TaskManager,
+    kind: NameKind,
+    name: &str,
+) -> Result<String, TodoError> {
+    let name = name.trim();
+    let name = kind.marker().and_then(|marker| name.strip_prefix(marker)).unwrap_or(name);
+    let allowed = |c: char| c.is_alphanumeric() || "-_./".contains(c);
+    if name.is_empty() || !name.chars().all(allowed) {
+        let kind = match kind {
+            NameKind::Tag => "Tag",
+            NameKind::Context => "Context",
+            NameKind::Project => "Project",
+            NameKind::Assignee => "Assignee",
+        };
+        return Err(TodoError::InvalidName { kind, name: name.to_string() });
+    }
+    Ok(if task_manager.case_sensitive_names() { name.to_string() } else { name.to_lowercase() })
+}
 
-    let datetime = ist_offset
-        .from_local_datetime(&naive_datetime)
-        .single()
-        .ok_or_else(|| format!("Ambiguous or non-existent local time: '{}'", date_str))?;
+/// `normalize_name` for each of `names`, dropping repeats it produces.
+fn normalize_names(
+    task_manager: &TaskManager,
+    kind: NameKind,
+    names: Vec<String>,
+) -> Result<Vec<String>, TodoError> {
+    let mut normalized: Vec<String> = Vec::new();
+    for name in names {
+        let name = normalize_name(task_manager, kind, &name)?;
+        if !normalized.contains(&name) {
+            normalized.push(name);
+        }
+    }
+    Ok(normalized)
+}
+
+/// `normalize_name` for an assignee, with `me` standing for the current user.
+fn normalize_assignee(
+    task_manager: &TaskManager,
+    assignee: Option<String>,
+) -> Result<Option<String>, TodoError> {
+    let Some(name) = assignee else {
+        return Ok(None);
+    };
+    let name = if name.trim() == "me" {
+        task_manager.current_user().ok_or(TodoError::UnknownUser)?
+    } else {
+        name
+    };
+    normalize_name(task_manager, NameKind::Assignee, &name).map(Some)
+}
+
+fn normalize_project(
+    task_manager: &TaskManager,
+    project: Option<String>,
+) -> Result<Option<String>, TodoError> {
+    project.map(|name| normalize_name(task_manager, NameKind::Project, &name)).transpose()
+}
+
+/// What `split_shorthand` found in a description.
+struct Shorthand {
+    description: String,
+    priority: Option<Priority>,
+    tags: Vec<String>,
+    contexts: Vec<String>,
+}
+
+/// Splits todo.txt-style markers out of an `add` description: a word of one
+/// `!` means medium priority and two or more high, `#tag` adds a tag and
+/// `@context` a context. The remaining words make the description, joined by
+/// single spaces.
+fn split_shorthand(description: &str) -> Shorthand {
+    let mut shorthand = Shorthand {
+        description: String::new(),
+        priority: None,
+        tags: Vec::new(),
+        contexts: Vec::new(),
+    };
+    let mut words = Vec::new();
+    for word in description.split_whitespace() {
+        let push_new = |names: &mut Vec<String>, name: &str| {
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+        };
+        if let Some(tag) = word.strip_prefix('#').filter(|tag| !tag.is_empty()) {
+            push_new(&mut shorthand.tags, tag);
+        } else if let Some(context) = word.strip_prefix('@').filter(|c| !c.is_empty()) {
+            push_new(&mut shorthand.contexts, context);
+        } else if !word.is_empty() && word.chars().all(|c| c == '!') {
+            let priority = if word.len() == 1 { Priority::Medium } else { Priority::High };
+            shorthand.priority = Some(priority);
+        } else {
+            words.push(word);
+        }
+    }
+    shorthand.description = words.join(" ");
+    shorthand
+}
+
+/// Accepts `scheme:rest` with a letter-first scheme (`https:`, `mailto:`,
+/// ...) and no whitespace, which is as far as a URL is checked.
+fn parse_url(text: &str) -> Result<String, String> {
+    let invalid = || format!("'{}' doesn't look like a URL, e.g. https://example.com", text);
+    let (scheme, rest) = text.split_once(':').ok_or_else(invalid)?;
+    let scheme_ok = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+    if !scheme_ok || rest.is_empty() || text.chars().any(char::is_whitespace) {
+        return Err(invalid());
+    }
+    Ok(text.to_string())
+}
+
+/// Parses a task id in any form `--id-format` displays: decimal, zero-padded
+/// decimal or hex with an `x` (or `0x`) prefix.
+fn parse_id(text: &str) -> Result<usize, String> {
+    let hex = text.strip_prefix("0x").or_else(|| text.strip_prefix('x'));
+    match hex {
+        Some(digits) => usize::from_str_radix(digits, 16),
+        None => text.parse(),
+    }
+    .map_err(|e| match e.kind() {
+        IntErrorKind::PosOverflow => format!("'{}' is too large to be a task id", text),
+        _ => format!("'{}' is not a task id (expected e.g. 7, 007 or x7)", text),
+    })
+}
+
+/// What `focus` is given: a task to focus on, or `clear`.
+#[derive(Debug, Clone, Copy)]
+enum FocusTarget {
+    Task(usize),
+    Clear,
+}
 
-    Ok(datetime)
+fn parse_focus_target(text: &str) -> Result<FocusTarget, String> {
+    if text == "clear" {
+        Ok(FocusTarget::Clear)
+    } else {
+        parse_id(text).map(FocusTarget::Task)
+    }
+}
+
+/// Parses durations such as `45m`, `1h`, `2d`, `1w` or combinations like `1h30m`.
+fn parse_duration(duration_str: &str) -> Result<Duration, String> {
+    let invalid = || {
+        format!(
+            "Invalid duration '{}'. Expected a number followed by m, h, d or w, e.g. '30m' or '1h30m'.",
+            duration_str
+        )
+    };
+    let mut total = Duration::zero();
+    let mut digits = String::new();
+    for c in duration_str.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let amount: i64 = digits.parse().map_err(|_| invalid())?;
+        digits.clear();
+        let part = match c {
+            'm' => Duration::try_minutes(amount),
+            'h' => Duration::try_hours(amount),
+            'd' => Duration::try_days(amount),
+            'w' => Duration::try_weeks(amount),
+            _ => None,
+        };
+        total = part
+            .and_then(|part| total.checked_add(&part))
+            .ok_or_else(invalid)?;
+    }
+    if !digits.is_empty() || total.is_zero() {
+        return Err(invalid());
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(text).unwrap()
+    }
+
+    /// A manager over a task file that is never written, for its defaults.
+    fn task_manager(name: &str) -> TaskManager {
+        let dir = std::env::temp_dir().join(format!("todo_cli-{}-{}", name, std::process::id()));
+        TaskManager::new(dir.join("tasks.json"), Config::default()).unwrap()
+    }
+
+    #[test]
+    fn due_date_with_an_offset_is_stored_in_ist() {
+        let due = parse_due_date("2026-03-01 09:00 -04:00", false, NaiveTime::MIN).unwrap();
+        assert_eq!(due, at("2026-03-01T18:30:00+05:30"));
+        assert_eq!(due.offset(), &task::ist());
+        let due = parse_due_date("2026-03-01 09:00 +00:00", true, NaiveTime::MIN).unwrap();
+        assert_eq!(due, at("2026-03-01T14:30:00+05:30"));
+    }
+
+    #[test]
+    fn due_date_without_an_offset_is_read_as_ist() {
+        let due = parse_due_date("2026-03-01 09:00", true, NaiveTime::MIN).unwrap();
+        assert_eq!(due, at("2026-03-01T09:00:00+05:30"));
+    }
+
+    #[test]
+    fn zone_names_are_rejected_with_an_offset_hint() {
+        for zone in ["America/New_York", "Europe/London", "UTC", "EST"] {
+            let error = parse_due_date(&format!("2026-03-01 09:00 {}", zone), false, NaiveTime::MIN)
+                .unwrap_err();
+            assert!(error.contains("IANA"), "{}", error);
+            assert!(error.contains("'2026-03-01 09:00 +05:30'"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn parses_fixed_offsets_and_ist() {
+        assert_eq!(parse_offset("+05:30"), Ok(task::ist()));
+        assert_eq!(parse_offset("ist"), Ok(task::ist()));
+        assert_eq!(parse_offset(" -04:00 "), FixedOffset::west_opt(4 * 3600).ok_or(String::new()));
+        assert!(parse_offset("+25:00").is_err());
+        assert!(parse_offset("soon").unwrap_err().starts_with("Invalid offset"));
+        assert!(parse_offset("Asia/Tokyo").unwrap_err().contains("IANA"));
+    }
+
+    #[test]
+    fn relative_days_snap_to_the_due_time_but_hours_do_not() {
+        let task_manager = task_manager("relative");
+        let base = Some(at("2026-03-01T10:15:00+05:30"));
+        let due = |text: &str| parse_due_arg(&task_manager, text, false, base).unwrap();
+        assert_eq!(due("+1d"), at("2026-03-02T23:59:00+05:30"));
+        assert_eq!(due("+1w"), at("2026-03-08T23:59:00+05:30"));
+        assert_eq!(due("+24h"), at("2026-03-02T10:15:00+05:30"));
+        assert_eq!(due("+48h"), at("2026-03-03T10:15:00+05:30"));
+        assert_eq!(due("+1d30m"), at("2026-03-02T10:45:00+05:30"));
+    }
+
+    /// `text` read by `parse_natural_due` at `now`, with 23:59 as the default.
+    fn natural(text: &str, now: &str) -> Option<Result<DateTime<FixedOffset>, String>> {
+        let default_time = NaiveTime::from_hms_opt(23, 59, 0).unwrap();
+        parse_natural_due(text, at(now), default_time, false)
+    }
+
+    // 2026-03-06 is a Friday.
+    const FRIDAY_MORNING: &str = "2026-03-06T10:00:00+05:30";
+
+    #[test]
+    fn natural_due_combines_a_day_and_a_time() {
+        let due = natural("tomorrow 9am", FRIDAY_MORNING).unwrap();
+        assert_eq!(due, Ok(at("2026-03-07T09:00:00+05:30")));
+        let due = natural("Friday noon", FRIDAY_MORNING).unwrap();
+        assert_eq!(due, Ok(at("2026-03-06T12:00:00+05:30")));
+        let due = natural("friday noon", "2026-03-06T13:00:00+05:30").unwrap();
+        assert_eq!(due, Ok(at("2026-03-13T12:00:00+05:30")));
+        let due = natural("mon", FRIDAY_MORNING).unwrap();
+        assert_eq!(due, Ok(at("2026-03-09T23:59:00+05:30")));
+    }
+
+    #[test]
+    fn natural_time_alone_rolls_over_once_passed() {
+        let due = natural("9am", FRIDAY_MORNING).unwrap();
+        assert_eq!(due, Ok(at("2026-03-07T09:00:00+05:30")));
+        let due = natural("5:30pm", FRIDAY_MORNING).unwrap();
+        assert_eq!(due, Ok(at("2026-03-06T17:30:00+05:30")));
+    }
+
+    #[test]
+    fn clock_reads_twelve_and_rejects_bad_hours() {
+        assert_eq!(parse_clock("12am"), Some(NaiveTime::MIN));
+        assert_eq!(parse_clock("12pm"), NaiveTime::from_hms_opt(12, 0, 0));
+        assert_eq!(parse_clock("17:30"), NaiveTime::from_hms_opt(17, 30, 0));
+        assert_eq!(parse_clock("13pm"), None);
+        assert_eq!(parse_clock("0am"), None);
+        assert_eq!(parse_clock("9"), None);
+        assert!(natural("tomorrow 13pm", FRIDAY_MORNING).unwrap().is_err());
+        assert!(natural("tomorrow 9", FRIDAY_MORNING).unwrap().is_err());
+
+        let task_manager = task_manager("clock");
+        for text in ["13pm", "9"] {
+            assert!(parse_due_arg(&task_manager, text, false, Some(at(FRIDAY_MORNING))).is_err());
+        }
+    }
+
+    #[test]
+    fn strict_natural_due_needs_a_time() {
+        let default_time = NaiveTime::from_hms_opt(23, 59, 0).unwrap();
+        let due = parse_natural_due("friday", at(FRIDAY_MORNING), default_time, true).unwrap();
+        assert!(due.unwrap_err().contains("strict mode requires one"));
+        let due = parse_natural_due("friday 9am", at(FRIDAY_MORNING), default_time, true);
+        assert_eq!(due, Some(Ok(at("2026-03-13T09:00:00+05:30"))));
+    }
+
+    #[test]
+    fn iso_dates_are_not_read_as_words() {
+        assert_eq!(natural("2026-03-10 08:00", FRIDAY_MORNING), None);
+        assert_eq!(natural("2026-03-10", FRIDAY_MORNING), None);
+        let task_manager = task_manager("iso");
+        let due = parse_due_arg(&task_manager, "2026-03-10 08:00", false, Some(at(FRIDAY_MORNING)));
+        assert_eq!(due.unwrap(), at("2026-03-10T08:00:00+05:30"));
+    }
+
+    #[test]
+    fn ids_past_usize_max_are_too_large() {
+        assert_eq!(parse_id(&usize::MAX.to_string()), Ok(usize::MAX));
+        let past = (u128::try_from(usize::MAX).unwrap() + 1).to_string();
+        assert_eq!(parse_id(&past), Err(format!("'{}' is too large to be a task id", past)));
+        let hex_past = format!("x1{}", "0".repeat(usize::BITS as usize / 4));
+        assert!(parse_id(&hex_past).unwrap_err().contains("too large"));
+        assert!(parse_id("seven").unwrap_err().contains("is not a task id"));
+    }
+
+    #[test]
+    fn local_time_before_the_earliest_ist_instant_is_an_error() {
+        // The earliest date chrono handles, read as IST, is 5:30 before the
+        // first instant it can represent, so no instant matches.
+        let error = parse_due_date("-262143-01-01 05:29", false, NaiveTime::MIN).unwrap_err();
+        assert!(error.starts_with("Local time '-262143-01-01 05:29'"), "{}", error);
+        assert!(parse_due_date("-262143-01-01 05:30", false, NaiveTime::MIN).is_ok());
+    }
 }
@@ -1,11 +1,210 @@
-use crate::task::{Task, TaskStatus};
-use serde::{Deserialize, Serialize};
-use std::cell::RefCell;
+use crate::bulk;
+use crate::config::{Config, RetentionAction};
+use crate::display::{self, ColorChoice, Markers, GREEN, RED, YELLOW};
+use crate::error::{self, ErrorFormat, TodoError};
+use crate::filter::TaskFilter;
+use crate::focus::Focus;
+use crate::group::{self, GroupBy};
+use crate::sort::{self, EffectivePriority, Nulls, SortKey, SortSpec};
+use crate::history::{self, History, Operation};
+use crate::html;
+use crate::pager;
+use crate::prompt;
+use crate::regex::Regex;
+use crate::deps;
+use crate::doctor;
+use crate::output::{self, OutputFormat};
+use crate::table::{self, Separator, Table};
+use crate::todotxt;
+use crate::task::{self, now_ist, Annotation, Priority, SubTask, Task, TaskStatus};
+use crate::streak;
+use crate::urgency;
+use serde::{Deserialize, Serialize, Serializer as _};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::rc::Rc;
-use chrono::{DateTime, FixedOffset};
+use std::time::SystemTime;
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDateTime, NaiveTime};
+
+/// Characters between the brackets of a `projects` progress bar.
+const PROGRESS_BAR_WIDTH: usize = 20;
+
+/// Longest bar `stats --streak` draws for a day.
+const STREAK_BAR_WIDTH: usize = 30;
+
+/// When a `backup` was taken, as its file name gives it.
+const BACKUP_TIME_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// A column that `list --fields` can display.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum ListField {
+    Id,
+    Status,
+    Description,
+    Due,
+    /// Time until the due date, e.g. "due in 3h" or "2d overdue"
+    Until,
+    /// Comma-separated tags
+    Tags,
+    /// Comma-separated contexts
+    Contexts,
+    Project,
+    Assignee,
+    Priority,
+    /// Subtask progress, e.g. "2/5"
+    Subtasks,
+    /// Urgency score, as `--sort urgency` orders by
+    Urgency,
+}
+
+impl ListField {
+    /// The task key the field stands for in JSON output; `until` and
+    /// `urgency` are only ever computed for display.
+    fn json_key(self) -> Option<&'static str> {
+        match self {
+            ListField::Id => Some("id"),
+            ListField::Status => Some("status"),
+            ListField::Description => Some("description"),
+            ListField::Due => Some("due_date"),
+            ListField::Until | ListField::Urgency => None,
+            ListField::Tags => Some("tags"),
+            ListField::Contexts => Some("contexts"),
+            ListField::Project => Some("project"),
+            ListField::Assignee => Some("assignee"),
+            ListField::Priority => Some("priority"),
+            ListField::Subtasks => Some("subtasks"),
+        }
+    }
+}
+
+/// The JSON keys for `list --json --fields`, or `None` to print whole tasks.
+fn json_keys(fields: Option<&[ListField]>) -> Result<Option<Vec<&'static str>>, TodoError> {
+    let Some(fields) = fields else {
+        return Ok(None);
+    };
+    fields
+        .iter()
+        .map(|field| {
+            field.json_key().ok_or_else(|| {
+                let name = format!("{:?}", field).to_lowercase();
+                TodoError::InvalidField(format!("The {} field is only shown in tables.", name))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+}
+
+/// What `search` looks for in task descriptions.
+#[derive(Debug)]
+pub enum SearchQuery {
+    /// A substring, ignoring case.
+    Text(String),
+    /// A pattern given with `--regex`.
+    Regex(Regex),
+}
+
+impl SearchQuery {
+    fn matches(&self, description: &str) -> bool {
+        match self {
+            SearchQuery::Text(text) => description.to_lowercase().contains(&text.to_lowercase()),
+            SearchQuery::Regex(regex) => regex.is_match(description),
+        }
+    }
+}
+
+impl std::fmt::Display for SearchQuery {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SearchQuery::Text(text) => write!(f, "\"{}\"", text),
+            SearchQuery::Regex(regex) => write!(f, "/{}/", regex),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ListOptions {
+    pub filter: TaskFilter,
+    /// Columns for the table view; `None` uses the default line format.
+    pub fields: Option<Vec<ListField>>,
+    /// Line width to fit descriptions into. `None` disables truncation.
+    pub width: Option<usize>,
+    pub group_by: Option<GroupBy>,
+    /// Sort keys, most significant first; empty keeps file order.
+    pub sort: Vec<SortSpec>,
+    /// Placement of undated tasks when sorting by due date or tag.
+    pub nulls: Nulls,
+    /// Coefficients for `--sort urgency`.
+    pub urgency: urgency::Weights,
+    /// Pending tasks created longer ago than this are counted in the footer
+    /// and, with `stale_marker`, marked "[stale]".
+    pub max_age: Option<Duration>,
+    pub stale_marker: bool,
+    /// Keep only the first this many tasks after filtering and sorting.
+    pub limit: Option<usize>,
+    /// Print a "5 tasks: 3 pending, 2 done, 1 overdue" footer.
+    pub summary: bool,
+    /// Frame the list with the "--- Your ToDo Tasks ---" header, a closing
+    /// separator and blank lines around both.
+    pub header: bool,
+    /// Send output longer than the terminal through `$PAGER`.
+    pub pager: bool,
+    /// Wrap descriptions onto indented extra lines instead of truncating them
+    /// to `width`.
+    pub wrap: bool,
+    /// Nest each task's prerequisites under it.
+    pub tree: bool,
+    /// Column separator for the `fields` table.
+    pub separator: Separator,
+}
+
+impl ListOptions {
+    /// The tasks matching the filter, in the requested order.
+    fn select<'a>(&self, tasks: &'a [Task]) -> Vec<&'a Task> {
+        let mut selected = self.filter.apply(tasks);
+        if !self.sort.is_empty() {
+            sort::sort_tasks(&mut selected, &self.sort, self.nulls, tasks, &self.urgency);
+        }
+        if let Some(limit) = self.limit {
+            selected.truncate(limit);
+        }
+        selected
+    }
+
+    /// The age past which tasks get the "[stale]" marker, if they get one.
+    fn stale_marker_after(&self) -> Option<Duration> {
+        self.max_age.filter(|_| self.stale_marker)
+    }
+}
+
+/// Edits for `modify`; `None` and empty fields leave the task as it is.
+#[derive(Debug, Default)]
+pub struct TaskChanges {
+    pub description: Option<String>,
+    pub due_date: Option<DateTime<FixedOffset>>,
+    pub priority: Option<Priority>,
+    /// Ids to add to `depends_on`.
+    pub after: Vec<usize>,
+    pub add_tags: Vec<String>,
+    pub remove_tags: Vec<String>,
+    /// Mark the task someday, dropping its due date and repeat rule.
+    pub someday: bool,
+    pub project: Option<String>,
+    /// Take the task out of its project.
+    pub clear_project: bool,
+    pub assignee: Option<String>,
+    /// Leave the task unassigned.
+    pub clear_assignee: bool,
+    pub estimate: Option<Duration>,
+    /// Drop the task's estimate.
+    pub clear_estimate: bool,
+    pub wait_until: Option<DateTime<FixedOffset>>,
+    /// Stop the task waiting, so it shows up again.
+    pub clear_wait: bool,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TaskManager {
@@ -13,108 +212,3644 @@ pub struct TaskManager {
     pub tasks: Rc<RefCell<Vec<Task>>>,
     #[serde(skip)]
     file_path: PathBuf,
+    #[serde(skip)]
+    config: Config,
+    #[serde(skip)]
+    pub quiet: bool,
+    #[serde(skip)]
+    pub verbose: bool,
+    #[serde(skip)]
+    pub error_format: ErrorFormat,
+    #[serde(skip)]
+    pub color: ColorChoice,
+    #[serde(skip)]
+    pub markers: Markers,
+    #[serde(skip)]
+    pub format: OutputFormat,
+    /// Task id to position in `tasks`, for lookups by id without a scan.
+    /// Rebuilt on load and save, and whenever a lookup finds it out of date.
+    #[serde(skip)]
+    index: RefCell<HashMap<usize, usize>>,
+    /// The task `focus` is on, read from its file on load.
+    #[serde(skip)]
+    focus: RefCell<Option<Focus>>,
+    /// Modification time of the task file when it was loaded or last saved;
+    /// a different one at save time means another process changed it.
+    #[serde(skip)]
+    loaded_mtime: Cell<Option<SystemTime>>,
+    /// The task list as last loaded or saved, serialized, so that a save
+    /// which would write the same thing again can be skipped.
+    #[serde(skip)]
+    last_saved: RefCell<String>,
+    /// Save even when the task file changed on disk since it was loaded.
+    #[serde(skip)]
+    pub overwrite: bool,
     next_id: usize,
 }
 
 impl TaskManager {
-    pub fn new(file_name: &str) -> io::Result<Self> {
-        let file_path = PathBuf::from(file_name);
+    pub fn new(file_path: PathBuf, config: Config) -> Result<Self, TodoError> {
         let mut manager = TaskManager {
             tasks: Rc::new(RefCell::new(Vec::new())),
             file_path,
+            config,
+            quiet: false,
+            verbose: false,
+            error_format: ErrorFormat::Human,
+            color: ColorChoice::Auto,
+            markers: Markers::default(),
+            format: OutputFormat::Human,
+            index: RefCell::new(HashMap::new()),
+            focus: RefCell::new(None),
+            loaded_mtime: Cell::new(None),
+            last_saved: RefCell::new(String::new()),
+            overwrite: false,
             next_id: 0,
         };
         manager.load_tasks()?;
+        match Focus::load(&Focus::path_for(&manager.file_path)) {
+            Ok(focus) => *manager.focus.borrow_mut() = focus,
+            Err(e) => eprintln!("Warning: could not read the focused task: {}", e),
+        }
         Ok(manager)
     }
 
-    fn load_tasks(&mut self) -> io::Result<()> {
+    /// Copy of the task file as it was before the most recent save.
+    fn backup_path(file_path: &Path) -> PathBuf {
+        file_path.with_extension("bak")
+    }
+
+    /// Copy of a task file that failed to parse, kept for `restore-backup`.
+    fn corrupt_path(file_path: &Path) -> PathBuf {
+        file_path.with_extension("corrupt")
+    }
+
+    fn load_tasks(&mut self) -> Result<(), TodoError> {
+        self.loaded_mtime.set(modified(&self.file_path));
         if self.file_path.exists() {
             let data = fs::read_to_string(&self.file_path)?;
-            let loaded_tasks: Vec<Task> = serde_json::from_str(&data)?;
+            let mut loaded_tasks: Vec<Task> = match serde_json::from_str(&data) {
+                Ok(tasks) => tasks,
+                Err(e) => {
+                    let corrupt_path = Self::corrupt_path(&self.file_path);
+                    if fs::write(&corrupt_path, &data).is_ok() {
+                        eprintln!(
+                            "Warning: {} could not be parsed; a copy was saved to {}. \
+                             Run `restore-backup` to recover the last good version.",
+                            self.file_path.display(),
+                            corrupt_path.display()
+                        );
+                    }
+                    return Err(e.into());
+                }
+            };
             self.next_id = loaded_tasks
                 .iter()
                 .map(|t| t.id)
                 .max()
-                .map_or(0, |max_id| max_id + 1);
+                .map_or(Some(0), |max_id| max_id.checked_add(1))
+                .ok_or(TodoError::IdsExhausted)?;
+            let loaded_at = now_ist();
+            for task in loaded_tasks.iter_mut().filter(|t| t.updated_at.is_none()) {
+                task.updated_at = Some(task.created_at.unwrap_or(loaded_at));
+            }
             *self.tasks.borrow_mut() = loaded_tasks;
         } else {
             *self.tasks.borrow_mut() = Vec::new();
             self.next_id = 0;
         }
+        *self.last_saved.borrow_mut() = serde_json::to_string_pretty(&*self.tasks.borrow())?;
+        self.reindex(&self.tasks.borrow());
         Ok(())
     }
 
-    pub fn save_tasks(&self) -> io::Result<()> {
-        let data = serde_json::to_string_pretty(&*self.tasks.borrow())?;
-        let mut file = fs::File::create(&self.file_path)?;
+    fn reindex(&self, tasks: &[Task]) {
+        let mut index = self.index.borrow_mut();
+        index.clear();
+        // In reverse, so a duplicated id maps to its first task as a scan would.
+        for (position, task) in tasks.iter().enumerate().rev() {
+            index.insert(task.id, position);
+        }
+    }
+
+    /// Position of the task with `id` in `tasks`. Uses the index while it
+    /// agrees with `tasks`, and rebuilds it when it doesn't.
+    fn position_of_id(&self, tasks: &[Task], id: usize) -> Option<usize> {
+        let cached = self.index.borrow().get(&id).copied();
+        if let Some(position) = cached
+            && tasks.get(position).is_some_and(|t| t.id == id)
+        {
+            return Some(position);
+        }
+        self.reindex(tasks);
+        self.index.borrow().get(&id).copied()
+    }
+
+    fn task_by_id<'a>(&self, tasks: &'a [Task], id: usize) -> Option<&'a Task> {
+        self.position_of_id(tasks, id).map(|position| &tasks[position])
+    }
+
+    fn task_by_id_mut<'a>(&self, tasks: &'a mut [Task], id: usize) -> Option<&'a mut Task> {
+        self.position_of_id(tasks, id).map(|position| &mut tasks[position])
+    }
+
+    /// Writes `data` to a temporary file and renames it over the task file at
+    /// `path`, so a crash mid-write never leaves a half-written file behind,
+    /// keeping the previous contents as the `.bak` backup. Refuses, unless
+    /// `overwrite`, when the file's modification time is no longer
+    /// `loaded_mtime`, as another process changed it since it was read.
+    fn write_task_file(
+        path: &Path,
+        data: &str,
+        loaded_mtime: Option<SystemTime>,
+        overwrite: bool,
+    ) -> io::Result<()> {
+        if !overwrite && modified(path) != loaded_mtime {
+            return Err(io::Error::other(format!(
+                "{} changed on disk since it was loaded, probably by another todo_cli, \
+                 so nothing was saved. Run the command again, or pass --overwrite to \
+                 replace those changes.",
+                path.display()
+            )));
+        }
+        let temp_path = path.with_extension("tmp");
+        let mut file = fs::File::create(&temp_path)?;
         file.write_all(data.as_bytes())?;
+        file.sync_all()?;
+        if path.exists() {
+            fs::copy(path, Self::backup_path(path))?;
+        }
+        fs::rename(&temp_path, path)
+    }
+
+    /// Writes the task list with `write_task_file`. Nothing is written when
+    /// the list is unchanged since it was loaded or last saved, or is empty
+    /// while there is no task file, which is left uncreated; nor, unless
+    /// `overwrite`, when the file changed on disk since, which is an error.
+    /// Returns whether it wrote anything.
+    pub fn save_tasks(&self) -> io::Result<bool> {
+        let data = serde_json::to_string_pretty(&*self.tasks.borrow())?;
+        if data == *self.last_saved.borrow() {
+            self.debug("no changes to save");
+            return Ok(false);
+        }
+        if self.tasks.borrow().is_empty() && !self.file_path.exists() {
+            self.debug(&format!("no tasks, so {} is not created", self.file_path.display()));
+            *self.last_saved.borrow_mut() = data;
+            return Ok(false);
+        }
+        if let Some(parent) = self.file_path.parent()
+            && !parent.as_os_str().is_empty()
+            && !parent.exists()
+        {
+            fs::create_dir_all(parent).map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("could not create directory {}: {}", parent.display(), e),
+                )
+            })?;
+            self.debug(&format!("created directory {}", parent.display()));
+        }
+        Self::write_task_file(&self.file_path, &data, self.loaded_mtime.get(), self.overwrite)?;
+        self.loaded_mtime.set(modified(&self.file_path));
+        self.reindex(&self.tasks.borrow());
+        self.debug(&format!(
+            "wrote {} task(s), {} bytes, to {}",
+            self.tasks.borrow().len(),
+            data.len(),
+            self.file_path.display()
+        ));
+        *self.last_saved.borrow_mut() = data;
+        Ok(true)
+    }
+
+    /// The directory `backup` writes the task file at `file_path` to.
+    fn backup_dir(file_path: &Path, config: &Config) -> PathBuf {
+        config.backup_dir.clone().unwrap_or_else(|| file_path.with_file_name("backups"))
+    }
+
+    /// Name of a `backup` copy of the task file at `file_path` taken at `at`,
+    /// e.g. `tasks-20240601-120000.json`, so names sort oldest first.
+    fn backup_name(file_path: &Path, at: DateTime<FixedOffset>) -> String {
+        format!("{}-{}.json", Self::backup_stem(file_path), at.format(BACKUP_TIME_FORMAT))
+    }
+
+    fn backup_stem(file_path: &Path) -> String {
+        file_path.file_stem().unwrap_or_default().to_string_lossy().into_owned()
+    }
+
+    /// The `backup` copies of the task file at `file_path`, oldest first.
+    fn backup_files(file_path: &Path, config: &Config) -> io::Result<Vec<PathBuf>> {
+        let dir = Self::backup_dir(file_path, config);
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let prefix = format!("{}-", Self::backup_stem(file_path));
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let taken = name.strip_prefix(&prefix).and_then(|rest| rest.strip_suffix(".json"));
+            if taken.is_some_and(|t| NaiveDateTime::parse_from_str(t, BACKUP_TIME_FORMAT).is_ok())
+            {
+                files.push(path);
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// The backup `name`, as `backups` lists it, with or without `.json`.
+    pub fn named_backup(
+        file_path: &Path,
+        config: &Config,
+        name: &str,
+    ) -> Result<PathBuf, TodoError> {
+        let dir = Self::backup_dir(file_path, config);
+        [dir.join(name), dir.join(format!("{}.json", name))]
+            .into_iter()
+            .find(|path| path.is_file())
+            .ok_or_else(|| TodoError::BackupNotFound(name.to_string()))
+    }
+
+    /// Copies the task file into the backup directory under a timestamped
+    /// name, then deletes the oldest copies beyond `keep_backups`.
+    pub fn backup(&self) -> Result<(), TodoError> {
+        if !self.file_path.exists() {
+            println!("Nothing to back up: {} doesn't exist yet.", self.file_path.display());
+            return Ok(());
+        }
+        let dir = Self::backup_dir(&self.file_path, &self.config);
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(Self::backup_name(&self.file_path, now_ist()));
+        fs::copy(&self.file_path, &path)?;
+        self.debug(&format!("Wrote {}", path.display()));
+        self.info(&format!(
+            "Backed up {} task(s) to {}.",
+            self.tasks.borrow().len(),
+            path.display()
+        ));
+        let files = Self::backup_files(&self.file_path, &self.config)?;
+        let keep = self.config.keep_backups;
+        if keep > 0 && files.len() > keep {
+            let old = &files[..files.len() - keep];
+            for file in old {
+                fs::remove_file(file)?;
+                self.debug(&format!("Removed {}", file.display()));
+            }
+            self.info(&format!("Removed {} old backup(s), keeping {}.", old.len(), keep));
+        }
         Ok(())
     }
 
-    pub fn add_task(&mut self, description: String, due_date: Option<DateTime<FixedOffset>>) {
-        let new_task = Task::new(self.next_id, description, due_date);
-        self.tasks.borrow_mut().push(new_task);
-        self.next_id += 1;
-        if let Err(e) = self.save_tasks() {
-            eprintln!("Error saving tasks: {}", e);
+    /// Lists the `backup` copies, newest first, with how many tasks each has.
+    pub fn list_backups(&self) -> io::Result<()> {
+        let files = Self::backup_files(&self.file_path, &self.config)?;
+        if files.is_empty() {
+            let dir = Self::backup_dir(&self.file_path, &self.config);
+            println!("No backups in {}. Run `backup` to take one.", dir.display());
+            return Ok(());
+        }
+        for file in files.iter().rev() {
+            let tasks = fs::read_to_string(file)
+                .ok()
+                .and_then(|data| serde_json::from_str::<Vec<Task>>(&data).ok());
+            let name = file.file_name().unwrap_or_default().to_string_lossy();
+            match tasks {
+                Some(tasks) => println!("{}  {} task(s)", name, tasks.len()),
+                None => println!("{}  unreadable", name),
+            }
         }
-        println!("Task added successfully.");
+        Ok(())
     }
 
-    pub fn list_tasks(&self) {
-        let tasks = self.tasks.borrow();
-        if tasks.is_empty() {
-            println!("No tasks found. Add one using `todo_cli add \"My task\"`");
-            return;
+    /// Replaces the task file at `file_path` with `backup`, or else the newest
+    /// of its `.bak` and `.corrupt` copies that still parses, after asking for
+    /// confirmation (skipped with `yes`). Runs without loading the task file,
+    /// since that may be the file that is broken.
+    pub fn restore_backup(
+        file_path: &Path,
+        backup: Option<&Path>,
+        yes: bool,
+        batch: bool,
+    ) -> Result<(), TodoError> {
+        let automatic = [Self::backup_path(file_path), Self::corrupt_path(file_path)];
+        let mut candidates: Vec<(std::time::SystemTime, PathBuf)> =
+            backup.map_or(automatic.to_vec(), |backup| vec![backup.to_path_buf()])
+                .into_iter()
+                .filter_map(|path| {
+                    let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+                    Some((modified, path))
+                })
+                .collect();
+        if candidates.is_empty() {
+            println!("No backup found for {}.", file_path.display());
+            return Ok(());
+        }
+        candidates.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+        let mut restorable = None;
+        let mut last_error = None;
+        for (_, path) in candidates {
+            let data = fs::read_to_string(&path)?;
+            match serde_json::from_str::<Vec<Task>>(&data) {
+                Ok(tasks) => {
+                    restorable = Some((path, data, tasks));
+                    break;
+                }
+                Err(e) => {
+                    eprintln!("Warning: skipping {}: {}", path.display(), e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        let Some((backup_path, data, tasks)) = restorable else {
+            return Err(last_error.expect("every candidate failed to parse").into());
+        };
+        let question = format!(
+            "Replace {} with {} ({} task(s))?",
+            file_path.display(),
+            backup_path.display(),
+            tasks.len()
+        );
+        if !yes && !prompt::confirm(&question, batch) {
+            println!("Restore cancelled.");
+            return Ok(());
         }
+        fs::write(file_path, data)?;
+        println!(
+            "Restored {} task(s) from {}.",
+            tasks.len(),
+            backup_path.display()
+        );
+        Ok(())
+    }
 
-        println!("\n--- Your ToDo Tasks ---");
-        for task in tasks.iter() {
-            let status_char = match task.status {
-                TaskStatus::Pending => ' ',
-                TaskStatus::Done => 'x',
-            };
-            let due_date_str = if let Some(dt) = task.due_date {
-                format!(" (Due: {})", dt.format("%Y-%m-%d %H:%M IST"))
+    /// Checks the task file at `file_path` for problems and prints them; with
+    /// `fix`, repairs the fixable ones and saves them with `write_task_file`,
+    /// which refuses, unless `overwrite`, if the file changed meanwhile. Runs
+    /// without loading the file, which may not load. With `json`, prints
+    /// `{"findings": [...]}` instead, plus `"fixes"` under `fix`. Fails when
+    /// an error-severity problem is left unfixed.
+    pub fn doctor(
+        file_path: &Path,
+        fix: bool,
+        json: bool,
+        overwrite: bool,
+    ) -> Result<(), TodoError> {
+        if !file_path.exists() {
+            if json {
+                let report = serde_json::json!({ "findings": [] });
+                println!("{}", serde_json::to_string_pretty(&report)?);
             } else {
-                String::new()
+                println!("{} does not exist yet; nothing to check.", file_path.display());
+            }
+            return Ok(());
+        }
+        let loaded_mtime = modified(file_path);
+        let data = fs::read_to_string(file_path)?;
+        let mut tasks: Vec<serde_json::Value> = match serde_json::from_str(&data) {
+            Ok(tasks) => tasks,
+            Err(e) => {
+                let path = file_path.display();
+                let message = format!("{} is not a JSON array of tasks: {}", path, e);
+                if json {
+                    let findings = [doctor::not_a_task_list(message)];
+                    let report = serde_json::json!({ "findings": findings });
+                    println!("{}", serde_json::to_string_pretty(&report)?);
+                } else {
+                    println!("{}", message);
+                    println!("Run `restore-backup` to recover the last good version.");
+                }
+                return Err(TodoError::DoctorErrors(1));
+            }
+        };
+        let findings = doctor::examine(&tasks, now_ist());
+        let fixes = if fix { doctor::repair(&mut tasks) } else { Vec::new() };
+        if !fixes.is_empty() {
+            let data = serde_json::to_string_pretty(&tasks)?;
+            Self::write_task_file(file_path, &data, loaded_mtime, overwrite)?;
+        }
+        let errors = findings
+            .iter()
+            .filter(|f| f.severity == doctor::Severity::Error && !(fix && f.fixable))
+            .count();
+        if json {
+            let report = match fix {
+                true => serde_json::json!({ "findings": findings, "fixes": fixes }),
+                false => serde_json::json!({ "findings": findings }),
             };
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            Self::print_doctor_report(&tasks, &findings, fix, fixes.len());
+        }
+        match errors {
+            0 => Ok(()),
+            errors => Err(TodoError::DoctorErrors(errors)),
+        }
+    }
+
+    fn print_doctor_report(
+        tasks: &[serde_json::Value],
+        findings: &[doctor::Finding],
+        fix: bool,
+        fixed: usize,
+    ) {
+        if findings.is_empty() {
+            println!("Checked {} task(s): no problems found.", tasks.len());
+            return;
+        }
+        for finding in findings {
+            let note = if finding.fixable { " [fixable]" } else { "" };
+            println!("- {}{}", finding.message, note);
+        }
+        let fixable = findings.iter().filter(|f| f.fixable).count();
+        if !fix {
             println!(
-                "[{}] {}. {}{}",
-                status_char, task.id, task.description, due_date_str
+                "{} problem(s) found, {} fixable with `doctor --fix`.",
+                findings.len(),
+                fixable
+            );
+            return;
+        }
+        println!(
+            "Fixed {} problem(s); {} need(s) fixing by hand.",
+            fixed,
+            findings.len() - fixable
+        );
+    }
+
+    /// Gives every task after the first one holding an id a fresh id, so the
+    /// id-based commands stay unambiguous after hand edits. Under `strict` a
+    /// duplicate is an error instead.
+    pub fn fix_duplicate_ids(&mut self, strict: bool) -> Result<(), TodoError> {
+        let mut seen = std::collections::HashSet::new();
+        let mut renumbered = 0;
+        for task in self.tasks.borrow_mut().iter_mut() {
+            if seen.insert(task.id) {
+                continue;
+            }
+            if strict {
+                return Err(TodoError::DuplicateId(task.id));
+            }
+            eprintln!(
+                "Warning: task \"{}\" had duplicate id {}; renumbered to {}.",
+                task.description, task.id, self.next_id
             );
+            task.id = self.next_id;
+            self.next_id = self.next_id.checked_add(1).ok_or(TodoError::IdsExhausted)?;
+            renumbered += 1;
+        }
+        if renumbered > 0 {
+            self.save_tasks()?;
+        }
+        Ok(())
+    }
+
+    /// Prints the `--verbose` diagnostics about where tasks were loaded from.
+    pub fn report_load(&self) {
+        self.debug(&format!("database: {}", absolute(&self.file_path).display()));
+        if !self.file_path.exists() {
+            self.debug("database file does not exist yet; starting empty");
+        }
+        self.debug(&format!("loaded {} task(s)", self.tasks.borrow().len()));
+        self.debug("timezone: IST (UTC+05:30)");
+    }
+
+    fn archive_path(&self) -> PathBuf {
+        self.file_path.with_extension("archive.json")
+    }
+
+    /// The tasks in the archive file; none when there is no archive yet.
+    fn load_archive(&self) -> io::Result<Vec<Task>> {
+        let archive_path = self.archive_path();
+        if !archive_path.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(&archive_path)?)?)
+    }
+
+    /// Moves the tasks matching `should_archive` out of the task list and
+    /// appends them to the archive file next to it. Returns how many moved.
+    /// When the task file can't be saved afterwards, the archive file and
+    /// the loaded tasks are put back as they were, so the tasks aren't left
+    /// in both files.
+    fn archive_where(&self, should_archive: impl Fn(&Task) -> bool) -> io::Result<usize> {
+        let original = self.tasks.borrow().clone();
+        let (archived, kept): (Vec<Task>, Vec<Task>) =
+            original.iter().cloned().partition(|t| should_archive(t));
+        if archived.is_empty() {
+            return Ok(0);
+        }
+
+        let archive_path = self.archive_path();
+        let previous = match archive_path.exists() {
+            true => Some(fs::read_to_string(&archive_path)?),
+            false => None,
+        };
+        let mut archive = self.load_archive()?;
+        let count = archived.len();
+        archive.extend(archived);
+        fs::write(&archive_path, serde_json::to_string_pretty(&archive)?)?;
+        self.debug(&format!("appended {} task(s) to {}", count, archive_path.display()));
+        *self.tasks.borrow_mut() = kept;
+        if let Err(e) = self.save_tasks() {
+            *self.tasks.borrow_mut() = original;
+            let restored = match previous {
+                Some(previous) => fs::write(&archive_path, previous),
+                None => fs::remove_file(&archive_path),
+            };
+            if let Err(restore_error) = restored {
+                eprintln!(
+                    "Warning: could not take the tasks back out of {}: {}",
+                    archive_path.display(),
+                    restore_error
+                );
+            }
+            return Err(e);
         }
-        println!("-----------------------\n");
+        Ok(count)
+    }
+
+    pub fn archive_done(&self) -> Result<(), TodoError> {
+        match self.archive_where(|t| !t.is_pending())? {
+            0 => self.info("No completed tasks to archive."),
+            count => self.info(&format!(
+                "Archived {} completed task(s) to {}.",
+                count,
+                self.archive_path().display()
+            )),
+        }
+        Ok(())
     }
 
-    pub fn mark_task_done(&self, index: usize) {
+    /// Deletes every completed task that isn't locked, after listing them and
+    /// asking unless `yes` is set. Unlike `archive_done`, no copy is kept, but
+    /// `undo` brings them back.
+    pub fn clear_done(&self, yes: bool, batch: bool) -> Result<(), TodoError> {
+        let positions: Vec<usize> = self
+            .tasks
+            .borrow()
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| !t.is_pending() && !t.locked)
+            .map(|(position, _)| position)
+            .collect();
+        if positions.is_empty() {
+            self.info("No completed tasks to clear.");
+            return Ok(());
+        }
+        if !yes {
+            let tasks = self.tasks.borrow();
+            let descriptions: Vec<&str> =
+                positions.iter().map(|&p| tasks[p].description.as_str()).collect();
+            if !prompt::confirm_removal("Delete them?", &descriptions, batch) {
+                println!("Nothing deleted.");
+                return Ok(());
+            }
+        }
         let mut tasks = self.tasks.borrow_mut();
-        if let Some(task) = tasks.get_mut(index) {
-            if task.is_pending() {
-                task.mark_done();
-                if let Err(e) = self.save_tasks() {
-                    eprintln!("Error saving tasks: {}", e);
-                }
-                println!("Task {} marked as done.", task.id);
-            } else {
-                println!("Task {} is already done.", task.id);
+        let mut removed: Vec<(usize, Task)> = removal_order(&positions)
+            .into_iter()
+            .map(|position| (position, tasks.remove(position)))
+            .collect();
+        drop(tasks);
+        removed.reverse();
+        let count = removed.len();
+        self.save_and_record(
+            format!("Cleared {} completed task(s)", count),
+            Operation::Delete { removed },
+        )?;
+        self.info(&format!("Deleted {} completed task(s).", count));
+        Ok(())
+    }
+
+    /// Deletes every task, locked ones too, once `PURGE` is typed at the
+    /// prompt; `yes` skips it (the CLI only allows that with
+    /// `--i-understand`). `undo` brings the tasks back.
+    pub fn purge(&self, yes: bool, batch: bool) -> Result<(), TodoError> {
+        let tasks = self.tasks.borrow();
+        if tasks.is_empty() {
+            self.info("No tasks to purge.");
+            return Ok(());
+        }
+        if !yes {
+            let locked = tasks.iter().filter(|t| t.locked).count();
+            eprintln!(
+                "All {} task(s) will be destroyed, pending, done and locked ({}) alike.",
+                tasks.len(),
+                locked
+            );
+            if !prompt::confirm_typed("Delete every task?", "PURGE", batch) {
+                println!("Nothing purged.");
+                return Ok(());
+            }
+        }
+        drop(tasks);
+        let removed: Vec<(usize, Task)> = self.tasks.borrow_mut().drain(..).enumerate().collect();
+        let count = removed.len();
+        self.save_and_record(format!("Purged {} task(s)", count), Operation::Delete { removed })?;
+        self.info(&format!("Purged {} task(s).", count));
+        Ok(())
+    }
+
+    /// Keeps the `keep` most recently created tasks, pending ones first, and
+    /// removes the rest after listing them and asking unless `yes`. Locked
+    /// tasks are always kept, on top of `keep`. With `archive` the removed
+    /// tasks go to the archive file as with `archive`; otherwise they are
+    /// deleted, and `undo` brings them back.
+    pub fn trim(
+        &self,
+        keep: usize,
+        archive: bool,
+        yes: bool,
+        batch: bool,
+    ) -> Result<(), TodoError> {
+        let tasks = self.tasks.borrow();
+        let positions = beyond_keep(&tasks, keep, |_| false);
+        if positions.is_empty() {
+            self.info(&format!("Nothing to trim: there are no more than {} task(s).", keep));
+            return Ok(());
+        }
+        if !yes {
+            let descriptions: Vec<&str> =
+                positions.iter().map(|&p| tasks[p].description.as_str()).collect();
+            let question = if archive { "Archive them?" } else { "Delete them?" };
+            if !prompt::confirm_removal(question, &descriptions, batch) {
+                println!("Nothing trimmed.");
+                return Ok(());
             }
-        } else {
-            println!("Invalid task index: {}. Use `list` to see available tasks.", index);
         }
+        let ids: Vec<usize> = positions.iter().map(|&p| tasks[p].id).collect();
+        drop(tasks);
+        if archive {
+            let count = self.archive_where(|t| ids.contains(&t.id))?;
+            self.info(&format!(
+                "Trimmed {} task(s) into {}.",
+                count,
+                self.archive_path().display()
+            ));
+            return Ok(());
+        }
+        let count = self.delete_positions(&positions, "Trimmed")?;
+        self.info(&format!("Trimmed {} task(s).", count));
+        Ok(())
+    }
+
+    /// Deletes the tasks at `positions` as one undo step recorded as
+    /// "<verb> N task(s)". Returns how many were deleted.
+    fn delete_positions(&self, positions: &[usize], verb: &str) -> Result<usize, TodoError> {
+        let mut tasks = self.tasks.borrow_mut();
+        let mut removed: Vec<(usize, Task)> = removal_order(positions)
+            .into_iter()
+            .map(|position| (position, tasks.remove(position)))
+            .collect();
+        drop(tasks);
+        removed.reverse();
+        let count = removed.len();
+        self.save_and_record(format!("{} {} task(s)", verb, count), Operation::Delete { removed })?;
+        Ok(count)
     }
 
-    pub fn delete_task(&self, index: usize) {
+    /// Writes every task to a temporary file, one line each, opens it in the
+    /// user's editor and applies what changed there: new descriptions,
+    /// removed tasks and added ones. The changes are listed and confirmed
+    /// unless `yes`, rejected as a whole if any line is invalid or touches a
+    /// locked task without `force`, and undone as one step. With `dry_run`
+    /// the changes are summed up and nothing is written.
+    pub fn bulk_edit(
+        &mut self,
+        force: bool,
+        yes: bool,
+        batch: bool,
+        dry_run: bool,
+    ) -> Result<(), TodoError> {
+        let file_name = format!("todo_cli-bulk-{}.txt", std::process::id());
+        let path = std::env::temp_dir().join(file_name);
+        fs::write(&path, bulk::render(&self.tasks.borrow()))?;
+        let edited = bulk::edit(&path).and_then(|()| fs::read_to_string(&path));
+        // Best effort: the file is only a scratch copy.
+        let _ = fs::remove_file(&path);
+        let changes = bulk::parse(&edited?, &self.tasks.borrow()).map_err(TodoError::BulkEdit)?;
+        if changes.is_empty() {
+            self.info("No changes.");
+            return Ok(());
+        }
+        let renamed = changes
+            .renamed
+            .into_iter()
+            .map(|(id, description)| Ok((id, self.check_description(description, false)?)))
+            .collect::<Result<Vec<_>, TodoError>>()?;
+        let added = changes
+            .added
+            .into_iter()
+            .map(|description| self.check_description(description, false))
+            .collect::<Result<Vec<_>, TodoError>>()?;
+        let tasks = self.tasks.borrow();
+        let is_locked = |id: usize| self.task_by_id(&tasks, id).is_some_and(|t| t.locked);
+        let changed_ids = renamed.iter().map(|(id, _)| *id).chain(changes.removed.iter().copied());
+        if !force && let Some(id) = changed_ids.into_iter().find(|&id| is_locked(id)) {
+            return Err(TodoError::Locked(id));
+        }
+        if dry_run {
+            let removed: Vec<String> = changes
+                .removed
+                .iter()
+                .filter_map(|&id| self.task_by_id(&tasks, id))
+                .map(|task| format!("{}: {}", task.id, task.description))
+                .collect();
+            let edited: Vec<String> = renamed
+                .iter()
+                .map(|(id, description)| format!("{}: {}", id, description))
+                .collect();
+            prompt::print_diff(&added, &removed, &edited);
+            return Ok(());
+        }
+        for (id, description) in &renamed {
+            eprintln!("  ~ {}: {}", id, description);
+        }
+        for &id in &changes.removed {
+            if let Some(task) = self.task_by_id(&tasks, id) {
+                eprintln!("  - {}: {}", id, task.description);
+            }
+        }
+        for description in &added {
+            eprintln!("  + {}", description);
+        }
+        drop(tasks);
+        if !yes && !prompt::confirm("Apply these changes?", batch) {
+            println!("Nothing changed.");
+            return Ok(());
+        }
+
         let mut tasks = self.tasks.borrow_mut();
-        if index < tasks.len() {
-            let removed_task = tasks.remove(index);
-            if let Err(e) = self.save_tasks() {
-                eprintln!("Error saving tasks: {}", e);
+        let mut operations = Vec::new();
+        let (mut before, mut after) = (Vec::new(), Vec::new());
+        for (id, description) in &renamed {
+            if let Some(task) = self.task_by_id_mut(&mut tasks, *id) {
+                before.push(task.clone());
+                task.description = description.clone();
+                task.touch();
+                after.push(task.clone());
             }
-            println!("Task \"{}\" (ID: {}) deleted.", removed_task.description, removed_task.id);
-        } else {
-            println!("Invalid task index: {}. Use `list` to see available tasks.", index);
         }
+        if !after.is_empty() {
+            operations.push(Operation::Update { before, after });
+        }
+        let positions: Vec<usize> =
+            changes.removed.iter().filter_map(|&id| self.position_of_id(&tasks, id)).collect();
+        let mut removed: Vec<(usize, Task)> = removal_order(&positions)
+            .into_iter()
+            .map(|position| (position, tasks.remove(position)))
+            .collect();
+        removed.reverse();
+        if !removed.is_empty() {
+            operations.push(Operation::Delete { removed });
+        }
+        self.check_id_room(added.len())?;
+        let mut new_tasks = Vec::with_capacity(added.len());
+        for description in added {
+            new_tasks.push(Task::new(self.next_id, description, None));
+            self.next_id += 1;
+        }
+        tasks.extend(new_tasks.iter().cloned());
+        drop(tasks);
+        let summary = format!(
+            "Bulk edit: {} renamed, {} deleted, {} added",
+            renamed.len(),
+            changes.removed.len(),
+            new_tasks.len()
+        );
+        if !new_tasks.is_empty() {
+            operations.push(Operation::Add { tasks: new_tasks });
+        }
+        self.save_and_record(summary.clone(), Operation::Batch { operations })?;
+        self.info(&format!("{}.", summary));
+        Ok(())
+    }
+
+    /// Maintenance pass run after loading, applying the retention policy in
+    /// the config: done tasks completed more than `retain_done_days` ago go,
+    /// then, past `max_tasks`, the tasks `trim` would pick, all archived or
+    /// deleted as `retention_action` says. Once applied there is nothing
+    /// left for it to do, so running it on every invocation is harmless.
+    /// Returns how many tasks were removed.
+    pub fn apply_retention(&self) -> Result<usize, TodoError> {
+        let retain_days = self.config.retain_done_days();
+        if retain_days.is_none() && self.config.max_tasks.is_none() {
+            return Ok(0);
+        }
+        let tasks = self.tasks.borrow();
+        let cutoff = retain_days.map(|days| Local::now().fixed_offset() - Duration::days(days));
+        let expired = |t: &Task| {
+            !t.is_pending()
+                && cutoff.is_some_and(|cutoff| t.completed_at.is_some_and(|at| at < cutoff))
+        };
+        let mut positions: Vec<usize> = (0..tasks.len()).filter(|&p| expired(&tasks[p])).collect();
+        let expired_count = positions.len();
+        if let Some(max) = self.config.max_tasks {
+            positions.extend(beyond_keep(&tasks, max, expired));
+            positions.sort_unstable();
+        }
+        if positions.is_empty() {
+            return Ok(0);
+        }
+        let ids: Vec<usize> = positions.iter().map(|&p| tasks[p].id).collect();
+        drop(tasks);
+        let removed = match self.config.retention_action {
+            RetentionAction::Archive => self.archive_where(|t| ids.contains(&t.id))?,
+            RetentionAction::Delete => self.delete_positions(&positions, "Retention removed")?,
+        };
+        self.debug(&format!(
+            "retention: {} {} task(s), {} past retain_done_days and {} over max_tasks",
+            match self.config.retention_action {
+                RetentionAction::Archive => "archived",
+                RetentionAction::Delete => "deleted",
+            },
+            removed,
+            expired_count,
+            removed.saturating_sub(expired_count)
+        ));
+        Ok(removed)
+    }
+
+    /// Trims `description`, rejecting it if nothing is left, and enforces the
+    /// configured `max_description_length`. Over-long descriptions are
+    /// rejected, or shortened with a warning when `truncate` is set.
+    pub fn check_description(
+        &self,
+        description: String,
+        truncate: bool,
+    ) -> Result<String, TodoError> {
+        let description = description.trim().to_string();
+        if description.is_empty() {
+            return Err(TodoError::EmptyDescription);
+        }
+        let Some(max) = self.config.max_description_length else {
+            return Ok(description);
+        };
+        let length = description.chars().count();
+        if length <= max {
+            return Ok(description);
+        }
+        if !truncate {
+            return Err(TodoError::DescriptionTooLong { length, max });
+        }
+        eprintln!("Warning: description truncated to {} characters.", max);
+        Ok(description.chars().take(max).collect())
+    }
+
+    /// The id of a pending task with the same description, ignoring case.
+    pub fn find_duplicate(&self, description: &str) -> Option<usize> {
+        let description = description.to_lowercase();
+        self.tasks
+            .borrow()
+            .iter()
+            .find(|t| t.is_pending() && t.description.to_lowercase() == description)
+            .map(|t| t.id)
+    }
+
+    pub fn default_due_time(&self) -> NaiveTime {
+        self.config.default_due_time
+    }
+
+    pub fn stale_after_days(&self) -> Option<i64> {
+        self.config.stale_after_days
+    }
+
+    pub fn overdue_grace(&self) -> Duration {
+        self.config.overdue_grace
+    }
+
+    pub fn shorthand(&self) -> bool {
+        self.config.shorthand
+    }
+
+    pub fn urgency_weights(&self) -> urgency::Weights {
+        self.config.urgency
+    }
+
+    pub fn someday_position(&self) -> Nulls {
+        self.config.someday_position
+    }
+
+    pub fn case_sensitive_names(&self) -> bool {
+        self.config.case_sensitive_names
+    }
+
+    /// Who `--assignee me` stands for: the config's `user`, else the login
+    /// name from the environment.
+    pub fn current_user(&self) -> Option<String> {
+        self.config
+            .user
+            .clone()
+            .or_else(|| std::env::var("USER").ok())
+            .or_else(|| std::env::var("USERNAME").ok())
+            .filter(|user| !user.trim().is_empty())
+    }
+
+    pub fn default_command(&self) -> Option<&str> {
+        self.config.default_command.as_deref()
+    }
+
+    /// A fresh pending task carrying the next free id, for the caller to fill
+    /// in before passing it to `add_task`.
+    pub fn new_task(
+        &self,
+        description: String,
+        due_date: Option<DateTime<FixedOffset>>,
+    ) -> Result<Task, TodoError> {
+        self.check_id_room(1)?;
+        Ok(Task::new(self.next_id, description, due_date))
+    }
+
+    /// Fails unless `count` more ids can be handed out from `next_id`, so
+    /// that counting it up can't overflow.
+    fn check_id_room(&self, count: usize) -> Result<(), TodoError> {
+        match self.next_id.checked_add(count) {
+            Some(_) => Ok(()),
+            None => Err(TodoError::IdsExhausted),
+        }
+    }
+
+    /// Reports the first of `indices` that isn't a position in the list.
+    pub fn check_indices(&self, indices: &[usize]) -> Result<(), TodoError> {
+        let len = self.tasks.borrow().len();
+        match indices.iter().find(|&&index| index >= len) {
+            Some(&index) => Err(TodoError::InvalidIndex(index)),
+            None => Ok(()),
+        }
+    }
+
+    /// Reports the first of `ids` that doesn't name an existing task.
+    pub fn check_ids(&self, ids: &[usize]) -> Result<(), TodoError> {
+        let tasks = self.tasks.borrow();
+        match ids.iter().find(|id| self.position_of_id(&tasks, **id).is_none()) {
+            Some(&id) => Err(TodoError::InvalidId(id)),
+            None => Ok(()),
+        }
+    }
+
+    /// Appends a task built with `new_task`.
+    pub fn add_task(&mut self, new_task: Task) -> Result<Task, TodoError> {
+        self.tasks.borrow_mut().push(new_task.clone());
+        self.save_and_record(
+            format!("Added task {} \"{}\"", new_task.id, new_task.description),
+            Operation::Add {
+                tasks: vec![new_task.clone()],
+            },
+        )?;
+        self.next_id = self.next_id.max(new_task.id + 1);
+        self.info("Task added successfully.");
+        Ok(new_task)
+    }
+
+    /// Appends `new_tasks`, numbered from the next free id in the order given
+    /// whatever ids they carry, with a single save and undo step. Returns
+    /// them as added.
+    pub fn add_new_tasks(&mut self, mut new_tasks: Vec<Task>) -> Result<Vec<Task>, TodoError> {
+        self.check_id_room(new_tasks.len())?;
+        for task in &mut new_tasks {
+            task.id = self.next_id;
+            self.next_id += 1;
+        }
+        self.tasks.borrow_mut().extend(new_tasks.iter().cloned());
+        let ids: Vec<String> = new_tasks.iter().map(|t| t.id.to_string()).collect();
+        self.save_and_record(
+            format!("Added tasks {}", ids.join(", ")),
+            Operation::Add {
+                tasks: new_tasks.clone(),
+            },
+        )?;
+        self.info(&format!("Added {} task(s): ids {}.", new_tasks.len(), ids.join(", ")));
+        Ok(new_tasks)
+    }
+
+    /// Appends all `items` as new tasks and saves once, so large imports cost a
+    /// single file write instead of one per task.
+    pub fn add_tasks(
+        &mut self,
+        items: Vec<(String, Option<DateTime<FixedOffset>>)>,
+    ) -> Result<usize, TodoError> {
+        let count = items.len();
+        self.check_id_room(count)?;
+        let mut added = Vec::with_capacity(count);
+        for (description, due_date) in items {
+            added.push(Task::new(self.next_id, description, due_date));
+            self.next_id += 1;
+        }
+        if count > 0 {
+            self.tasks.borrow_mut().extend(added.iter().cloned());
+            self.save_and_record(
+                format!("Added {} task(s)", count),
+                Operation::Add { tasks: added },
+            )?;
+        }
+        Ok(count)
+    }
+
+    /// Imports a plain-text file with one task description per line, or a
+    /// JSON array of tasks as written by `export`, or under `--format todotxt`
+    /// a todo.txt file. Blank lines are skipped, and in plain text so are
+    /// lines starting with `#`, which serve as comments.
+    /// Nothing is added if any line is over the configured length limit.
+    /// With `replace`, the imported tasks take the place of all existing ones
+    /// after confirmation unless `yes`: lines are numbered from 0, exported
+    /// tasks keep their ids. Appended exported tasks get fresh ids.
+    /// With `dry_run`, what the import would change is summed up instead.
+    pub fn import_tasks(
+        &mut self,
+        path: &Path,
+        replace: bool,
+        yes: bool,
+        batch: bool,
+        dry_run: bool,
+    ) -> Result<(), TodoError> {
+        let data = fs::read_to_string(path)?;
+        // Left by `export --bom` and by spreadsheet tools.
+        let data = data.strip_prefix('\u{feff}').unwrap_or(&data);
+        if self.format == OutputFormat::Todotxt {
+            let tasks = data
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .enumerate()
+                .map(|(id, (number, line))| {
+                    let time = self.config.default_due_time;
+                    let mut task = todotxt::parse_line(line, id, time).map_err(|e| {
+                        TodoError::InvalidDate(format!("Line {}: {}.", number + 1, e))
+                    })?;
+                    task.description = self.check_description(task.description, false)?;
+                    Ok(task)
+                })
+                .collect::<Result<Vec<Task>, TodoError>>()?;
+            return self.import_snapshot(tasks, path, replace, yes, batch, dry_run);
+        }
+        if data.trim_start().starts_with('[') {
+            let tasks: Vec<Task> = serde_json::from_str(data)?;
+            return self.import_snapshot(tasks, path, replace, yes, batch, dry_run);
+        }
+        let items: Vec<(String, Option<DateTime<FixedOffset>>)> = data
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| Ok((self.check_description(line.to_string(), false)?, None)))
+            .collect::<Result<_, TodoError>>()?;
+        if replace {
+            let added: Vec<Task> = items
+                .into_iter()
+                .enumerate()
+                .map(|(id, (description, due_date))| Task::new(id, description, due_date))
+                .collect();
+            return self.replace_tasks(added, path, yes, batch, dry_run);
+        }
+        if dry_run {
+            let added: Vec<String> =
+                items.into_iter().map(|(description, _)| description).collect();
+            prompt::print_diff(&added, &[], &[]);
+            return Ok(());
+        }
+        let count = self.add_tasks(items)?;
+        self.info(&format!("Imported {} task(s) from {}.", count, path.display()));
+        Ok(())
+    }
+
+    /// Imports tasks read from an `export` file: in place of the whole list
+    /// with `replace`, otherwise appended under fresh ids, with their
+    /// `depends_on` entries renumbered to match (or dropped when they named
+    /// tasks outside the file). A file listing an id twice is rejected.
+    fn import_snapshot(
+        &mut self,
+        mut tasks: Vec<Task>,
+        path: &Path,
+        replace: bool,
+        yes: bool,
+        batch: bool,
+        dry_run: bool,
+    ) -> Result<(), TodoError> {
+        let mut seen = HashSet::new();
+        if let Some(task) = tasks.iter().find(|task| !seen.insert(task.id)) {
+            return Err(TodoError::DuplicateImportId(task.id));
+        }
+        if replace {
+            return self.replace_tasks(tasks, path, yes, batch, dry_run);
+        }
+        if dry_run {
+            let added: Vec<String> = tasks.into_iter().map(|task| task.description).collect();
+            prompt::print_diff(&added, &[], &[]);
+            return Ok(());
+        }
+        self.check_id_room(tasks.len())?;
+        let new_ids: HashMap<usize, usize> = tasks
+            .iter()
+            .enumerate()
+            .map(|(offset, task)| (task.id, self.next_id + offset))
+            .collect();
+        for task in &mut tasks {
+            task.id = new_ids[&task.id];
+            task.depends_on =
+                task.depends_on.iter().filter_map(|id| new_ids.get(id)).copied().collect();
+        }
+        let count = tasks.len();
+        self.next_id += count;
+        if count > 0 {
+            self.tasks.borrow_mut().extend(tasks.iter().cloned());
+            self.save_and_record(
+                format!("Imported {} task(s) from {}", count, path.display()),
+                Operation::Add { tasks },
+            )?;
+        }
+        self.info(&format!("Imported {} task(s) from {}.", count, path.display()));
+        Ok(())
+    }
+
+    /// Swaps the whole task list for `added`, after listing what goes and
+    /// asking unless `yes` is set or the list is empty. With `dry_run` it
+    /// compares the two lists by id instead.
+    fn replace_tasks(
+        &mut self,
+        added: Vec<Task>,
+        path: &Path,
+        yes: bool,
+        batch: bool,
+        dry_run: bool,
+    ) -> Result<(), TodoError> {
+        let next_id = match added.iter().map(|t| t.id).max() {
+            Some(max_id) => max_id.checked_add(1).ok_or(TodoError::IdsExhausted)?,
+            None => 0,
+        };
+        if dry_run {
+            let (new, gone, edited) = replacement_diff(&self.tasks.borrow(), &added);
+            prompt::print_diff(&new, &gone, &edited);
+            return Ok(());
+        }
+        let existing = self.tasks.borrow().len();
+        let question = format!("Replace them with the {} in {}?", added.len(), path.display());
+        let confirmed = {
+            let tasks = self.tasks.borrow();
+            let descriptions: Vec<&str> =
+                tasks.iter().map(|t| t.description.as_str()).collect();
+            existing == 0 || yes || prompt::confirm_removal(&question, &descriptions, batch)
+        };
+        if !confirmed {
+            println!("Import cancelled.");
+            return Ok(());
+        }
+        let removed: Vec<(usize, Task)> =
+            self.tasks.borrow_mut().drain(..).enumerate().collect();
+        self.next_id = next_id;
+        self.tasks.borrow_mut().extend(added.iter().cloned());
+        let count = added.len();
+        self.save_and_record(
+            format!("Replaced {} task(s) with {} from {}", existing, count, path.display()),
+            Operation::Batch {
+                operations: vec![
+                    Operation::Delete { removed },
+                    Operation::Add { tasks: added },
+                ],
+            },
+        )?;
+        self.info(&format!(
+            "Replaced {} task(s) with {} imported from {}.",
+            existing,
+            count,
+            path.display()
+        ));
+        Ok(())
+    }
+
+    /// Prints every task, unfiltered and in file order, as a pretty-printed
+    /// JSON array with all fields, for `import --replace` to restore. With
+    /// `--format csv` it prints the CSV table instead, which is not a
+    /// complete copy.
+    /// `fields`, when given, keeps only those keys (or CSV columns), which
+    /// also makes the export unfit for restoring.
+    /// `--format html` prints a page for people to read instead.
+    /// The output is written as `encoding` says, to `destination` when
+    /// given, which `open` then opens.
+    pub fn export_tasks(
+        &self,
+        fields: &[String],
+        encoding: output::Encoding,
+        destination: Option<&Path>,
+        open: bool,
+    ) -> Result<(), TodoError> {
+        let tasks = self.tasks.borrow();
+        let fields = output::export_fields(fields, self.format).map_err(TodoError::InvalidField)?;
+        let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+        let all: Vec<&Task> = tasks.iter().collect();
+        let mut out = Vec::new();
+        if self.format == OutputFormat::Html {
+            let now = Local::now().fixed_offset();
+            out.extend(html::page(&all, now, self.config.overdue_grace).into_bytes());
+        } else if self.format == OutputFormat::Csv && !fields.is_empty() {
+            output::write_csv_columns(&mut out, &all, &fields)?;
+        } else if matches!(self.format, OutputFormat::Csv | OutputFormat::Todotxt) {
+            output::write_tasks(&mut out, &all, self.format)?;
+        } else {
+            let mut serializer = serde_json::Serializer::pretty(&mut out);
+            if fields.is_empty() {
+                serializer.collect_seq(tasks.iter())?;
+            } else {
+                let picked = tasks.iter().map(|task| output::task_fields(task, &fields));
+                serializer.collect_seq(picked)?;
+            }
+            writeln!(out)?;
+        }
+        let Some(path) = destination else {
+            return Ok(output::write_encoded(io::stdout().lock(), &out, encoding)?);
+        };
+        output::write_encoded(fs::File::create(path)?, &out, encoding)?;
+        self.info(&format!("Exported {} task(s) to {}.", all.len(), path.display()));
+        if open {
+            open_with_default_app(path)?;
+        }
+        Ok(())
+    }
+
+    /// Saves the task list and, if that succeeded, appends `operation` to the
+    /// undo log; nothing is recorded when the save found nothing changed. A
+    /// failed save undoes `operation` in memory, so the loaded tasks still
+    /// match the file, and is returned for the command to fail with. A
+    /// failure to write the log only warns.
+    fn save_and_record(&self, summary: String, operation: Operation) -> Result<(), TodoError> {
+        match self.save_tasks() {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(e) => {
+                operation.revert(&mut self.tasks.borrow_mut());
+                return Err(e.into());
+            }
+        }
+        let path = History::path_for(&self.file_path);
+        let result = History::load(&path).and_then(|mut history| {
+            history.record(summary, operation);
+            history.save(&path)
+        });
+        match result {
+            Ok(()) => self.debug(&format!("recorded history entry in {}", path.display())),
+            Err(e) => eprintln!("Warning: could not update undo history: {}", e),
+        }
+        Ok(())
+    }
+
+    pub fn undo(&self) -> io::Result<()> {
+        let path = History::path_for(&self.file_path);
+        let mut history = History::load(&path)?;
+        let Some(entry) = history.undo() else {
+            println!("Nothing to undo.");
+            return Ok(());
+        };
+        entry.operation.revert(&mut self.tasks.borrow_mut());
+        let summary = entry.summary.clone();
+        self.save_tasks()?;
+        history.save(&path)?;
+        self.info(&format!("Undid: {}", summary));
+        Ok(())
+    }
+
+    pub fn redo(&self) -> io::Result<()> {
+        let path = History::path_for(&self.file_path);
+        let mut history = History::load(&path)?;
+        let Some(entry) = history.redo() else {
+            println!("Nothing to redo.");
+            return Ok(());
+        };
+        entry.operation.apply(&mut self.tasks.borrow_mut());
+        let summary = entry.summary.clone();
+        self.save_tasks()?;
+        history.save(&path)?;
+        self.info(&format!("Redid: {}", summary));
+        Ok(())
+    }
+
+    /// Prints the last `limit` entries of the undo log, newest first, marking
+    /// the ones that are currently undone.
+    pub fn show_history(&self, limit: usize, json: bool) -> io::Result<()> {
+        let history = History::load(&History::path_for(&self.file_path))?;
+        let entries = history.recent(limit);
+        if json {
+            let items: Vec<serde_json::Value> = entries
+                .iter()
+                .map(|(entry, undone)| {
+                    serde_json::json!({
+                        "at": entry.at,
+                        "summary": entry.summary,
+                        "undone": undone,
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&items)?);
+            return Ok(());
+        }
+        if entries.is_empty() {
+            println!("No recorded changes.");
+            return Ok(());
+        }
+        for (entry, undone) in entries {
+            let undone = if undone { " (undone)" } else { "" };
+            println!("{}  {}{}", display::format_datetime(entry.at), entry.summary, undone);
+        }
+        Ok(())
+    }
+
+    /// Prints the settings in effect, for working out why two setups behave
+    /// differently: the version, where the tasks and the config are read
+    /// from, the timezone, colors, default order and date format.
+    pub fn show_info(&self, config_path: &Path, json: bool) -> io::Result<()> {
+        let database = absolute(&self.file_path);
+        let config = absolute(config_path);
+        let color = format!("{:?}", self.color).to_lowercase();
+        let color_enabled = display::color_enabled(self.color);
+        let nulls = format!("{:?}", self.config.someday_position).to_lowercase();
+        let display_timezone = match display::display_offset() {
+            Some(offset) => format!("UTC{}", offset),
+            None => "IST (UTC+05:30)".to_string(),
+        };
+        if json {
+            let info = serde_json::json!({
+                "version": env!("CARGO_PKG_VERSION"),
+                "database": database,
+                "database_exists": self.file_path.exists(),
+                "config": config,
+                "config_found": config_path.exists(),
+                "timezone": "IST (UTC+05:30)",
+                "display_timezone": display_timezone,
+                "color": color,
+                "color_enabled": color_enabled,
+                "sort": "file order",
+                "nulls": nulls,
+                "date_format": display::date_pattern(),
+            });
+            println!("{}", serde_json::to_string_pretty(&info)?);
+            return Ok(());
+        }
+        let missing = |exists: bool, note: &'static str| if exists { "" } else { note };
+        println!("version:     {}", env!("CARGO_PKG_VERSION"));
+        println!(
+            "database:    {}{}",
+            database.display(),
+            missing(self.file_path.exists(), " (not created yet)")
+        );
+        println!(
+            "config:      {}{}",
+            config.display(),
+            missing(config_path.exists(), " (not found; using defaults)")
+        );
+        println!("timezone:    IST (UTC+05:30)");
+        println!("shown in:    {}", display_timezone);
+        println!("color:       {} ({})", color, if color_enabled { "on" } else { "off" });
+        println!("sort:        file order, undated tasks {}", nulls);
+        println!("date format: {}", display::date_pattern());
+        Ok(())
+    }
+
+    /// Reports a problem that does not stop the command, such as one bad
+    /// index among several, in the configured `--error-format`.
+    fn report(&self, error: TodoError) {
+        error::report(&error, self.error_format);
+    }
+
+    /// A task id in the configured `id_format`. Padding is to the largest id
+    /// currently in the list.
+    fn id_label(&self, id: usize) -> String {
+        let max_id = self.tasks.borrow().iter().map(|t| t.id).max().unwrap_or(0);
+        display::format_id(id, self.config.id_format, max_id)
+    }
+
+    /// Prints a diagnostic to stderr when running with `--verbose`.
+    fn debug(&self, message: &str) {
+        if self.verbose {
+            eprintln!("[verbose] {}", message);
+        }
+    }
+
+    /// Prints an informational message unless running with `--quiet`.
+    fn info(&self, message: &str) {
+        if !self.quiet {
+            println!("{}", message);
+        }
+    }
+
+    pub fn list_tasks(&self, options: &ListOptions) -> io::Result<()> {
+        let all_tasks = self.tasks.borrow();
+        if all_tasks.is_empty() {
+            println!("No tasks found. Add one using `todo_cli add \"My task\"`");
+            return Ok(());
+        }
+        let tasks = options.select(&all_tasks);
+        if tasks.is_empty() {
+            println!("No tasks match the given filters.");
+            return Ok(());
+        }
+
+        if options.sort.iter().any(|spec| spec.key == SortKey::Smart) {
+            self.debug_inherited_priorities(&all_tasks);
+        }
+
+        let now = Local::now().fixed_offset();
+        let use_color = display::color_enabled(self.color);
+
+        let mut out = Vec::new();
+        if options.header {
+            out.extend([String::new(), "--- Your ToDo Tasks ---".to_string()]);
+        }
+        match options.group_by {
+            Some(group_by) => {
+                let groups = group::group_tasks(
+                    &tasks,
+                    group_by,
+                    now,
+                    self.config.overdue_grace,
+                    self.config.week_starts_on,
+                    &self.config.someday_label,
+                    self.config.someday_position,
+                );
+                for (i, (label, group)) in groups.iter().enumerate() {
+                    if i > 0 {
+                        out.push(String::new());
+                    }
+                    out.push(format!("{}:", label));
+                    self.render_tasks(&mut out, group, options, now, use_color);
+                }
+            }
+            None => self.render_tasks(&mut out, &tasks, options, now, use_color),
+        }
+        if options.summary {
+            out.push(summary_line(&tasks, now, self.config.overdue_grace, options.max_age));
+        }
+        if options.header {
+            out.extend(["-----------------------".to_string(), String::new()]);
+        }
+        pager::print_lines(&out, options.pager)
+    }
+
+    /// Redraws the pending list whenever the task file's modification time
+    /// changes, checking every `interval`. This polls rather than asking the
+    /// OS for change notifications, which network filesystems and some
+    /// containers don't deliver. Runs until interrupted.
+    pub fn watch(&mut self, interval: std::time::Duration) -> Result<(), TodoError> {
+        // Kept to show under the list, which would otherwise clear it away.
+        let mut reload_error = None;
+        loop {
+            let options = ListOptions {
+                filter: TaskFilter {
+                    waiting: Some(false),
+                    ..TaskFilter::default()
+                },
+                width: display::terminal_width(),
+                nulls: self.config.someday_position,
+                urgency: self.config.urgency,
+                summary: true,
+                header: true,
+                ..ListOptions::default()
+            };
+            // Clears the screen and moves the cursor to the top left.
+            print!("\x1b[2J\x1b[H");
+            self.list_tasks(&options)?;
+            if let Some(e) = reload_error.take() {
+                self.report(e);
+            }
+            println!(
+                "Watching {}, checking every {}s. Press Ctrl-C to stop.",
+                self.file_path.display(),
+                interval.as_secs()
+            );
+            let seen = modified(&self.file_path);
+            while modified(&self.file_path) == seen {
+                std::thread::sleep(interval);
+            }
+            self.debug("task file changed; reloading");
+            // Another process may be halfway through writing it, in which
+            // case the old list stays up until the next change.
+            reload_error = self.load_tasks().err();
+        }
+    }
+
+    /// Prints just the footer `list` would end with, counting the tasks that
+    /// match `options`, even when there are none.
+    pub fn list_summary(&self, options: &ListOptions) {
+        let all_tasks = self.tasks.borrow();
+        let tasks = options.select(&all_tasks);
+        let now = Local::now().fixed_offset();
+        println!("{}", summary_line(&tasks, now, self.config.overdue_grace, options.max_age));
+    }
+
+    /// Under `--verbose`, names every task whose smart-sort priority was
+    /// raised by a task depending on it.
+    fn debug_inherited_priorities(&self, tasks: &[Task]) {
+        if !self.verbose {
+            return;
+        }
+        let effective = sort::effective_priorities(tasks);
+        for task in tasks {
+            if let Some(EffectivePriority {
+                priority: Some(priority),
+                inherited_from: Some(from),
+            }) = effective.get(&task.id)
+            {
+                self.debug(&format!(
+                    "task {} inherits {:?} priority from dependent task {}",
+                    task.id, priority, from
+                ));
+            }
+        }
+    }
+
+    /// Appends the lines for `tasks` to `out`, as a table or one line each.
+    fn render_tasks(
+        &self,
+        out: &mut Vec<String>,
+        tasks: &[&Task],
+        options: &ListOptions,
+        now: DateTime<FixedOffset>,
+        use_color: bool,
+    ) {
+        let stale = options.stale_marker_after();
+        match &options.fields {
+            Some(fields) => self.render_field_table(out, tasks, fields, options, now, use_color),
+            None if options.tree => {
+                for row in deps::tree(tasks) {
+                    let indent = "  ".repeat(row.depth);
+                    let width = options.width.map(|w| w.saturating_sub(indent.len()));
+                    if row.repeat {
+                        let line = self.task_lines(row.task, width, false, now, stale, use_color);
+                        out.push(format!("{}{} (see above)", indent, line[0]));
+                        continue;
+                    }
+                    let wrap = options.wrap;
+                    for line in self.task_lines(row.task, width, wrap, now, stale, use_color) {
+                        out.push(format!("{}{}", indent, line));
+                    }
+                }
+            }
+            None => {
+                for task in tasks {
+                    let lines =
+                        self.task_lines(task, options.width, options.wrap, now, stale, use_color);
+                    out.extend(lines);
+                }
+            }
+        }
+    }
+
+    /// Prints the tasks whose description matches `query`, a case-insensitive
+    /// substring or a regex, or with `count_only` just how many there are.
+    /// Done tasks are skipped unless `include_done` is set, to keep results
+    /// actionable. `page` is how many matches to skip and how many of the
+    /// rest to show at most; when either is given, a footer gives the range
+    /// shown.
+    pub fn search_tasks(
+        &self,
+        query: &SearchQuery,
+        include_done: bool,
+        width: Option<usize>,
+        count_only: bool,
+        (offset, limit): (usize, Option<usize>),
+    ) -> io::Result<()> {
+        let tasks = self.tasks.borrow();
+        let matches: Vec<&Task> = tasks
+            .iter()
+            .filter(|t| include_done || t.is_pending())
+            .filter(|t| query.matches(&t.description))
+            .collect();
+        if count_only {
+            println!("{}", matches.len());
+            return Ok(());
+        }
+        let total = matches.len();
+        let page: Vec<&Task> =
+            matches.into_iter().skip(offset).take(limit.unwrap_or(usize::MAX)).collect();
+        if self.format != OutputFormat::Human {
+            return output::print_tasks(&page, self.format);
+        }
+        if total == 0 {
+            println!("No tasks match {}.", query);
+            return Ok(());
+        }
+        let now = Local::now().fixed_offset();
+        let use_color = display::color_enabled(self.color);
+        for task in &page {
+            for line in self.task_lines(task, width, false, now, None, use_color) {
+                println!("{}", line);
+            }
+        }
+        if offset > 0 || limit.is_some() {
+            match page.len() {
+                0 => println!("Showing none of {} matches.", total),
+                shown => {
+                    println!("Showing {}-{} of {} matches.", offset + 1, offset + shown, total)
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints pending task descriptions starting with `prefix`
+    /// (case-insensitive), one per line and undecorated, for shell completion.
+    pub fn complete_descriptions(&self, prefix: &str) {
+        let prefix = prefix.to_lowercase();
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        for task in self.tasks.borrow().iter().filter(|t| t.is_pending()) {
+            if task.description.to_lowercase().starts_with(&prefix)
+                && writeln!(out, "{}", task.description).is_err()
+            {
+                return;
+            }
+        }
+    }
+
+    /// Renders a task in the default one-line format used by `list`, fitting
+    /// the description into `width` columns when given: truncated, or with
+    /// `wrap` continued on extra lines indented under the description. Tasks
+    /// older than `stale_after` are marked "[stale]".
+    fn task_lines(
+        &self,
+        task: &Task,
+        width: Option<usize>,
+        wrap: bool,
+        now: DateTime<FixedOffset>,
+        stale_after: Option<Duration>,
+        use_color: bool,
+    ) -> Vec<String> {
+        let status_char = match task.status {
+            TaskStatus::Pending => ' ',
+            TaskStatus::Done => 'x',
+        };
+        let mut details = String::new();
+        if self.is_focused(task.id) {
+            details.push_str(" [focus]");
+        }
+        if task.locked {
+            details.push_str(&format!(" {}", self.markers.locked));
+        }
+        if stale_after.is_some_and(|max_age| task.is_stale(now, max_age)) {
+            details.push_str(" [stale]");
+        }
+        if let Some(until) = task.wait_until.filter(|_| task.is_waiting(now)) {
+            details.push_str(&format!(" [waiting until {}]", display::format_datetime(until)));
+        }
+        details.extend(task.tags.iter().map(|tag| format!(" #{}", tag)));
+        details.extend(task.contexts.iter().map(|context| format!(" @{}", context)));
+        if let Some(project) = &task.project {
+            details.push_str(&format!(" [{}]", project));
+        }
+        if let Some(assignee) = &task.assignee {
+            details.push_str(&format!(" [for {}]", assignee));
+        }
+        if let Some(dt) = task.due_date {
+            details.push_str(&format!(" (Due: {})", display::format_datetime(dt)));
+        }
+        let until_due = match task.due_date {
+            Some(dt) if task.is_pending() => Some(self.until_due_label(dt, now)),
+            _ => None,
+        };
+        let prefix = format!("[{}] {}. ", status_char, self.id_label(task.id));
+        let until_len = until_due
+            .as_ref()
+            .map_or(0, |(label, _)| display::width(label) + 1);
+        let suffix_len = display::width(&details) + until_len;
+        let until_due_str = match until_due {
+            Some((label, color)) => format!(" {}", display::paint(&label, color, use_color)),
+            None => String::new(),
+        };
+        let prefix_len = display::width(&prefix);
+        let link = |text: &str| display::hyperlink(text, task.url.as_deref(), use_color);
+        let Some(width) = width else {
+            let description = link(&task.description);
+            return vec![format!("{}{}{}{}", prefix, description, details, until_due_str)];
+        };
+        if !wrap {
+            let description = display::truncate(
+                &task.description,
+                width.saturating_sub(prefix_len + suffix_len),
+                self.markers.ellipsis,
+            );
+            let description = link(&description);
+            return vec![format!("{}{}{}{}", prefix, description, details, until_due_str)];
+        }
+
+        let budget = width.saturating_sub(prefix_len);
+        let indent = " ".repeat(prefix_len);
+        let wrapped = display::wrap(&task.description, budget);
+        let last_len = prefix_len + wrapped.last().map_or(0, |text| display::width(text));
+        let mut lines: Vec<String> = wrapped
+            .iter()
+            .enumerate()
+            .map(|(i, text)| format!("{}{}", if i == 0 { &prefix } else { &indent }, link(text)))
+            .collect();
+        let suffix = format!("{}{}", details, until_due_str);
+        if !suffix.is_empty() {
+            let last = lines.last_mut().expect("wrap returns at least one line");
+            if last_len + suffix_len <= width {
+                last.push_str(&suffix);
+            } else {
+                lines.push(format!("{}{}", indent, suffix.trim_start()));
+            }
+        }
+        lines
+    }
+
+    /// Prints the tasks created or updated at or after `since` as a JSON
+    /// array, in file order.
+    pub fn changes_since(&self, since: DateTime<FixedOffset>) -> io::Result<()> {
+        let tasks = self.tasks.borrow();
+        let changed: Vec<&Task> = tasks
+            .iter()
+            .filter(|t| t.updated_at.or(t.created_at).is_some_and(|at| at >= since))
+            .collect();
+        let data = serde_json::to_string_pretty(&changed)?;
+        println!("{}", data);
+        Ok(())
+    }
+
+    /// Prints the selected tasks as a pretty-printed JSON array, serialized
+    /// straight to stdout so large lists are never held as one string.
+    /// With `--fields`, each task keeps only the keys those fields stand for.
+    pub fn list_tasks_json(&self, options: &ListOptions) -> Result<(), TodoError> {
+        let stdout = io::stdout();
+        let mut out = io::BufWriter::new(stdout.lock());
+        self.write_tasks_json(options, &mut out)?;
+        Ok(out.flush()?)
+    }
+
+    /// Writes what `list_tasks_json` prints to `out`.
+    fn write_tasks_json(
+        &self,
+        options: &ListOptions,
+        out: &mut impl Write,
+    ) -> Result<(), TodoError> {
+        let keys = json_keys(options.fields.as_deref())?;
+        let tasks = self.tasks.borrow();
+        let mut serializer = serde_json::Serializer::pretty(&mut *out);
+        match keys {
+            Some(keys) => serializer.collect_seq(
+                options.select(&tasks).into_iter().map(|task| output::task_fields(task, &keys)),
+            )?,
+            None => serializer.collect_seq(options.select(&tasks))?,
+        }
+        writeln!(out)?;
+        Ok(())
+    }
+
+    /// Prints the selected tasks as CSV or todo.txt, as chosen by `--format`.
+    pub fn list_tasks_formatted(&self, options: &ListOptions) -> io::Result<()> {
+        output::print_tasks(&options.select(&self.tasks.borrow()), self.format)
+    }
+
+    pub fn list_tasks_ndjson(&self, options: &ListOptions) -> Result<(), TodoError> {
+        let keys = json_keys(options.fields.as_deref())?;
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        for task in options.select(&self.tasks.borrow()) {
+            match &keys {
+                Some(keys) => serde_json::to_writer(&mut out, &output::task_fields(task, keys))?,
+                None => serde_json::to_writer(&mut out, task)?,
+            }
+            out.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    /// Prints the ids of the matching tasks, one per line, for piping into
+    /// commands such as `done --id`.
+    pub fn list_task_ids(&self, options: &ListOptions) -> io::Result<()> {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        for task in options.select(&self.tasks.borrow()) {
+            writeln!(out, "{}", self.id_label(task.id))?;
+        }
+        Ok(())
+    }
+
+    /// Prints `id. description` for each matching task and nothing else: no
+    /// header, footer, status, dates, color or truncation, for pasting into
+    /// notes and emails.
+    pub fn list_tasks_plain(&self, options: &ListOptions) -> io::Result<()> {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        for task in options.select(&self.tasks.borrow()) {
+            writeln!(out, "{}. {}", self.id_label(task.id), task.description)?;
+        }
+        Ok(())
+    }
+
+    /// Renders only the chosen columns, in the given order, as a table laid
+    /// out with `options.separator`.
+    /// With `options.wrap`, long descriptions continue on extra lines with
+    /// the other columns left blank.
+    fn render_field_table(
+        &self,
+        out: &mut Vec<String>,
+        tasks: &[&Task],
+        fields: &[ListField],
+        options: &ListOptions,
+        now: DateTime<FixedOffset>,
+        use_color: bool,
+    ) {
+        let mut rows: Vec<Vec<table::Cell>> = tasks
+            .iter()
+            .map(|task| {
+                fields
+                    .iter()
+                    .map(|field| self.field_cell(task, *field, now))
+                    .collect()
+            })
+            .collect();
+        let mut continuations: Vec<Vec<String>> = vec![Vec::new(); rows.len()];
+        let description_col = fields.iter().position(|f| *f == ListField::Description);
+        if let (Some(max_width), Some(col)) = (options.width, description_col) {
+            let others: usize = (0..fields.len())
+                .filter(|i| *i != col)
+                .map(|i| rows.iter().map(|row| display::width(&row[i].0)).max().unwrap_or(0))
+                .sum();
+            let overhead = options.separator.overhead(fields.len());
+            let budget = max_width.saturating_sub(others + overhead);
+            for (row, extra) in rows.iter_mut().zip(continuations.iter_mut()) {
+                if options.wrap {
+                    let mut lines = display::wrap(&row[col].0, budget).into_iter();
+                    row[col].0 = lines.next().unwrap_or_default();
+                    extra.extend(lines);
+                } else {
+                    row[col].0 = display::truncate(&row[col].0, budget, self.markers.ellipsis);
+                }
+            }
+        }
+        let mut lines = Vec::new();
+        for (row, extra) in rows.into_iter().zip(continuations) {
+            lines.push(row);
+            for text in extra {
+                let mut line = vec![(String::new(), None); fields.len()];
+                if let Some(col) = description_col {
+                    line[col].0 = text;
+                }
+                lines.push(line);
+            }
+        }
+        let table = Table {
+            separator: options.separator,
+            right_aligned: (0..fields.len()).filter(|&i| fields[i] == ListField::Urgency).collect(),
+            ..Table::default()
+        };
+        out.extend(table.render(&lines, use_color));
+    }
+
+    fn field_cell(
+        &self,
+        task: &Task,
+        field: ListField,
+        now: DateTime<FixedOffset>,
+    ) -> (String, Option<&'static str>) {
+        match field {
+            ListField::Id => (self.id_label(task.id), None),
+            ListField::Status => {
+                let status_char = match task.status {
+                    TaskStatus::Pending => ' ',
+                    TaskStatus::Done => 'x',
+                };
+                (format!("[{}]", status_char), None)
+            }
+            ListField::Description => (task.description.clone(), None),
+            ListField::Due => (
+                task.due_date
+                    .map(display::format_datetime)
+                    .unwrap_or_default(),
+                None,
+            ),
+            ListField::Until => match task.due_date {
+                Some(dt) if task.is_pending() => {
+                    let (label, color) = self.until_due_label(dt, now);
+                    (label, Some(color))
+                }
+                _ => (String::new(), None),
+            },
+            ListField::Tags => (task.tags.join(","), None),
+            ListField::Contexts => (task.contexts.join(","), None),
+            ListField::Project => (task.project.clone().unwrap_or_default(), None),
+            ListField::Assignee => (task.assignee.clone().unwrap_or_default(), None),
+            ListField::Priority => (
+                task.priority.map_or(String::new(), |p| format!("{:?}", p).to_lowercase()),
+                None,
+            ),
+            ListField::Subtasks => {
+                let (done, total) = task.subtask_progress();
+                if total == 0 {
+                    (String::new(), None)
+                } else {
+                    (format!("{}/{}", done, total), None)
+                }
+            }
+            ListField::Urgency => {
+                let score = urgency::score(task, &self.tasks.borrow(), now, &self.config.urgency);
+                (format!("{:.1}", score), None)
+            }
+        }
+    }
+
+    fn until_due_label(
+        &self,
+        due: DateTime<FixedOffset>,
+        now: DateTime<FixedOffset>,
+    ) -> (String, &'static str) {
+        let remaining = due - now;
+        if remaining < -self.config.overdue_grace {
+            (format!("[{} overdue]", display::humanize(remaining)), RED)
+        } else if remaining < Duration::zero() {
+            (format!("[due {} ago]", display::humanize(remaining)), YELLOW)
+        } else if remaining <= self.config.urgent_within {
+            (format!("[due in {}]", display::humanize(remaining)), RED)
+        } else if remaining <= self.config.warn_within() {
+            (format!("[due in {}]", display::humanize(remaining)), YELLOW)
+        } else {
+            (format!("[due in {}]", display::humanize(remaining)), GREEN)
+        }
+    }
+
+    /// Prints a task's details. With `relative`, timestamps are shown as
+    /// "in 3 days" / "2 hours ago" instead of absolute times.
+    pub fn show_task(&self, id: usize, relative: bool) -> Result<(), TodoError> {
+        let tasks = self.tasks.borrow();
+        let Some(task) = self.task_by_id(&tasks, id) else {
+            return Err(TodoError::InvalidId(id));
+        };
+        if self.format != OutputFormat::Human {
+            return Ok(output::print_task(task, self.format)?);
+        }
+        let now = Local::now().fixed_offset();
+        let format_time = |dt| {
+            if relative {
+                display::relative(dt, now)
+            } else {
+                display::format_datetime(dt)
+            }
+        };
+        println!("Task {}: {}", self.id_label(task.id), task.description);
+        println!("Status: {:?}", task.status);
+        if let Some(dt) = task.due_date {
+            println!("Due: {}", format_time(dt));
+        }
+        if task.someday {
+            println!("Due: someday");
+        }
+        if let Some(dt) = task.wait_until {
+            println!("Waiting until: {}", format_time(dt));
+        }
+        if let Some(dt) = task.created_at {
+            println!("Created: {}", format_time(dt));
+        }
+        if let Some(dt) = task.completed_at {
+            println!("Completed: {}", format_time(dt));
+        }
+        if let Some(note) = &task.completion_note {
+            println!("Completion note: {}", note);
+        }
+        if self.verbose
+            && let Some(dt) = task.updated_at
+        {
+            println!("Updated: {}", format_time(dt));
+        }
+        if !task.tags.is_empty() {
+            println!("Tags: {}", task.tags.join(", "));
+        }
+        if let Some(project) = &task.project {
+            println!("Project: {}", project);
+        }
+        if let Some(assignee) = &task.assignee {
+            println!("Assignee: {}", assignee);
+        }
+        if !task.contexts.is_empty() {
+            let contexts: Vec<String> = task.contexts.iter().map(|c| format!("@{}", c)).collect();
+            println!("Contexts: {}", contexts.join(", "));
+        }
+        if let Some(priority) = task.priority {
+            println!("Priority: {:?}", priority);
+        }
+        if let Some(recurrence) = &task.recurrence {
+            println!("Repeats: {}", recurrence.describe());
+        }
+        if let Some(url) = &task.url {
+            println!("Link: {}", url);
+        }
+        if task.locked {
+            println!("Locked: yes");
+        }
+        match (task.estimate, task.time_spent) {
+            (Some(estimate), Some(spent)) => println!(
+                "Time: est {} / actual {}{}",
+                display::hours_minutes(estimate),
+                display::hours_minutes(spent),
+                variance(estimate, spent).map(|v| format!(", {}", v)).unwrap_or_default()
+            ),
+            (Some(estimate), None) => println!("Estimate: {}", display::hours_minutes(estimate)),
+            (None, Some(spent)) => println!("Time spent: {}", display::hours_minutes(spent)),
+            (None, None) => {}
+        }
+        if !task.depends_on.is_empty() {
+            let ids: Vec<String> = task.depends_on.iter().map(|id| id.to_string()).collect();
+            let blocked = if task.is_blocked(&tasks) { " (blocked)" } else { "" };
+            println!("Depends on: {}{}", ids.join(", "), blocked);
+        }
+        if !task.subtasks.is_empty() {
+            let (done, total) = task.subtask_progress();
+            println!("Subtasks ({}/{}):", done, total);
+            for (i, subtask) in task.subtasks.iter().enumerate() {
+                let mark = if subtask.done { 'x' } else { ' ' };
+                println!("  [{}] {}. {}", mark, i, subtask.text);
+            }
+        }
+        if !task.annotations.is_empty() {
+            println!("Annotations:");
+            let mut annotations: Vec<&Annotation> = task.annotations.iter().collect();
+            annotations.sort_by_key(|annotation| annotation.at);
+            for annotation in annotations {
+                println!("  {}  {}", format_time(annotation.at), annotation.text);
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends a note stamped with the current time to task `id`'s log.
+    pub fn annotate_task(&self, id: usize, text: &str) -> Result<(), TodoError> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(TodoError::EmptyNote);
+        }
+        let mut tasks = self.tasks.borrow_mut();
+        let Some(task) = self.task_by_id_mut(&mut tasks, id) else {
+            return Err(TodoError::InvalidId(id));
+        };
+        let before = task.clone();
+        task.annotations.push(Annotation {
+            at: now_ist(),
+            text: text.to_string(),
+        });
+        task.touch();
+        let after = task.clone();
+        drop(tasks);
+        self.save_and_record(
+            format!("Annotated task {}", id),
+            Operation::Update {
+                before: vec![before],
+                after: vec![after],
+            },
+        )?;
+        self.info(&format!("Annotated task {}.", id));
+        Ok(())
+    }
+
+    pub fn add_subtask(&self, parent_id: usize, text: String) -> Result<(), TodoError> {
+        let mut tasks = self.tasks.borrow_mut();
+        let Some(task) = self.task_by_id_mut(&mut tasks, parent_id) else {
+            self.report(TodoError::InvalidId(parent_id));
+            return Ok(());
+        };
+        let before = task.clone();
+        task.subtasks.push(SubTask { text, done: false });
+        task.touch();
+        let sub_index = task.subtasks.len() - 1;
+        let after = task.clone();
+        drop(tasks);
+        self.save_and_record(
+            format!("Added subtask {} to task {}", sub_index, parent_id),
+            Operation::Update {
+                before: vec![before],
+                after: vec![after],
+            },
+        )?;
+        self.info(&format!("Subtask {} added to task {}.", sub_index, parent_id));
+        Ok(())
+    }
+
+    /// Toggles a subtask's done flag. With `auto_complete_parent` set, checking
+    /// the last open subtask also marks the parent done.
+    pub fn toggle_subtask(&self, parent_id: usize, sub_index: usize) -> Result<(), TodoError> {
+        let mut tasks = self.tasks.borrow_mut();
+        let Some(task) = self.task_by_id_mut(&mut tasks, parent_id) else {
+            self.report(TodoError::InvalidId(parent_id));
+            return Ok(());
+        };
+        let before = task.clone();
+        let Some(subtask) = task.subtasks.get_mut(sub_index) else {
+            self.report(TodoError::InvalidSubtaskIndex {
+                parent_id,
+                index: sub_index,
+            });
+            return Ok(());
+        };
+        subtask.done = !subtask.done;
+        let checked = subtask.done;
+        task.touch();
+        let (done, total) = task.subtask_progress();
+        let completed_parent = self.config.auto_complete_parent
+            && task.is_pending()
+            && done == total;
+        if completed_parent {
+            task.mark_done();
+        }
+        let parent = task.clone();
+        drop(tasks);
+        let state = if checked { "checked" } else { "unchecked" };
+        self.save_and_record(
+            format!("Subtask {} of task {} {}", sub_index, parent_id, state),
+            Operation::Update {
+                before: vec![before],
+                after: vec![parent.clone()],
+            },
+        )?;
+        self.info(&format!(
+            "Subtask {} of task {} {} ({}/{}).",
+            sub_index, parent_id, state, done, total
+        ));
+        if completed_parent {
+            self.info(&format!("Task {} marked as done.", parent_id));
+            self.run_on_done_hook(&parent);
+        }
+        Ok(())
+    }
+
+    /// Prints the `count` most recently completed tasks, newest first. Tasks
+    /// completed before `completed_at` was recorded have no timestamp and are
+    /// listed after the dated ones.
+    pub fn recent_tasks(&self, count: usize) {
+        let tasks = self.tasks.borrow();
+        let mut done: Vec<&Task> = tasks.iter().filter(|t| !t.is_pending()).collect();
+        if done.is_empty() {
+            println!("No completed tasks yet.");
+            return;
+        }
+        done.sort_by_key(|t| std::cmp::Reverse(t.completed_at));
+        done.truncate(count);
+        for task in &done {
+            let completed_str = match task.completed_at {
+                Some(dt) => format!(" (Completed: {})", display::format_datetime(dt)),
+                None => String::new(),
+            };
+            let note_str = match &task.completion_note {
+                Some(note) => format!(" - {}", note),
+                None => String::new(),
+            };
+            println!(
+                "[x] {}. {}{}{}",
+                self.id_label(task.id),
+                task.description,
+                completed_str,
+                note_str
+            );
+        }
+        if done.iter().any(|t| t.completed_at.is_none()) {
+            println!("Note: some tasks have no completion time, so their order is approximate.");
+        }
+    }
+
+    /// Prints the current and longest completion streaks and the tasks
+    /// completed on each of the last `days` days, counting archived tasks
+    /// too, so archiving doesn't break a streak.
+    pub fn streak_report(&self, days: usize) -> io::Result<()> {
+        let archive = self.load_archive()?;
+        let tasks = self.tasks.borrow();
+        let completed = tasks
+            .iter()
+            .chain(&archive)
+            .filter_map(|t| t.completed_at)
+            .map(|at| at.with_timezone(&task::ist()).date_naive());
+        let today = now_ist().date_naive();
+        let streaks = streak::streaks(completed, today, days);
+        match self.format {
+            OutputFormat::Human => {}
+            OutputFormat::Todotxt | OutputFormat::Html => {
+                unreachable!("stats rejects --format todotxt and html")
+            }
+            OutputFormat::Json => {
+                let per_day: Vec<serde_json::Value> = streaks
+                    .per_day
+                    .iter()
+                    .map(|(day, count)| serde_json::json!({ "date": day, "completed": count }))
+                    .collect();
+                let report = serde_json::json!({
+                    "current": streaks.current,
+                    "longest": streaks.longest,
+                    "per_day": per_day,
+                });
+                println!("{}", serde_json::to_string_pretty(&report)?);
+                return Ok(());
+            }
+            OutputFormat::Csv => {
+                let rows: Vec<Vec<String>> = streaks
+                    .per_day
+                    .iter()
+                    .map(|(day, count)| vec![day.to_string(), count.to_string()])
+                    .collect();
+                return output::write_csv(&["date", "completed"], &rows);
+            }
+        }
+        println!("Current streak: {} day(s)", streaks.current);
+        println!("Longest streak: {} day(s)", streaks.longest);
+        if streaks.per_day.is_empty() {
+            return Ok(());
+        }
+        println!();
+        println!("Completed per day, last {} day(s):", streaks.per_day.len());
+        let most = streaks.per_day.iter().map(|(_, count)| *count).max().unwrap_or(0);
+        let width = streaks.per_day.iter().map(|(_, count)| count.to_string().len()).max();
+        for (day, count) in &streaks.per_day {
+            // Scaled down only when the busiest day wouldn't fit.
+            let bar = if most > STREAK_BAR_WIDTH {
+                count * STREAK_BAR_WIDTH / most
+            } else {
+                *count
+            };
+            let line = format!(
+                "{}  {:>width$}  {}",
+                day.format("%a %Y-%m-%d"),
+                count,
+                self.markers.bar.repeat(bar),
+                width = width.unwrap_or(1)
+            );
+            println!("{}", line.trim_end());
+        }
+        Ok(())
+    }
+
+    /// Prints overall counts, or with `by_tag` one row of counts per tag
+    /// (tasks without tags under "(untagged)"), most pending work first.
+    /// `compact` prints the overall counts as `key=value` pairs on one line;
+    /// the keys match the JSON ones and should stay stable. `separator` lays
+    /// out the per-tag table.
+    pub fn stats(&self, by_tag: bool, compact: bool, separator: Separator) -> io::Result<()> {
+        let all_tasks = self.tasks.borrow();
+        let now = Local::now().fixed_offset();
+        let grace = self.config.overdue_grace;
+        let tasks: Vec<&Task> = all_tasks.iter().collect();
+        if !by_tag {
+            let pending = tasks.iter().filter(|t| t.is_pending()).count();
+            let overdue = tasks.iter().filter(|t| t.is_overdue(now, grace)).count();
+            let done = tasks.len() - pending;
+            match self.format {
+                OutputFormat::Human if compact => println!(
+                    "total={} pending={} done={} overdue={}",
+                    tasks.len(),
+                    pending,
+                    done,
+                    overdue
+                ),
+                OutputFormat::Human => println!("{}", summary_line(&tasks, now, grace, None)),
+                OutputFormat::Json => println!(
+                    "{}",
+                    serde_json::json!({
+                        "total": tasks.len(),
+                        "pending": pending,
+                        "done": done,
+                        "overdue": overdue,
+                    })
+                ),
+                OutputFormat::Csv => {
+                    let row = [tasks.len(), pending, done, overdue].map(|n| n.to_string());
+                    output::write_csv(&["total", "pending", "done", "overdue"], &[row.to_vec()])?;
+                }
+                OutputFormat::Todotxt | OutputFormat::Html => {
+                    unreachable!("stats rejects --format todotxt and html")
+                }
+            }
+            return Ok(());
+        }
+
+        let mut rows: Vec<(String, usize, usize, usize)> = Vec::new();
+        for task in &tasks {
+            let untagged = ["(untagged)".to_string()];
+            let tags = if task.tags.is_empty() { &untagged[..] } else { &task.tags[..] };
+            for tag in tags {
+                let index = match rows.iter().position(|row| row.0 == *tag) {
+                    Some(index) => index,
+                    None => {
+                        rows.push((tag.clone(), 0, 0, 0));
+                        rows.len() - 1
+                    }
+                };
+                let row = &mut rows[index];
+                if task.is_pending() {
+                    row.1 += 1;
+                } else {
+                    row.2 += 1;
+                }
+                if task.is_overdue(now, grace) {
+                    row.3 += 1;
+                }
+            }
+        }
+        rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        match self.format {
+            OutputFormat::Human => {}
+            OutputFormat::Todotxt | OutputFormat::Html => {
+                unreachable!("stats rejects --format todotxt and html")
+            }
+            OutputFormat::Json => {
+                let items: Vec<serde_json::Value> = rows
+                    .iter()
+                    .map(|(tag, pending, done, overdue)| {
+                        serde_json::json!({
+                            "tag": tag,
+                            "pending": pending,
+                            "done": done,
+                            "overdue": overdue,
+                        })
+                    })
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&items)?);
+                return Ok(());
+            }
+            OutputFormat::Csv => {
+                let rows: Vec<Vec<String>> = rows
+                    .iter()
+                    .map(|(tag, pending, done, overdue)| {
+                        let counts = [pending, done, overdue].map(|n| n.to_string());
+                        [vec![tag.clone()], counts.to_vec()].concat()
+                    })
+                    .collect();
+                return output::write_csv(&["tag", "pending", "done", "overdue"], &rows);
+            }
+        }
+        if rows.is_empty() {
+            println!("No tasks found.");
+            return Ok(());
+        }
+
+        let table = Table {
+            separator,
+            header: Some(["Tag", "Pending", "Done", "Overdue"].map(String::from).to_vec()),
+            right_aligned: vec![1, 2, 3],
+        };
+        let rows: Vec<Vec<table::Cell>> = rows
+            .into_iter()
+            .map(|(tag, pending, done, overdue)| {
+                let counts = [pending, done, overdue].map(|n| (n.to_string(), None));
+                [vec![(tag, None)], counts.to_vec()].concat()
+            })
+            .collect();
+        for line in table.render(&rows, false) {
+            println!("{}", line);
+        }
+        Ok(())
+    }
+
+    /// Prints every project with how much of it is done, as a percentage and
+    /// a bar, least complete first so stalled projects come up top.
+    pub fn list_projects(&self) {
+        let tasks = self.tasks.borrow();
+        // (name, done, total)
+        let mut rows: Vec<(&str, usize, usize)> = Vec::new();
+        for task in tasks.iter() {
+            let Some(project) = task.project.as_deref() else {
+                continue;
+            };
+            let index = match rows.iter().position(|row| row.0 == project) {
+                Some(index) => index,
+                None => {
+                    rows.push((project, 0, 0));
+                    rows.len() - 1
+                }
+            };
+            rows[index].2 += 1;
+            if !task.is_pending() {
+                rows[index].1 += 1;
+            }
+        }
+        if rows.is_empty() {
+            println!(
+                "No tasks have a project. Set one with `add --project` or `modify --project`."
+            );
+            return;
+        }
+        // Compares done/total fractions without rounding: a/b < c/d iff ad < cb.
+        rows.sort_by(|a, b| (a.1 * b.2).cmp(&(b.1 * a.2)).then_with(|| a.0.cmp(b.0)));
+        let name_width = rows.iter().map(|row| display::width(row.0)).max().unwrap_or(0);
+        for (name, done, total) in rows {
+            let filled = done * PROGRESS_BAR_WIDTH / total;
+            let bar = format!(
+                "[{}{}]",
+                "#".repeat(filled),
+                "-".repeat(PROGRESS_BAR_WIDTH - filled)
+            );
+            println!(
+                "{}  {}  {:>3}%  ({}/{})",
+                display::pad(name, name_width),
+                bar,
+                done * 100 / total,
+                done,
+                total
+            );
+        }
+    }
+
+    /// Prints every task that has both an estimate and time spent under
+    /// `focus`, with how far off the estimate was, then the totals.
+    pub fn estimates_report(&self) {
+        let tasks = self.tasks.borrow();
+        let tracked: Vec<(&Task, Duration, Duration)> = tasks
+            .iter()
+            .filter_map(|task| Some((task, task.estimate?, task.time_spent?)))
+            .collect();
+        if tracked.is_empty() {
+            println!(
+                "No task has both an estimate and time spent. Set one with --estimate and \
+                 track the other with `focus`."
+            );
+            return;
+        }
+        let table = Table {
+            header: Some(["Id", "Task", "Estimate", "Actual", "Variance"].map(String::from).to_vec()),
+            right_aligned: vec![2, 3, 4],
+            ..Table::default()
+        };
+        let rows: Vec<Vec<table::Cell>> = tracked
+            .iter()
+            .map(|(task, estimate, spent)| {
+                [
+                    self.id_label(task.id),
+                    task.description.clone(),
+                    display::hours_minutes(*estimate),
+                    display::hours_minutes(*spent),
+                    variance(*estimate, *spent).unwrap_or_default(),
+                ]
+                .map(|text| (text, None))
+                .to_vec()
+            })
+            .collect();
+        for line in table.render(&rows, false) {
+            println!("{}", line);
+        }
+        let estimated = tracked.iter().map(|(_, estimate, _)| *estimate).sum();
+        let spent = tracked.iter().map(|(_, _, spent)| *spent).sum();
+        println!(
+            "\nTotal: est {} / actual {}{}",
+            display::hours_minutes(estimated),
+            display::hours_minutes(spent),
+            variance(estimated, spent).map(|v| format!(", {}", v)).unwrap_or_default()
+        );
+    }
+
+    /// Prints only the description of the pending task due soonest (overdue
+    /// ones included), skipping tasks still waiting on a dependency, or
+    /// nothing if there is none. Never modifies anything, so it is safe to
+    /// call from a shell prompt.
+    pub fn peek(&self) {
+        let tasks = self.tasks.borrow();
+        let now = now_ist();
+        let next = tasks
+            .iter()
+            .filter(|t| t.is_pending() && !t.is_blocked(&tasks) && !t.is_waiting(now))
+            .filter_map(|t| t.due_date.map(|due| (due, t)))
+            .min_by_key(|(due, _)| *due);
+        if let Some((_, task)) = next {
+            println!("{}", task.description);
+        }
+    }
+
+    pub fn notify(&self) -> io::Result<()> {
+        let now = Local::now().fixed_offset();
+        let mut tasks = self.tasks.borrow_mut();
+        let mut fired = 0;
+        for task in tasks.iter_mut() {
+            if !task.is_pending() || task.notified {
+                continue;
+            }
+            let (Some(due), Some(remind_at)) = (task.due_date, task.remind_at()) else {
+                continue;
+            };
+            if now < remind_at {
+                continue;
+            }
+            let when = if due <= now {
+                format!("{} overdue", display::humanize(due - now))
+            } else {
+                format!("due in {}", display::humanize(due - now))
+            };
+            println!(
+                "Reminder: task {}. {} is {} (Due: {})",
+                task.id,
+                task.description,
+                when,
+                display::format_datetime(due)
+            );
+            task.notified = true;
+            fired += 1;
+        }
+        drop(tasks);
+        if fired > 0 {
+            self.save_tasks()?;
+        }
+        Ok(())
+    }
+
+    /// Maps task ids to their current positions, reporting unknown ids.
+    pub fn positions_of(&self, ids: &[usize]) -> Vec<usize> {
+        let tasks = self.tasks.borrow();
+        ids.iter()
+            .filter_map(|&id| {
+                let position = self.position_of_id(&tasks, id);
+                if position.is_none() {
+                    self.report(TodoError::InvalidId(id));
+                }
+                position
+            })
+            .collect()
+    }
+
+    /// Position of the one pending task whose description is exactly
+    /// `description`, compared case-sensitively.
+    pub fn position_of_description(&self, description: &str) -> Result<usize, TodoError> {
+        let tasks = self.tasks.borrow();
+        let matches: Vec<usize> = tasks
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| t.is_pending() && t.description == description)
+            .map(|(position, _)| position)
+            .collect();
+        match matches[..] {
+            [position] => Ok(position),
+            [] => Err(TodoError::NoExactMatch(description.to_string())),
+            _ => Err(TodoError::AmbiguousMatch {
+                description: description.to_string(),
+                ids: matches.iter().map(|&position| tasks[position].id).collect(),
+            }),
+        }
+    }
+
+    /// Prints the next `count` due dates of a repeating task without changing
+    /// anything.
+    pub fn recur_preview(&self, id: usize, count: usize) {
+        let tasks = self.tasks.borrow();
+        let Some(task) = self.task_by_id(&tasks, id) else {
+            self.report(TodoError::InvalidId(id));
+            return;
+        };
+        let (Some(recurrence), Some(due)) = (&task.recurrence, task.due_date) else {
+            println!("Task {} does not repeat.", id);
+            return;
+        };
+        println!("Task {} repeats {}; next {} due date(s):", id, recurrence.describe(), count);
+        for date in recurrence.occurrences(due, count) {
+            println!("  {}", display::format_datetime(date));
+        }
+    }
+
+    /// Applies `changes` to task `id`. New dependencies must exist and must
+    /// not lead back to the task.
+    pub fn modify_task(&self, id: usize, changes: TaskChanges) -> Result<(), TodoError> {
+        self.check_ids(&changes.after)?;
+        let mut tasks = self.tasks.borrow_mut();
+        if let Some(cycle) = deps::find_cycle(&tasks, id, &changes.after) {
+            return Err(TodoError::DependencyCycle(cycle));
+        }
+        let Some(task) = self.task_by_id_mut(&mut tasks, id) else {
+            return Err(TodoError::InvalidId(id));
+        };
+        let before = task.clone();
+        if let Some(description) = changes.description {
+            task.description = description;
+        }
+        if let Some(due_date) = changes.due_date {
+            task.due_date = Some(due_date);
+            task.someday = false;
+            task.notified = false;
+        }
+        if changes.someday {
+            task.someday = true;
+            task.due_date = None;
+            task.recurrence = None;
+        }
+        if let Some(priority) = changes.priority {
+            task.priority = Some(priority);
+        }
+        if changes.project.is_some() || changes.clear_project {
+            task.project = changes.project;
+        }
+        if changes.assignee.is_some() || changes.clear_assignee {
+            task.assignee = changes.assignee;
+        }
+        if changes.estimate.is_some() || changes.clear_estimate {
+            task.estimate = changes.estimate;
+        }
+        if changes.wait_until.is_some() || changes.clear_wait {
+            task.wait_until = changes.wait_until;
+        }
+        for dependency in changes.after {
+            if !task.depends_on.contains(&dependency) {
+                task.depends_on.push(dependency);
+            }
+        }
+        for tag in changes.add_tags {
+            if !task.has_tag(&tag) {
+                task.tags.push(tag);
+            }
+        }
+        task.tags.retain(|tag| !changes.remove_tags.contains(tag));
+        task.touch();
+        let after = task.clone();
+        drop(tasks);
+        self.save_and_record(
+            format!("Modified task {}", id),
+            Operation::Update {
+                before: vec![before],
+                after: vec![after],
+            },
+        )?;
+        self.info(&format!("Task {} updated.", id));
+        Ok(())
+    }
+
+    /// Sets the due date of each task in `planned`, saved and undone as one
+    /// change. Tasks that have meanwhile disappeared are skipped.
+    pub fn schedule_tasks(
+        &self,
+        planned: &[(usize, DateTime<FixedOffset>)],
+    ) -> Result<(), TodoError> {
+        let mut tasks = self.tasks.borrow_mut();
+        let (mut before, mut after) = (Vec::new(), Vec::new());
+        for &(id, due) in planned {
+            let Some(task) = self.task_by_id_mut(&mut tasks, id) else {
+                continue;
+            };
+            before.push(task.clone());
+            task.due_date = Some(due);
+            task.someday = false;
+            task.notified = false;
+            task.touch();
+            after.push(task.clone());
+        }
+        drop(tasks);
+        if after.is_empty() {
+            return Ok(());
+        }
+        self.save_and_record(
+            format!("Planned {} task(s)", after.len()),
+            Operation::Update { before, after },
+        )?;
+        Ok(())
+    }
+
+    /// Pushes the due date of task `id` back by `by`, counting from now when
+    /// the task is overdue or has no due date.
+    pub fn snooze_task(&self, id: usize, by: Duration) -> Result<(), TodoError> {
+        let now = now_ist();
+        let due = {
+            let tasks = self.tasks.borrow();
+            let Some(task) = self.task_by_id(&tasks, id) else {
+                return Err(TodoError::InvalidId(id));
+            };
+            task.due_date.map_or(now, |due| due.max(now))
+        };
+        let due_date = task::whole_minute(due + by);
+        self.modify_task(
+            id,
+            TaskChanges {
+                due_date: Some(due_date),
+                ..TaskChanges::default()
+            },
+        )?;
+        self.info(&format!("Snoozed until {}.", display::format_datetime(due_date)));
+        Ok(())
+    }
+
+    /// Adds (`add`) or removes `tag` on each task in `ids`, saving once.
+    /// Tasks that already have (or lack) the tag are left alone.
+    pub fn retag_tasks(&self, ids: &[usize], tag: &str, add: bool) -> Result<(), TodoError> {
+        let mut tasks = self.tasks.borrow_mut();
+        let mut before = Vec::new();
+        let mut after = Vec::new();
+        for &id in ids {
+            let Some(task) = self.task_by_id_mut(&mut tasks, id) else {
+                self.report(TodoError::InvalidId(id));
+                continue;
+            };
+            if task.has_tag(tag) == add {
+                continue;
+            }
+            before.push(task.clone());
+            if add {
+                task.tags.push(tag.to_string());
+            } else {
+                task.tags.retain(|t| t != tag);
+            }
+            task.touch();
+            after.push(task.clone());
+        }
+        drop(tasks);
+        let summary = if add {
+            format!("Tagged {} task(s) with #{}", after.len(), tag)
+        } else {
+            format!("Removed #{} from {} task(s)", tag, after.len())
+        };
+        self.info(&format!("{}.", summary));
+        if !after.is_empty() {
+            self.save_and_record(summary, Operation::Update { before, after })?;
+        }
+        Ok(())
+    }
+
+    /// Moves every overdue task's due date to 23:59 today, or `days` later,
+    /// with one save, after asking unless `yes`.
+    pub fn reschedule_overdue(&self, days: u32, yes: bool, batch: bool) -> Result<(), TodoError> {
+        let now = now_ist();
+        let grace = self.config.overdue_grace;
+        let count = self.tasks.borrow().iter().filter(|t| t.is_overdue(now, grace)).count();
+        if count == 0 {
+            println!("No overdue tasks.");
+            return Ok(());
+        }
+        let end_of_day = now.date_naive().and_hms_opt(23, 59, 0).expect("23:59 is a valid time");
+        let Some(due_date) = task::local_time(&now.timezone(), end_of_day)
+            .and_then(|due| due.checked_add_signed(Duration::days(days.into())))
+        else {
+            self.report(TodoError::InvalidDate(format!("{} days from today is out of range", days)));
+            return Ok(());
+        };
+        let question = format!(
+            "Move {} overdue task(s) to {}?",
+            count,
+            display::format_datetime(due_date)
+        );
+        if !yes && !prompt::confirm(&question, batch) {
+            println!("Nothing rescheduled.");
+            return Ok(());
+        }
+        let mut tasks = self.tasks.borrow_mut();
+        let mut before = Vec::new();
+        let mut after = Vec::new();
+        for task in tasks.iter_mut().filter(|t| t.is_overdue(now, grace)) {
+            before.push(task.clone());
+            task.due_date = Some(due_date);
+            task.notified = false;
+            task.touch();
+            after.push(task.clone());
+        }
+        drop(tasks);
+        let summary = format!("Rescheduled {} overdue task(s)", after.len());
+        self.save_and_record(summary.clone(), Operation::Update { before, after })?;
+        self.info(&format!("{} to {}.", summary, display::format_datetime(due_date)));
+        Ok(())
+    }
+
+    /// Locks or unlocks task `id` against `delete` and `done`.
+    pub fn set_locked(&self, id: usize, locked: bool) -> Result<(), TodoError> {
+        let mut tasks = self.tasks.borrow_mut();
+        let Some(task) = self.task_by_id_mut(&mut tasks, id) else {
+            self.report(TodoError::InvalidId(id));
+            return Ok(());
+        };
+        let verb = if locked { "locked" } else { "unlocked" };
+        if task.locked == locked {
+            self.info(&format!("Task {} is already {}.", id, verb));
+            return Ok(());
+        }
+        let before = task.clone();
+        task.locked = locked;
+        task.touch();
+        let after = task.clone();
+        drop(tasks);
+        let summary = if locked { "Locked" } else { "Unlocked" };
+        self.save_and_record(
+            format!("{} task {}", summary, id),
+            Operation::Update {
+                before: vec![before],
+                after: vec![after],
+            },
+        )?;
+        self.info(&format!("Task {} {}.", id, verb));
+        Ok(())
+    }
+
+    /// Bumps a task's `updated_at` without changing anything else, to bring
+    /// it back to the top of `list --sort updated`.
+    pub fn touch_task(&self, id: usize) -> Result<(), TodoError> {
+        let mut tasks = self.tasks.borrow_mut();
+        let Some(task) = self.task_by_id_mut(&mut tasks, id) else {
+            self.report(TodoError::InvalidId(id));
+            return Ok(());
+        };
+        let before = task.clone();
+        task.touch();
+        let after = task.clone();
+        drop(tasks);
+        self.save_and_record(
+            format!("Touched task {}", id),
+            Operation::Update {
+                before: vec![before],
+                after: vec![after],
+            },
+        )?;
+        self.info(&format!("Task {} touched.", id));
+        Ok(())
+    }
+
+    fn is_focused(&self, id: usize) -> bool {
+        self.focus.borrow().as_ref().is_some_and(|focus| focus.id == id)
+    }
+
+    /// Focuses on pending task `id`, first ending any focus on another task
+    /// so that its time is counted.
+    pub fn start_focus(&self, id: usize) -> Result<(), TodoError> {
+        let tasks = self.tasks.borrow();
+        let task = self.task_by_id(&tasks, id).ok_or(TodoError::InvalidId(id))?;
+        if !task.is_pending() {
+            self.info(&format!("Task {} is already done.", id));
+            return Ok(());
+        }
+        let description = task.description.clone();
+        drop(tasks);
+        if self.is_focused(id) {
+            self.info(&format!("Already focusing on task {}.", id));
+            return Ok(());
+        }
+        self.stop_focus()?;
+        let focus = Focus { id, since: now_ist() };
+        focus.save(&Focus::path_for(&self.file_path))?;
+        *self.focus.borrow_mut() = Some(focus);
+        self.info(&format!("Focusing on task {}: {}", id, description));
+        Ok(())
+    }
+
+    /// Prints the focused task and how long it has been focused.
+    pub fn show_focus(&self) {
+        let focus = self.focus.borrow();
+        let tasks = self.tasks.borrow();
+        let focused = focus
+            .as_ref()
+            .and_then(|focus| Some((focus, self.task_by_id(&tasks, focus.id)?)));
+        match focused {
+            Some((focus, task)) => println!(
+                "{}. {} (focused for {})",
+                self.id_label(task.id),
+                task.description,
+                display::hours_minutes(now_ist() - focus.since)
+            ),
+            None => println!("No task is focused. Pick one with `focus <id>`."),
+        }
+    }
+
+    /// Ends the focus, adding the time since it started to the task's
+    /// `time_spent`.
+    pub fn stop_focus(&self) -> Result<(), TodoError> {
+        let Some(focus) = self.focus.borrow_mut().take() else {
+            return Ok(());
+        };
+        Focus::clear(&Focus::path_for(&self.file_path))?;
+        let spent = now_ist() - focus.since;
+        let mut tasks = self.tasks.borrow_mut();
+        // A task deleted while focused has nothing left to credit.
+        let Some(task) = self.task_by_id_mut(&mut tasks, focus.id) else {
+            return Ok(());
+        };
+        let before = task.clone();
+        task.add_time_spent(spent);
+        task.touch();
+        let after = task.clone();
+        drop(tasks);
+        self.save_and_record(
+            format!("Tracked {} on task {}", display::hours_minutes(spent), focus.id),
+            Operation::Update {
+                before: vec![before],
+                after: vec![after],
+            },
+        )?;
+        self.info(&format!(
+            "Stopped focusing on task {} after {}.",
+            focus.id,
+            display::hours_minutes(spent)
+        ));
+        Ok(())
+    }
+
+    /// Adds a pending copy of task `id` under the next free id, keeping what
+    /// it is about (description, tags, contexts, project, priority, link,
+    /// estimate, notes and unchecked subtasks) but none of its history or
+    /// links to other tasks. The copy is due at `due_date`, if given.
+    pub fn clone_task(
+        &mut self,
+        id: usize,
+        due_date: Option<DateTime<FixedOffset>>,
+    ) -> Result<(), TodoError> {
+        let tasks = self.tasks.borrow();
+        let Some(source) = self.task_by_id(&tasks, id).cloned() else {
+            self.report(TodoError::InvalidId(id));
+            return Ok(());
+        };
+        drop(tasks);
+        if let Err(e) = self.check_id_room(1) {
+            self.report(e);
+            return Ok(());
+        }
+        let mut task = Task::new(self.next_id, source.description, due_date);
+        task.tags = source.tags;
+        task.contexts = source.contexts;
+        task.project = source.project;
+        task.assignee = source.assignee;
+        task.priority = source.priority;
+        task.url = source.url;
+        task.estimate = source.estimate;
+        task.annotations = source.annotations;
+        task.subtasks = source
+            .subtasks
+            .into_iter()
+            .map(|subtask| SubTask { done: false, ..subtask })
+            .collect();
+        self.next_id += 1;
+        let new_id = task.id;
+        self.tasks.borrow_mut().push(task.clone());
+        self.save_and_record(
+            format!("Cloned task {} as {}", id, new_id),
+            Operation::Add { tasks: vec![task] },
+        )?;
+        self.info(&format!("Cloned task {} as task {}.", id, new_id));
+        Ok(())
+    }
+
+    /// Changes task `from`'s id to `to`, which must be unused, updating the
+    /// dependencies and the focus that name it.
+    pub fn renumber_task(&mut self, from: usize, to: usize) -> Result<(), TodoError> {
+        let mut tasks = self.tasks.borrow_mut();
+        if self.task_by_id(&tasks, from).is_none() {
+            return Err(TodoError::InvalidId(from));
+        }
+        if from == to {
+            drop(tasks);
+            self.info(&format!("Task {} already has id {}.", from, to));
+            return Ok(());
+        }
+        if self.task_by_id(&tasks, to).is_some() {
+            return Err(TodoError::IdTaken(to));
+        }
+        let next_id = to.checked_add(1).ok_or(TodoError::IdsExhausted)?;
+        history::renumber(&mut tasks, from, to);
+        drop(tasks);
+        self.next_id = self.next_id.max(next_id);
+        self.save_and_record(
+            format!("Renumbered task {} to {}", from, to),
+            Operation::Renumber { from, to },
+        )?;
+        if self.is_focused(from) {
+            let mut focus = self.focus.borrow_mut();
+            if let Some(focus) = focus.as_mut() {
+                focus.id = to;
+                focus.save(&Focus::path_for(&self.file_path))?;
+            }
+        }
+        self.info(&format!("Task {} is now task {}.", from, to));
+        Ok(())
+    }
+
+    /// Renumbers the tasks 0, 1, 2, ... in the order of their current ids,
+    /// after asking unless `yes`, since ids noted elsewhere stop matching.
+    /// Dependencies and the focus follow, and `undo` restores the old ids.
+    pub fn compact_ids(&mut self, yes: bool, batch: bool) -> Result<(), TodoError> {
+        let mut ids: Vec<usize> = self.tasks.borrow().iter().map(|t| t.id).collect();
+        ids.sort_unstable();
+        // Each new id is below the old one and every smaller id has already
+        // moved, so renumbering in this order never meets a taken id.
+        let moves: Vec<(usize, usize)> = ids
+            .into_iter()
+            .enumerate()
+            .map(|(to, from)| (from, to))
+            .filter(|(from, to)| from != to)
+            .collect();
+        if moves.is_empty() {
+            self.info("Task ids are already sequential.");
+            return Ok(());
+        }
+        let question = format!(
+            "Renumber {} task(s)? Ids noted elsewhere will no longer match.",
+            moves.len()
+        );
+        if !yes && !prompt::confirm(&question, batch) {
+            println!("No ids changed.");
+            return Ok(());
+        }
+        let mut tasks = self.tasks.borrow_mut();
+        for &(from, to) in &moves {
+            history::renumber(&mut tasks, from, to);
+        }
+        self.next_id = tasks.len();
+        drop(tasks);
+        let operations = moves.iter().map(|&(from, to)| Operation::Renumber { from, to }).collect();
+        self.save_and_record(
+            format!("Compacted the ids of {} task(s)", moves.len()),
+            Operation::Batch { operations },
+        )?;
+        if let Some(&(_, to)) = moves.iter().find(|(from, _)| self.is_focused(*from)) {
+            let mut focus = self.focus.borrow_mut();
+            if let Some(focus) = focus.as_mut() {
+                focus.id = to;
+                focus.save(&Focus::path_for(&self.file_path))?;
+            }
+        }
+        for (from, to) in &moves {
+            self.info(&format!("{} -> {}", from, to));
+        }
+        self.info(&format!("Renumbered {} task(s).", moves.len()));
+        Ok(())
+    }
+
+    /// Replaces task `id` with one new task per part, each inheriting the
+    /// parent's tags and priority. The parent is kept as is, marked done
+    /// (`mark_done`) or deleted (`delete`); all of it is one undo step.
+    pub fn split_task(
+        &mut self,
+        id: usize,
+        parts: Vec<String>,
+        mark_done: bool,
+        delete: bool,
+    ) -> Result<(), TodoError> {
+        let mut tasks = self.tasks.borrow_mut();
+        let Some(position) = self.position_of_id(&tasks, id) else {
+            self.report(TodoError::InvalidId(id));
+            return Ok(());
+        };
+        if let Err(e) = self.check_id_room(parts.len()) {
+            self.report(e);
+            return Ok(());
+        }
+        let parent = tasks[position].clone();
+        let mut added = Vec::with_capacity(parts.len());
+        for part in parts {
+            let mut task = Task::new(self.next_id, part, None);
+            task.tags = parent.tags.clone();
+            task.contexts = parent.contexts.clone();
+            task.project = parent.project.clone();
+            task.assignee = parent.assignee.clone();
+            task.priority = parent.priority;
+            self.next_id += 1;
+            added.push(task);
+        }
+        tasks.extend(added.iter().cloned());
+        let ids: Vec<String> = added.iter().map(|t| t.id.to_string()).collect();
+        let mut operations = vec![Operation::Add { tasks: added }];
+        let mut completed = None;
+        if delete {
+            let removed = tasks.remove(position);
+            operations.push(Operation::Delete {
+                removed: vec![(position, removed)],
+            });
+        } else if mark_done && parent.is_pending() {
+            tasks[position].mark_done();
+            completed = Some(tasks[position].clone());
+            operations.push(Operation::Update {
+                before: vec![parent.clone()],
+                after: vec![tasks[position].clone()],
+            });
+        }
+        drop(tasks);
+        self.save_and_record(
+            format!("Split task {} into {}", id, ids.join(", ")),
+            Operation::Batch { operations },
+        )?;
+        self.info(&format!("Split task {} into task(s) {}.", id, ids.join(", ")));
+        if delete {
+            self.info(&format!("Task \"{}\" (ID: {}) deleted.", parent.description, id));
+        }
+        if let Some(task) = completed {
+            self.info(&format!("Task {} marked as done.", id));
+            self.run_on_done_hook(&task);
+        }
+        Ok(())
+    }
+
+    /// Marks the tasks at `indices` done with a single save, recording `note`
+    /// as each one's completion note. A repeating task moves on to its next
+    /// occurrence and gets the note as an annotation instead, since it stays
+    /// pending. Messages come in file order, or in the order given with
+    /// `keep_order`. Locked tasks are skipped unless `force`. The `on_done`
+    /// hook runs only once the save has succeeded.
+    pub fn mark_tasks_done(
+        &self,
+        indices: &[usize],
+        keep_order: bool,
+        force: bool,
+        note: Option<&str>,
+    ) -> Result<(), TodoError> {
+        let order = if keep_order {
+            indices.to_vec()
+        } else {
+            let mut ascending = removal_order(indices);
+            ascending.reverse();
+            ascending
+        };
+        let focus = self.focus.borrow().clone();
+        let mut focus_ended = None;
+        let mut tasks = self.tasks.borrow_mut();
+        let mut before = Vec::new();
+        let mut done_tasks = Vec::new();
+        for index in order {
+            let Some(task) = tasks.get_mut(index) else {
+                self.report(TodoError::InvalidIndex(index));
+                continue;
+            };
+            if !task.is_pending() {
+                self.info(&format!("Task {} is already done.", task.id));
+                continue;
+            }
+            if task.locked && !force {
+                self.report(TodoError::Locked(task.id));
+                continue;
+            }
+            before.push(task.clone());
+            if let Some(focus) = focus.as_ref().filter(|focus| focus.id == task.id) {
+                let spent = now_ist() - focus.since;
+                task.add_time_spent(spent);
+                focus_ended = Some(spent);
+            }
+            if let (Some(recurrence), Some(due)) = (&task.recurrence, task.due_date) {
+                // Stay on the original schedule, skipping occurrences already
+                // missed, rather than counting from the completion time.
+                let now = now_ist();
+                let mut next = recurrence.next_after(due);
+                while let Some(date) = next.filter(|date| *date <= now) {
+                    next = recurrence.next_after(date);
+                }
+                if let Some(next) = next {
+                    task.due_date = Some(next);
+                    task.notified = false;
+                    if let Some(note) = note {
+                        task.annotations.push(Annotation {
+                            at: now,
+                            text: format!("Completed: {}", note),
+                        });
+                    }
+                    task.touch();
+                    done_tasks.push(task.clone());
+                    continue;
+                }
+            }
+            task.mark_done();
+            task.completion_note = note.map(str::to_string);
+            done_tasks.push(task.clone());
+        }
+        drop(tasks);
+        if done_tasks.is_empty() {
+            return Ok(());
+        }
+        let ids: Vec<String> = done_tasks.iter().map(|t| t.id.to_string()).collect();
+        self.save_and_record(
+            format!("Marked task(s) {} done", ids.join(", ")),
+            Operation::Update {
+                before,
+                after: done_tasks.clone(),
+            },
+        )?;
+        if let (Some(focus), Some(spent)) = (focus, focus_ended) {
+            if let Err(e) = Focus::clear(&Focus::path_for(&self.file_path)) {
+                eprintln!("Warning: could not clear the focused task: {}", e);
+            }
+            *self.focus.borrow_mut() = None;
+            self.info(&format!(
+                "Stopped focusing on task {} after {}.",
+                focus.id,
+                display::hours_minutes(spent)
+            ));
+        }
+        for task in &done_tasks {
+            match (task.is_pending(), task.due_date) {
+                (true, Some(due)) => self.info(&format!(
+                    "Task {} done; it repeats, next due {}.",
+                    task.id,
+                    display::format_datetime(due)
+                )),
+                _ => self.info(&format!("Task {} marked as done.", task.id)),
+            }
+            self.run_on_done_hook(task);
+        }
+        Ok(())
+    }
+
+    /// Runs the configured `on_done` command for `task`. Hook failures are
+    /// reported as warnings and never undo the completion. The task's values
+    /// reach the command only as the variables `TODO_ID` and
+    /// `TODO_DESCRIPTION` (and `$1` and `$2` under `sh`), which `{id}` and
+    /// `{description}` in the template refer to, so a description can't
+    /// inject shell syntax.
+    fn run_on_done_hook(&self, task: &Task) {
+        let Some(template) = &self.config.on_done else {
+            return;
+        };
+        let id = task.id.to_string();
+        let mut command = if cfg!(windows) {
+            // Delayed expansion (`!VAR!`) substitutes after the line is parsed.
+            let line = template
+                .replace("{id}", "!TODO_ID!")
+                .replace("{description}", "!TODO_DESCRIPTION!");
+            let mut command = Command::new("cmd");
+            command.args(["/V:ON", "/C", &line]);
+            command
+        } else {
+            let script = template
+                .replace("{id}", "${TODO_ID}")
+                .replace("{description}", "${TODO_DESCRIPTION}");
+            let mut command = Command::new("sh");
+            command.args(["-c", &script, "sh", &id, &task.description]);
+            command
+        };
+        command.env("TODO_ID", &id).env("TODO_DESCRIPTION", &task.description);
+        match command.status() {
+            Ok(status) if status.success() => {}
+            Ok(status) => eprintln!("Warning: on_done hook exited with {}", status),
+            Err(e) => eprintln!("Warning: could not run on_done hook: {}", e),
+        }
+    }
+
+    /// Deletes the tasks at `indices` with a single save. Deleting more than
+    /// one lists them and asks first, unless `yes` is set. Messages come in
+    /// file order, or in the order given with `keep_order`. Locked tasks are
+    /// kept unless `force`.
+    pub fn delete_tasks(
+        &self,
+        indices: &[usize],
+        keep_order: bool,
+        force: bool,
+        yes: bool,
+        batch: bool,
+    ) -> Result<(), TodoError> {
+        let order: Vec<usize> = {
+            let tasks = self.tasks.borrow();
+            removal_order(indices)
+                .into_iter()
+                .filter(|&index| match tasks.get(index) {
+                    None => {
+                        self.report(TodoError::InvalidIndex(index));
+                        false
+                    }
+                    Some(task) if task.locked && !force => {
+                        self.report(TodoError::Locked(task.id));
+                        false
+                    }
+                    Some(_) => true,
+                })
+                .collect()
+        };
+        if order.is_empty() {
+            return Ok(());
+        }
+        if order.len() > 1 && !yes {
+            let tasks = self.tasks.borrow();
+            let descriptions: Vec<&str> =
+                order.iter().rev().map(|&index| tasks[index].description.as_str()).collect();
+            if !prompt::confirm_removal("Delete them?", &descriptions, batch) {
+                println!("Nothing deleted.");
+                return Ok(());
+            }
+        }
+        let mut tasks = self.tasks.borrow_mut();
+        let mut removed_tasks: Vec<(usize, Task)> =
+            order.into_iter().map(|index| (index, tasks.remove(index))).collect();
+        drop(tasks);
+        removed_tasks.reverse();
+        let ids: Vec<String> = removed_tasks.iter().map(|(_, t)| t.id.to_string()).collect();
+        self.save_and_record(
+            format!("Deleted task(s) {}", ids.join(", ")),
+            Operation::Delete {
+                removed: removed_tasks.clone(),
+            },
+        )?;
+        if keep_order {
+            removed_tasks.sort_by_key(|(position, _)| {
+                indices.iter().position(|index| index == position)
+            });
+        }
+        for (_, removed_task) in &removed_tasks {
+            self.info(&format!(
+                "Task \"{}\" (ID: {}) deleted.",
+                removed_task.description, removed_task.id
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// How far `actual` ran over (+) or under (-) `estimate`, as a rounded
+/// percentage of it, e.g. "+56%". `None` for a zero estimate.
+fn variance(estimate: Duration, actual: Duration) -> Option<String> {
+    let estimate = estimate.num_minutes();
+    if estimate <= 0 {
+        return None;
+    }
+    let difference = actual.num_minutes() - estimate;
+    let percent = (difference * 200 + estimate * difference.signum()) / (2 * estimate);
+    Some(format!("{:+}%", percent))
+}
+
+/// When the file at `path` was last modified; `None` if it doesn't exist.
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Opens `path` in the desktop's default application for its type, such as
+/// a browser for a web page, without waiting for it to close.
+fn open_with_default_app(path: &Path) -> io::Result<()> {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        // The empty argument is the window title `start` takes first.
+        command.args(["/C", "start", ""]);
+        command
+    } else if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else {
+        Command::new("xdg-open")
+    };
+    command.arg(path).spawn()?;
+    Ok(())
+}
+
+/// `path` made absolute, through symlinks when it exists.
+fn absolute(path: &Path) -> PathBuf {
+    fs::canonicalize(path)
+        .unwrap_or_else(|_| std::env::current_dir().unwrap_or_default().join(path))
+}
+
+/// What replacing `old` with `new` adds, removes and edits, matching tasks by
+/// id, as "id: description" lines. Creation and update times are left out of
+/// the comparison, since imported lines get fresh ones.
+fn replacement_diff(old: &[Task], new: &[Task]) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let line = |task: &Task| format!("{}: {}", task.id, task.description);
+    let untimed = |task: &Task| Task { created_at: None, updated_at: None, ..task.clone() };
+    let find = |tasks: &[Task], id: usize| tasks.iter().find(|t| t.id == id).map(untimed);
+    let added = new.iter().filter(|t| find(old, t.id).is_none()).map(line).collect();
+    let removed = old.iter().filter(|t| find(new, t.id).is_none()).map(line).collect();
+    let edited = new
+        .iter()
+        .filter(|t| find(old, t.id).is_some_and(|before| before != untimed(t)))
+        .map(line)
+        .collect();
+    (added, removed, edited)
+}
+
+/// Positions, ascending, of the tasks beyond the `keep` most recently
+/// created, pending ones counting as newer than done ones. Locked tasks and
+/// those `skip` matches are neither counted nor returned.
+fn beyond_keep(tasks: &[Task], keep: usize, skip: impl Fn(&Task) -> bool) -> Vec<usize> {
+    let mut ranked: Vec<usize> =
+        (0..tasks.len()).filter(|&p| !tasks[p].locked && !skip(&tasks[p])).collect();
+    // Newest first; tasks without a creation time count as the oldest.
+    ranked.sort_by(|&a, &b| {
+        let (a, b) = (&tasks[a], &tasks[b]);
+        b.is_pending().cmp(&a.is_pending()).then(b.created_at.cmp(&a.created_at))
+    });
+    let mut beyond = ranked.split_off(keep.min(ranked.len()));
+    beyond.sort_unstable();
+    beyond
+}
+
+/// Counts for the `list` footer, e.g. "5 tasks: 3 pending, 2 done, 1 overdue",
+/// ending in ", 1 stale" when `max_age` is given.
+fn summary_line(
+    tasks: &[&Task],
+    now: DateTime<FixedOffset>,
+    grace: Duration,
+    max_age: Option<Duration>,
+) -> String {
+    let pending = tasks.iter().filter(|t| t.is_pending()).count();
+    let overdue = tasks.iter().filter(|t| t.is_overdue(now, grace)).count();
+    let mut line = format!(
+        "{} {}: {} pending, {} done, {} overdue",
+        tasks.len(),
+        if tasks.len() == 1 { "task" } else { "tasks" },
+        pending,
+        tasks.len() - pending,
+        overdue
+    );
+    if let Some(max_age) = max_age {
+        let stale = tasks.iter().filter(|t| t.is_stale(now, max_age)).count();
+        line.push_str(&format!(", {} stale", stale));
+    }
+    line
+}
+
+/// Orders positions for one-by-one removal from a `Vec`: highest first, with
+/// duplicates dropped. Removing `[1, 3]` in the given order would shift the
+/// task at 3 down to 2 and delete the wrong one; removing 3 then 1 is safe.
+fn removal_order(positions: &[usize]) -> Vec<usize> {
+    let mut ordered = positions.to_vec();
+    ordered.sort_unstable_by(|a, b| b.cmp(a));
+    ordered.dedup();
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A quiet manager over a fresh task file in its own temporary directory.
+    fn manager(name: &str) -> TaskManager {
+        let dir = std::env::temp_dir().join(format!("todo_cli-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let mut manager = TaskManager::new(dir.join("tasks.json"), Config::default()).unwrap();
+        manager.quiet = true;
+        manager
+    }
+
+    fn cleanup(manager: TaskManager) {
+        if let Some(dir) = manager.file_path.parent() {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+
+    fn descriptions(items: &[&str]) -> Vec<(String, Option<DateTime<FixedOffset>>)> {
+        items.iter().map(|d| (d.to_string(), None)).collect()
+    }
+
+    /// Every task is indexed at its position, and nothing else is.
+    fn assert_index_matches(manager: &TaskManager) {
+        let tasks = manager.tasks.borrow();
+        let index = manager.index.borrow();
+        assert_eq!(index.len(), tasks.len());
+        for (position, task) in tasks.iter().enumerate() {
+            assert_eq!(index.get(&task.id), Some(&position), "task {}", task.id);
+        }
+    }
+
+    #[test]
+    fn index_stays_consistent_after_adds_and_deletes() {
+        let mut manager = manager("index");
+        manager.add_tasks(descriptions(&["a", "b", "c", "d", "e"])).unwrap();
+        assert_index_matches(&manager);
+
+        manager.delete_tasks(&[1, 3], false, false, true, true).unwrap();
+        assert_index_matches(&manager);
+        let task = Task::new(manager.next_id, "f".to_string(), None);
+        manager.add_task(task).unwrap();
+        manager.delete_tasks(&[0], false, false, true, true).unwrap();
+        assert_index_matches(&manager);
+
+        let ids: Vec<usize> = manager.tasks.borrow().iter().map(|t| t.id).collect();
+        assert_eq!(ids, [2, 4, 5]);
+        for id in [2, 4, 5] {
+            let tasks = manager.tasks.borrow();
+            assert_eq!(manager.task_by_id(&tasks, id).map(|t| t.id), Some(id));
+        }
+        for id in [0, 1, 3] {
+            assert!(manager.task_by_id(&manager.tasks.borrow(), id).is_none());
+        }
+        cleanup(manager);
+    }
+
+    #[test]
+    fn removal_order_is_descending_without_duplicates() {
+        assert_eq!(removal_order(&[1, 3]), [3, 1]);
+        assert_eq!(removal_order(&[2, 0, 2, 5]), [5, 2, 0]);
+        assert!(removal_order(&[]).is_empty());
+    }
+
+    #[test]
+    fn removal_order_keeps_later_positions_valid() {
+        let mut tasks = vec!["a", "b", "c", "d"];
+        // In the given order, removing "b" shifts "c" down to 1, so position 2
+        // then names "d" and the wrong task goes.
+        let mut naive = tasks.clone();
+        naive.remove(1);
+        naive.remove(2);
+        assert_eq!(naive, ["a", "c"]);
+        for position in removal_order(&[1, 2]) {
+            tasks.remove(position);
+        }
+        assert_eq!(tasks, ["a", "d"]);
+    }
+
+    #[test]
+    fn deleting_ascending_positions_removes_the_named_tasks() {
+        let mut manager = manager("removal");
+        manager.add_tasks(descriptions(&["a", "b", "c", "d"])).unwrap();
+        manager.delete_tasks(&[1, 2], false, false, true, true).unwrap();
+        let left: Vec<String> =
+            manager.tasks.borrow().iter().map(|t| t.description.clone()).collect();
+        assert_eq!(left, ["a", "d"]);
+        cleanup(manager);
+    }
+
+    #[test]
+    fn add_tasks_writes_the_file_once_for_a_batch() {
+        let mut manager = manager("bulk");
+        manager.add_tasks(descriptions(&["first"])).unwrap();
+        let before = fs::read_to_string(&manager.file_path).unwrap();
+
+        let items: Vec<String> = (0..1000).map(|n| format!("imported {}", n)).collect();
+        let items: Vec<&str> = items.iter().map(String::as_str).collect();
+        assert_eq!(manager.add_tasks(descriptions(&items)).unwrap(), 1000);
+
+        // Each write copies the file it replaces to the backup, so after a
+        // single write the backup still holds the file from before the batch.
+        let backup = fs::read_to_string(TaskManager::backup_path(&manager.file_path)).unwrap();
+        assert_eq!(backup, before);
+        let history = History::load(&History::path_for(&manager.file_path)).unwrap();
+        assert_eq!(history.recent(usize::MAX).len(), 2);
+        let saved: Vec<Task> =
+            serde_json::from_str(&fs::read_to_string(&manager.file_path).unwrap()).unwrap();
+        assert_eq!(saved.len(), 1001);
+        cleanup(manager);
+    }
+
+    #[test]
+    fn archiving_leaves_no_copy_behind_when_the_save_fails() {
+        let mut manager = manager("archive");
+        manager.add_tasks(descriptions(&["done", "open"])).unwrap();
+        manager.mark_tasks_done(&[0], false, false, None).unwrap();
+        // As if another process had written the task file since it was loaded.
+        let file = fs::File::options().write(true).open(&manager.file_path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH).unwrap();
+
+        assert!(manager.archive_done().is_err());
+        assert!(!manager.archive_path().exists());
+        assert_eq!(manager.tasks.borrow().len(), 2);
+        cleanup(manager);
+    }
+
+    #[test]
+    fn loading_a_task_with_the_largest_id_runs_out_of_ids() {
+        let manager = manager("max-id");
+        let task = format!(r#"{{"id": {}, "description": "a", "status": "Pending"}}"#, usize::MAX);
+        let json = format!("[{}]", task);
+        fs::write(&manager.file_path, json).unwrap();
+        let loaded = TaskManager::new(manager.file_path.clone(), Config::default());
+        assert!(matches!(loaded, Err(TodoError::IdsExhausted)));
+        cleanup(manager);
+    }
+
+    #[test]
+    fn add_tasks_refuses_ids_past_the_largest() {
+        let mut manager = manager("id-room");
+        manager.next_id = usize::MAX - 1;
+        assert!(matches!(
+            manager.add_tasks(descriptions(&["a", "b"])),
+            Err(TodoError::IdsExhausted)
+        ));
+        assert!(manager.tasks.borrow().is_empty());
+        assert_eq!(manager.add_tasks(descriptions(&["a"])).unwrap(), 1);
+        assert_eq!(manager.tasks.borrow()[0].id, usize::MAX - 1);
+        cleanup(manager);
+    }
+
+    #[test]
+    fn no_op_commands_do_not_write_the_task_file() {
+        let mut manager = manager("no-op");
+        manager.add_tasks(descriptions(&["a"])).unwrap();
+        manager.add_tasks(descriptions(&["b"])).unwrap();
+        let backup_path = TaskManager::backup_path(&manager.file_path);
+        let saved = modified(&manager.file_path);
+        let backup = fs::read_to_string(&backup_path).unwrap();
+        let backup_saved = modified(&backup_path);
+
+        manager.touch_task(99).unwrap();
+        assert!(!manager.save_tasks().unwrap());
+        assert_eq!(modified(&manager.file_path), saved);
+        assert_eq!(fs::read_to_string(&backup_path).unwrap(), backup);
+        assert_eq!(modified(&backup_path), backup_saved);
+        cleanup(manager);
+    }
+
+    #[test]
+    fn read_only_commands_do_not_create_the_task_file() {
+        let mut manager = manager("no-file");
+        manager.fix_duplicate_ids(false).unwrap();
+        manager.apply_retention().unwrap();
+        manager.list_tasks(&ListOptions::default()).unwrap();
+        manager.list_summary(&ListOptions { summary: true, ..ListOptions::default() });
+        manager.stats(false, false, Separator::default()).unwrap();
+        let query = SearchQuery::Text("milk".to_string());
+        manager.search_tasks(&query, false, None, false, (0, None)).unwrap();
+        assert!(!manager.save_tasks().unwrap());
+        assert!(!manager.file_path.exists());
+        cleanup(manager);
+    }
+
+    #[test]
+    fn streamed_json_matches_the_in_memory_serialization() {
+        let mut manager = manager("stream");
+        manager.add_tasks(descriptions(&["a", "b \"quoted\"", "日本語"])).unwrap();
+        manager.tasks.borrow_mut()[1].tags = vec!["work".to_string()];
+        let tasks = manager.tasks.borrow().clone();
+
+        for limit in [None, Some(2)] {
+            let options = ListOptions { limit, ..ListOptions::default() };
+            let mut streamed = Vec::new();
+            manager.write_tasks_json(&options, &mut streamed).unwrap();
+            let shown = &tasks[..limit.unwrap_or(tasks.len())];
+            let expected = serde_json::to_string_pretty(shown).unwrap() + "\n";
+            assert_eq!(String::from_utf8(streamed).unwrap(), expected);
+        }
+
+        let path = manager.file_path.with_file_name("export.json");
+        manager.export_tasks(&[], output::Encoding::default(), Some(&path), false).unwrap();
+        let expected = serde_json::to_string_pretty(&tasks).unwrap() + "\n";
+        assert_eq!(fs::read_to_string(&path).unwrap(), expected);
+        cleanup(manager);
+    }
+
+    #[test]
+    fn plain_text_import_skips_comments_and_blank_lines() {
+        let mut manager = manager("import-comments");
+        let path = manager.file_path.with_file_name("backlog.txt");
+        let text = "# header\nBuy milk\n\n   \n  # comment\n\tcall #42 back  \n#\nlast\n";
+        fs::write(&path, text).unwrap();
+        manager.import_tasks(&path, false, true, true, false).unwrap();
+        let imported: Vec<String> =
+            manager.tasks.borrow().iter().map(|t| t.description.clone()).collect();
+        assert_eq!(imported, ["Buy milk", "call #42 back", "last"]);
+        cleanup(manager);
     }
 }
@@ -0,0 +1,220 @@
+use crate::task::Task;
+use crate::todotxt;
+use chrono::{DateTime, FixedOffset};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::{Map, Value};
+use std::io::{self, Write};
+
+/// How `list`, `search`, `stats` and `show` print their results, chosen with
+/// the global `--format`. `export` and `import` use it too.
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Text for reading in a terminal
+    #[default]
+    Human,
+    /// JSON, with tasks in the same shape as `tasks.json`
+    Json,
+    /// Comma-separated values with a header row
+    Csv,
+    /// One todo.txt line per task; the format `import` reads with it
+    Todotxt,
+    /// A standalone web page with a table of the tasks, for `export`
+    Html,
+}
+
+/// Columns of a task in CSV output.
+const TASK_COLUMNS: [&str; 8] = [
+    "id",
+    "status",
+    "description",
+    "due_date",
+    "priority",
+    "tags",
+    "created_at",
+    "completed_at",
+];
+
+/// Quotes `text` for CSV when it holds a comma, quote or line break.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Writes a CSV header row followed by `rows` to stdout.
+pub fn write_csv(headers: &[&str], rows: &[Vec<String>]) -> io::Result<()> {
+    write_csv_to(&mut io::stdout().lock(), headers, rows)
+}
+
+fn write_csv_to(out: &mut impl Write, headers: &[&str], rows: &[Vec<String>]) -> io::Result<()> {
+    writeln!(out, "{}", headers.join(","))?;
+    for row in rows {
+        let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+/// Resolves `export --fields` names: keys of a task in `tasks.json` for
+/// JSON, or `TASK_COLUMNS` for CSV, with `due` accepted for `due_date`.
+pub fn export_fields(names: &[String], format: OutputFormat) -> Result<Vec<String>, String> {
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+    let known: Vec<String> = match format {
+        OutputFormat::Csv => TASK_COLUMNS.map(String::from).to_vec(),
+        OutputFormat::Todotxt => return Err("--fields doesn't apply to todo.txt.".to_string()),
+        OutputFormat::Html => return Err("--fields doesn't apply to HTML.".to_string()),
+        OutputFormat::Human | OutputFormat::Json => {
+            match serde_json::to_value(Task::new(0, String::new(), None)) {
+                Ok(Value::Object(task)) => task.into_iter().map(|(key, _)| key).collect(),
+                _ => unreachable!("a task serializes to an object"),
+            }
+        }
+    };
+    names
+        .iter()
+        .map(|name| {
+            let key = if name == "due" { "due_date" } else { name.as_str() };
+            if known.iter().any(|k| k == key) {
+                Ok(key.to_string())
+            } else {
+                Err(format!("Unknown field '{}'. Choose from: {}.", name, known.join(", ")))
+            }
+        })
+        .collect()
+}
+
+/// Some of a task's keys, serialized in the order they were asked for,
+/// which a `serde_json::Map` would sort instead.
+pub struct TaskFields<'a> {
+    values: Map<String, Value>,
+    keys: &'a [&'a str],
+}
+
+impl Serialize for TaskFields<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.keys.len()))?;
+        for key in self.keys {
+            if let Some(value) = self.values.get(*key) {
+                map.serialize_entry(key, value)?;
+            }
+        }
+        map.end()
+    }
+}
+
+/// `task` as a JSON object with only `keys`, in the order given.
+pub fn task_fields<'a>(task: &Task, keys: &'a [&'a str]) -> TaskFields<'a> {
+    let Ok(Value::Object(values)) = serde_json::to_value(task) else {
+        unreachable!("a task serializes to an object");
+    };
+    TaskFields { values, keys }
+}
+
+/// Writes `tasks` as CSV with only `columns`, which must be `TASK_COLUMNS`.
+pub fn write_csv_columns(
+    out: &mut impl Write,
+    tasks: &[&Task],
+    columns: &[&str],
+) -> io::Result<()> {
+    let picks: Vec<usize> = columns
+        .iter()
+        .filter_map(|column| TASK_COLUMNS.iter().position(|c| c == column))
+        .collect();
+    let rows: Vec<Vec<String>> = tasks
+        .iter()
+        .map(|task| {
+            let row = task_row(task);
+            picks.iter().map(|&i| row[i].clone()).collect()
+        })
+        .collect();
+    write_csv_to(out, columns, &rows)
+}
+
+fn timestamp(dt: Option<DateTime<FixedOffset>>) -> String {
+    dt.map(|dt| dt.to_rfc3339()).unwrap_or_default()
+}
+
+fn task_row(task: &Task) -> Vec<String> {
+    vec![
+        task.id.to_string(),
+        format!("{:?}", task.status),
+        task.description.clone(),
+        timestamp(task.due_date),
+        task.priority.map(|p| format!("{:?}", p)).unwrap_or_default(),
+        task.tags.join(";"),
+        timestamp(task.created_at),
+        timestamp(task.completed_at),
+    ]
+}
+
+/// Prints `tasks` as a JSON array or CSV table. `Human` output is left to the
+/// caller, which knows how the command lays out its text.
+pub fn print_tasks(tasks: &[&Task], format: OutputFormat) -> io::Result<()> {
+    write_tasks(&mut io::stdout().lock(), tasks, format)
+}
+
+/// Like `print_tasks`, to any writer.
+pub fn write_tasks(out: &mut impl Write, tasks: &[&Task], format: OutputFormat) -> io::Result<()> {
+    match format {
+        OutputFormat::Human => unreachable!("human output is printed by each command"),
+        OutputFormat::Html => unreachable!("only export writes HTML"),
+        OutputFormat::Json => writeln!(out, "{}", serde_json::to_string_pretty(tasks)?)?,
+        OutputFormat::Csv => {
+            let rows: Vec<Vec<String>> = tasks.iter().map(|task| task_row(task)).collect();
+            write_csv_to(out, &TASK_COLUMNS, &rows)?;
+        }
+        OutputFormat::Todotxt => {
+            for task in tasks {
+                writeln!(out, "{}", todotxt::format_line(task))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Byte-level choices for `export` output, for tools that want them, such
+/// as Excel, which reads UTF-8 CSV correctly only after a byte order mark.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Encoding {
+    /// Start with a UTF-8 byte order mark.
+    pub bom: bool,
+    /// End lines with CRLF rather than LF.
+    pub crlf: bool,
+}
+
+/// Writes `text` to `out` as `encoding` says.
+pub fn write_encoded(out: impl Write, text: &[u8], encoding: Encoding) -> io::Result<()> {
+    let mut out = io::BufWriter::new(out);
+    if encoding.bom {
+        out.write_all("\u{feff}".as_bytes())?;
+    }
+    if encoding.crlf {
+        for line in text.split_inclusive(|&byte| byte == b'\n') {
+            match line.strip_suffix(b"\n") {
+                Some(line) => {
+                    out.write_all(line.strip_suffix(b"\r").unwrap_or(line))?;
+                    out.write_all(b"\r\n")?;
+                }
+                None => out.write_all(line)?,
+            }
+        }
+    } else {
+        out.write_all(text)?;
+    }
+    out.flush()
+}
+
+/// Prints one task as a JSON object or a one-row CSV table.
+pub fn print_task(task: &Task, format: OutputFormat) -> io::Result<()> {
+    match format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(task)?);
+            Ok(())
+        }
+        _ => print_tasks(&[task], format),
+    }
+}
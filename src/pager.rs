@@ -0,0 +1,59 @@
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+/// `less` is rarely installed on Windows; `more` always is.
+const DEFAULT_PAGER: &str = if cfg!(windows) { "more" } else { "less -R" };
+
+/// Rows to assume when `$LINES` isn't set.
+const DEFAULT_HEIGHT: usize = 24;
+
+fn terminal_height() -> usize {
+    env::var("LINES")
+        .ok()
+        .and_then(|lines| lines.parse().ok())
+        .unwrap_or(DEFAULT_HEIGHT)
+}
+
+/// Prints `lines`, through `$PAGER` (default `less -R`) when `enabled`, stdout
+/// is a terminal and they wouldn't fit on one screen. An empty `$PAGER`
+/// turns paging off, as in git. Falls back to plain printing if the pager
+/// can't be started.
+pub fn print_lines(lines: &[String], enabled: bool) -> io::Result<()> {
+    let pager = env::var("PAGER").unwrap_or_else(|_| DEFAULT_PAGER.to_string());
+    let use_pager = enabled
+        && !pager.trim().is_empty()
+        && io::stdout().is_terminal()
+        && lines.len() > terminal_height();
+    if use_pager && let Ok(mut child) = spawn(&pager) {
+        if let Some(mut stdin) = child.stdin.take() {
+            for line in lines {
+                // The user may quit the pager before reading everything.
+                if writeln!(stdin, "{}", line).is_err() {
+                    break;
+                }
+            }
+        }
+        child.wait()?;
+        return Ok(());
+    }
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for line in lines {
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+fn spawn(pager: &str) -> io::Result<std::process::Child> {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", pager]);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.args(["-c", pager]);
+        command
+    };
+    command.stdin(Stdio::piped()).spawn()
+}
@@ -0,0 +1,111 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// How many descriptions `confirm_listed` lists before summing up the rest.
+const PREVIEW_COUNT: usize = 3;
+
+/// Asks a yes/no question on stderr and reads the answer from stdin. Anything
+/// but "y" or "yes" counts as no. In `--batch` mode nothing is asked and the
+/// answer is always no, so scripts take the safe path.
+pub fn confirm(question: &str, batch: bool) -> bool {
+    if batch {
+        eprintln!("{} [y/N] no (--batch)", question);
+        return false;
+    }
+    eprint!("{} [y/N] ", question);
+    if io::stderr().flush().is_err() {
+        return false;
+    }
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Asks on stderr for `word` to be typed, for commands too destructive for a
+/// y/N question. Only the exact word counts as yes; `--batch` always answers
+/// no.
+pub fn confirm_typed(question: &str, word: &str, batch: bool) -> bool {
+    if batch {
+        eprintln!("{} Type {} to confirm: no (--batch)", question, word);
+        return false;
+    }
+    eprint!("{} Type {} to confirm: ", question, word);
+    if io::stderr().flush().is_err() {
+        return false;
+    }
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    answer.trim() == word
+}
+
+/// Reads one line of text typed in answer to `question`, asked on stderr.
+/// `None` when it can't be asked: under `--batch`, when stdin isn't a
+/// terminal, or when input ends first.
+pub fn ask(question: &str, batch: bool) -> Option<String> {
+    if batch || !io::stdin().is_terminal() {
+        return None;
+    }
+    eprint!("{} ", question);
+    io::stderr().flush().ok()?;
+    let mut answer = String::new();
+    match io::stdin().lock().read_line(&mut answer) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(answer.trim_end_matches(['\r', '\n']).to_string()),
+    }
+}
+
+/// Asks `question` after listing on stderr what a destructive command is
+/// about to remove: how many tasks, and the first few descriptions. Used by
+/// every command that removes tasks in bulk, so they all ask the same way.
+pub fn confirm_removal(question: &str, descriptions: &[&str], batch: bool) -> bool {
+    eprintln!("{} task(s) will be removed:", descriptions.len());
+    preview_then_confirm(question, descriptions, batch)
+}
+
+/// Like `confirm_removal`, for commands that change tasks a filter picked
+/// rather than removing them.
+pub fn confirm_listed(question: &str, descriptions: &[&str], batch: bool) -> bool {
+    eprintln!("{} task(s) match:", descriptions.len());
+    preview_then_confirm(question, descriptions, batch)
+}
+
+fn preview_then_confirm(question: &str, descriptions: &[&str], batch: bool) -> bool {
+    for line in preview('-', descriptions) {
+        eprintln!("{}", line);
+    }
+    confirm(question, batch)
+}
+
+/// The first few of `items`, each after `mark`, and a count of the rest.
+fn preview<S: AsRef<str>>(mark: char, items: &[S]) -> Vec<String> {
+    let mut lines: Vec<String> = items
+        .iter()
+        .take(PREVIEW_COUNT)
+        .map(|item| format!("  {} {}", mark, item.as_ref()))
+        .collect();
+    if items.len() > PREVIEW_COUNT {
+        lines.push(format!("  ... and {} more", items.len() - PREVIEW_COUNT));
+    }
+    lines
+}
+
+/// Prints what a `--dry-run` would have changed: the counts, then a few of
+/// the tasks added, removed and edited, listed the way confirmations list
+/// them.
+pub fn print_diff(added: &[String], removed: &[String], edited: &[String]) {
+    println!(
+        "Dry run: {} added, {} removed, {} edited; nothing was written.",
+        added.len(),
+        removed.len(),
+        edited.len()
+    );
+    for line in [('+', added), ('-', removed), ('~', edited)]
+        .into_iter()
+        .flat_map(|(mark, items)| preview(mark, items))
+    {
+        println!("{}", line);
+    }
+}
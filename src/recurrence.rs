@@ -0,0 +1,190 @@
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Months, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+
+/// How a repeating task's due date moves forward when it is completed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Recurrence {
+    Daily,
+    Weekly,
+    /// Same day of every month; months without that day use their last day,
+    /// so a task due on the 31st falls on Feb 28/29 and is back on Mar 31.
+    Monthly { day: u32 },
+    /// Each of these days of the week, Monday first, e.g. Mon, Wed and Fri.
+    Weekdays { days: Vec<Weekday> },
+}
+
+/// A `--repeat` value: `daily`, `weekly`, `monthly` (on the day of the
+/// month of the first due date), or `weekdays:mon,wed,fri` for given days of
+/// the week, with plain `weekdays` meaning Monday to Friday.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RepeatRule {
+    Daily,
+    Weekly,
+    Monthly,
+    Weekdays(Vec<Weekday>),
+}
+
+impl RepeatRule {
+    pub fn parse(text: &str) -> Result<RepeatRule, String> {
+        let text = text.trim().to_lowercase();
+        let (rule, days) = match text.split_once(':') {
+            Some((rule, days)) => (rule, Some(days)),
+            None => (text.as_str(), None),
+        };
+        match (rule, days) {
+            ("daily", None) => Ok(RepeatRule::Daily),
+            ("weekly", None) => Ok(RepeatRule::Weekly),
+            ("monthly", None) => Ok(RepeatRule::Monthly),
+            ("weekdays", None) => Ok(RepeatRule::Weekdays(vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ])),
+            ("weekdays", Some(days)) => {
+                let mut days = days
+                    .split(',')
+                    .map(|day| {
+                        day.trim().parse::<Weekday>().map_err(|_| {
+                            format!(
+                                "Unknown weekday '{}'. Use mon, tue, wed, thu, fri, sat or sun.",
+                                day
+                            )
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                days.sort_by_key(|day| day.num_days_from_monday());
+                days.dedup();
+                Ok(RepeatRule::Weekdays(days))
+            }
+            _ => Err(format!(
+                "Unknown repeat '{}'. Use daily, weekly, monthly or weekdays:mon,wed,fri.",
+                text
+            )),
+        }
+    }
+
+    pub fn starting_at(&self, due: DateTime<FixedOffset>) -> Recurrence {
+        match self {
+            RepeatRule::Daily => Recurrence::Daily,
+            RepeatRule::Weekly => Recurrence::Weekly,
+            RepeatRule::Monthly => Recurrence::Monthly { day: due.day() },
+            RepeatRule::Weekdays(days) => Recurrence::Weekdays { days: days.clone() },
+        }
+    }
+}
+
+impl Recurrence {
+    /// The first occurrence after `due`, keeping its time of day.
+    pub fn next_after(&self, due: DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+        match self {
+            Recurrence::Daily => due.checked_add_signed(Duration::days(1)),
+            Recurrence::Weekly => due.checked_add_signed(Duration::weeks(1)),
+            Recurrence::Monthly { day } => {
+                let first = due.date_naive().with_day(1)?.checked_add_months(Months::new(1))?;
+                let date = first.with_day((*day).min(days_in_month(first)?))?;
+                crate::task::local_time(&due.timezone(), date.and_time(due.time()))
+            }
+            Recurrence::Weekdays { days } => {
+                let date = (1..=7)
+                    .filter_map(|ahead| due.date_naive().checked_add_signed(Duration::days(ahead)))
+                    .find(|date| days.contains(&date.weekday()))?;
+                crate::task::local_time(&due.timezone(), date.and_time(due.time()))
+            }
+        }
+    }
+
+    /// The next `count` occurrences after `due`.
+    pub fn occurrences(&self, due: DateTime<FixedOffset>, count: usize) -> Vec<DateTime<FixedOffset>> {
+        let mut dates = Vec::with_capacity(count);
+        let mut current = due;
+        while dates.len() < count {
+            let Some(next) = self.next_after(current) else {
+                break;
+            };
+            dates.push(next);
+            current = next;
+        }
+        dates
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Recurrence::Daily => "daily".to_string(),
+            Recurrence::Weekly => "weekly".to_string(),
+            Recurrence::Monthly { day } => format!("monthly on day {}", day),
+            Recurrence::Weekdays { days } => {
+                let days: Vec<String> = days.iter().map(|day| day.to_string()).collect();
+                format!("weekly on {}", days.join(", "))
+            }
+        }
+    }
+}
+
+fn days_in_month(first: NaiveDate) -> Option<u32> {
+    let next_month = first.checked_add_months(Months::new(1))?;
+    Some(next_month.pred_opt()?.day())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(text).unwrap()
+    }
+
+    // 2026-03-02 is a Monday.
+    fn weekdays(text: &str) -> Recurrence {
+        RepeatRule::parse(text).unwrap().starting_at(at("2026-03-02T09:00:00+05:30"))
+    }
+
+    #[test]
+    fn friday_moves_on_to_monday() {
+        let recurrence = weekdays("weekdays:mon,wed,fri");
+        let next = recurrence.next_after(at("2026-03-06T09:00:00+05:30"));
+        assert_eq!(next, Some(at("2026-03-09T09:00:00+05:30")));
+    }
+
+    #[test]
+    fn sunday_moves_on_to_monday() {
+        let recurrence = weekdays("weekdays");
+        let next = recurrence.next_after(at("2026-03-08T18:30:00+05:30"));
+        assert_eq!(next, Some(at("2026-03-09T18:30:00+05:30")));
+    }
+
+    #[test]
+    fn a_single_day_comes_back_a_week_later() {
+        let recurrence = weekdays("weekdays:thu");
+        let occurrences = recurrence.occurrences(at("2026-03-05T09:00:00+05:30"), 2);
+        assert_eq!(
+            occurrences,
+            [at("2026-03-12T09:00:00+05:30"), at("2026-03-19T09:00:00+05:30")]
+        );
+    }
+
+    #[test]
+    fn next_occurrence_counts_from_the_due_date_not_now() {
+        // Completed months late, the task still moves one step along its
+        // schedule; skipping missed occurrences is up to the caller.
+        let recurrence = weekdays("weekdays:mon,wed,fri");
+        let next = recurrence.next_after(at("2020-01-06T09:00:00+05:30"));
+        assert_eq!(next, Some(at("2020-01-08T09:00:00+05:30")));
+    }
+
+    #[test]
+    fn parse_sorts_and_deduplicates_days() {
+        assert_eq!(
+            RepeatRule::parse("Weekdays:fri, mon,wed,mon"),
+            Ok(RepeatRule::Weekdays(vec![Weekday::Mon, Weekday::Wed, Weekday::Fri]))
+        );
+    }
+
+    #[test]
+    fn parse_rejects_an_empty_day_list() {
+        assert!(RepeatRule::parse("weekdays:").unwrap_err().starts_with("Unknown weekday"));
+        assert!(RepeatRule::parse("weekdays:mon,").is_err());
+        assert!(RepeatRule::parse("fortnightly").unwrap_err().starts_with("Unknown repeat"));
+    }
+}
@@ -0,0 +1,367 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Perl {
+    Digit,
+    Word,
+    Space,
+}
+
+impl Perl {
+    fn matches(self, c: char) -> bool {
+        match self {
+            Perl::Digit => c.is_ascii_digit(),
+            Perl::Word => c.is_alphanumeric() || c == '_',
+            Perl::Space => c.is_whitespace(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ClassItem {
+    Range(char, char),
+    /// `\d`, `\w` or `\s`, or with `true` their negations `\D`, `\W`, `\S`.
+    Perl(Perl, bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Char(char),
+    /// `.`: any character but a line break.
+    Any,
+    Class { items: Vec<ClassItem>, negated: bool },
+    Start,
+    End,
+    WordBoundary,
+    Concat(Vec<Node>),
+    Alternation(Vec<Node>),
+    Repeat { node: Box<Node>, min: usize, max: Option<usize>, greedy: bool },
+}
+
+/// A compiled `--regex` pattern, matched anywhere in a text unless anchored.
+/// A small backtracking matcher covering the common syntax: literals, `.`,
+/// classes such as `[a-z]` and `[^0-9]`, `\d`, `\w` and `\s` (and their
+/// negations), anchors `^`, `$` and `\b`, groups, `|`, and the quantifiers
+/// `*`, `+`, `?` and `{n,m}`, each lazy with a trailing `?`. A leading `(?i)`
+/// makes the whole pattern ignore case.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regex {
+    source: String,
+    root: Node,
+    ignore_case: bool,
+}
+
+impl fmt::Display for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// Most repetitions `{n,m}` may ask for, so a typo can't make matching crawl.
+const MAX_REPEAT: usize = 1000;
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn alternation(&mut self) -> Result<Node, String> {
+        let mut branches = vec![self.concat()?];
+        while self.eat('|') {
+            branches.push(self.concat()?);
+        }
+        Ok(if branches.len() == 1 { branches.remove(0) } else { Node::Alternation(branches) })
+    }
+
+    fn concat(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantified(atom)?);
+        }
+        Ok(Node::Concat(nodes))
+    }
+
+    fn quantified(&mut self, atom: Node) -> Result<Node, String> {
+        let start = self.pos;
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => match self.bounds()? {
+                Some(bounds) => bounds,
+                // Not a valid `{n,m}`, so a literal brace, as in most engines.
+                None => return Ok(atom),
+            },
+            _ => return Ok(atom),
+        };
+        if self.pos == start {
+            self.pos += 1;
+        }
+        if matches!(atom, Node::Start | Node::End | Node::WordBoundary) {
+            return Err(format!("nothing to repeat before '{}'", self.chars[start]));
+        }
+        let greedy = !self.eat('?');
+        Ok(Node::Repeat { node: Box::new(atom), min, max, greedy })
+    }
+
+    /// Reads `{n}`, `{n,}` or `{n,m}`, or leaves the position alone and
+    /// returns `None` when the brace doesn't start one.
+    fn bounds(&mut self) -> Result<Option<(usize, Option<usize>)>, String> {
+        let rest: String = self.chars[self.pos..].iter().collect();
+        let Some(end) = rest.find('}') else {
+            return Ok(None);
+        };
+        let inside = &rest[1..end];
+        let number = |text: &str| text.parse::<usize>().ok();
+        let bounds = match inside.split_once(',') {
+            None => number(inside).map(|n| (n, Some(n))),
+            Some((min, "")) => number(min).map(|min| (min, None)),
+            Some((min, max)) => number(min).zip(number(max)).map(|(min, max)| (min, Some(max))),
+        };
+        let Some((min, max)) = bounds else {
+            return Ok(None);
+        };
+        if max.is_some_and(|max| max < min) {
+            return Err(format!("{{{}}} has its bounds the wrong way round", inside));
+        }
+        if max.unwrap_or(min) > MAX_REPEAT {
+            return Err(format!("{{{}}} repeats more than {} times", inside, MAX_REPEAT));
+        }
+        self.pos += inside.chars().count() + 2;
+        Ok(Some((min, max)))
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let c = self.peek().ok_or("unexpected end of pattern")?;
+        self.pos += 1;
+        match c {
+            '(' => {
+                // Groups don't capture, so `(?:...)` is the same as `(...)`.
+                if self.chars[self.pos..].starts_with(&['?', ':']) {
+                    self.pos += 2;
+                }
+                let inner = self.alternation()?;
+                if !self.eat(')') {
+                    return Err("unclosed group: missing ')'".to_string());
+                }
+                Ok(inner)
+            }
+            ')' => Err("unmatched ')'".to_string()),
+            '[' => self.class(),
+            '.' => Ok(Node::Any),
+            '^' => Ok(Node::Start),
+            '$' => Ok(Node::End),
+            '*' | '+' | '?' => Err(format!("nothing to repeat before '{}'", c)),
+            '\\' => match self.escape()? {
+                Escaped::Char(c) => Ok(Node::Char(c)),
+                Escaped::Perl(perl, negated) => Ok(Node::Class {
+                    items: vec![ClassItem::Perl(perl, negated)],
+                    negated: false,
+                }),
+                Escaped::WordBoundary => Ok(Node::WordBoundary),
+            },
+            c => Ok(Node::Char(c)),
+        }
+    }
+
+    fn escape(&mut self) -> Result<Escaped, String> {
+        let c = self.peek().ok_or("pattern ends with a lone '\\'")?;
+        self.pos += 1;
+        Ok(match c {
+            'd' => Escaped::Perl(Perl::Digit, false),
+            'D' => Escaped::Perl(Perl::Digit, true),
+            'w' => Escaped::Perl(Perl::Word, false),
+            'W' => Escaped::Perl(Perl::Word, true),
+            's' => Escaped::Perl(Perl::Space, false),
+            'S' => Escaped::Perl(Perl::Space, true),
+            'b' => Escaped::WordBoundary,
+            'n' => Escaped::Char('\n'),
+            't' => Escaped::Char('\t'),
+            c if c.is_ascii_alphanumeric() => return Err(format!("unsupported escape '\\{}'", c)),
+            c => Escaped::Char(c),
+        })
+    }
+
+    /// A `[...]` class, after its `[`.
+    fn class(&mut self) -> Result<Node, String> {
+        let negated = self.eat('^');
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.peek().ok_or("unclosed class: missing ']'")?;
+            self.pos += 1;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let low = match c {
+                '\\' => match self.escape()? {
+                    Escaped::Char(c) => c,
+                    Escaped::Perl(perl, negated) => {
+                        items.push(ClassItem::Perl(perl, negated));
+                        continue;
+                    }
+                    Escaped::WordBoundary => return Err("'\\b' can't go in a class".to_string()),
+                },
+                c => c,
+            };
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']')
+            {
+                self.pos += 1;
+                let high = match self.peek().ok_or("unclosed class: missing ']'")? {
+                    '\\' => {
+                        self.pos += 1;
+                        match self.escape()? {
+                            Escaped::Char(c) => c,
+                            _ => return Err(format!("bad range end after '{}-'", low)),
+                        }
+                    }
+                    c => {
+                        self.pos += 1;
+                        c
+                    }
+                };
+                if high < low {
+                    return Err(format!("range '{}-{}' goes backwards", low, high));
+                }
+                items.push(ClassItem::Range(low, high));
+            } else {
+                items.push(ClassItem::Range(low, low));
+            }
+        }
+        Ok(Node::Class { items, negated })
+    }
+}
+
+enum Escaped {
+    Char(char),
+    Perl(Perl, bool),
+    WordBoundary,
+}
+
+/// Matches a parsed pattern against one text.
+struct Matcher<'a> {
+    text: &'a [char],
+    ignore_case: bool,
+}
+
+impl Matcher<'_> {
+    fn same(&self, a: char, b: char) -> bool {
+        a == b || (self.ignore_case && a.to_lowercase().eq(b.to_lowercase()))
+    }
+
+    fn in_class(&self, items: &[ClassItem], c: char) -> bool {
+        let variants = if self.ignore_case {
+            vec![c, c.to_lowercase().next().unwrap_or(c), c.to_uppercase().next().unwrap_or(c)]
+        } else {
+            vec![c]
+        };
+        items.iter().any(|item| match *item {
+            ClassItem::Range(low, high) => variants.iter().any(|v| (low..=high).contains(v)),
+            ClassItem::Perl(perl, negated) => perl.matches(c) != negated,
+        })
+    }
+
+    fn is_word(&self, pos: usize) -> bool {
+        self.text.get(pos).is_some_and(|&c| Perl::Word.matches(c))
+    }
+
+    /// Whether `node` matches at `pos` with `then` accepting where it ends.
+    fn at(&self, node: &Node, pos: usize, then: &mut dyn FnMut(usize) -> bool) -> bool {
+        let next = self.text.get(pos).copied();
+        match node {
+            Node::Char(c) => next.is_some_and(|n| self.same(n, *c)) && then(pos + 1),
+            Node::Any => next.is_some_and(|n| n != '\n') && then(pos + 1),
+            Node::Class { items, negated } => {
+                next.is_some_and(|n| self.in_class(items, n) != *negated) && then(pos + 1)
+            }
+            Node::Start => pos == 0 && then(pos),
+            Node::End => pos == self.text.len() && then(pos),
+            Node::WordBoundary => {
+                let before = pos > 0 && self.is_word(pos - 1);
+                before != self.is_word(pos) && then(pos)
+            }
+            Node::Concat(nodes) => self.sequence(nodes, pos, then),
+            Node::Alternation(branches) => branches.iter().any(|b| self.at(b, pos, then)),
+            Node::Repeat { node, min, max, greedy } => {
+                self.repeat(node, (*min, *max, *greedy), 0, pos, then)
+            }
+        }
+    }
+
+    fn sequence(&self, nodes: &[Node], pos: usize, then: &mut dyn FnMut(usize) -> bool) -> bool {
+        match nodes.split_first() {
+            None => then(pos),
+            Some((first, rest)) => self.at(first, pos, &mut |end| self.sequence(rest, end, then)),
+        }
+    }
+
+    fn repeat(
+        &self,
+        node: &Node,
+        (min, max, greedy): (usize, Option<usize>, bool),
+        count: usize,
+        pos: usize,
+        then: &mut dyn FnMut(usize) -> bool,
+    ) -> bool {
+        let may_stop = count >= min;
+        let may_go_on = max.is_none_or(|max| count < max);
+        let again = |then: &mut dyn FnMut(usize) -> bool| {
+            may_go_on
+                && self.at(node, pos, &mut |end| {
+                    // Once the minimum is met, an empty match would loop forever.
+                    (end != pos || !may_stop)
+                        && self.repeat(node, (min, max, greedy), count + 1, end, then)
+                })
+        };
+        if !may_stop {
+            return again(then);
+        }
+        // Greedy repeats try one more first, lazy ones try stopping first.
+        if greedy && again(then) {
+            return true;
+        }
+        then(pos) || (!greedy && again(then))
+    }
+}
+
+impl Regex {
+    /// Compiles `pattern`, or says what is wrong with it.
+    pub fn new(pattern: &str) -> Result<Regex, String> {
+        let (ignore_case, body) = match pattern.strip_prefix("(?i)") {
+            Some(body) => (true, body),
+            None => (false, pattern),
+        };
+        let mut parser = Parser { chars: body.chars().collect(), pos: 0 };
+        let root = parser.alternation()?;
+        if parser.pos < parser.chars.len() {
+            return Err("unmatched ')'".to_string());
+        }
+        Ok(Regex { source: pattern.to_string(), root, ignore_case })
+    }
+
+    /// Whether the pattern matches anywhere in `text`.
+    pub fn is_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        let matcher = Matcher { text: &chars, ignore_case: self.ignore_case };
+        (0..=chars.len()).any(|start| matcher.at(&self.root, start, &mut |_| true))
+    }
+}
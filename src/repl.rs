@@ -0,0 +1,79 @@
+use std::io::{self, BufRead, IsTerminal, StdinLock, Write};
+
+/// Reads lines from stdin for the `shell` command, prompting only when stdin
+/// is a terminal so piped scripts produce clean output.
+pub struct Lines {
+    stdin: StdinLock<'static>,
+    interactive: bool,
+}
+
+impl Lines {
+    pub fn new() -> Self {
+        let stdin = io::stdin();
+        let interactive = stdin.is_terminal();
+        Lines {
+            stdin: stdin.lock(),
+            interactive,
+        }
+    }
+
+    /// The next line without its line ending, or `None` at end of input.
+    pub fn next(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        if self.interactive {
+            print!("{}", prompt);
+            io::stdout().flush()?;
+        }
+        let mut line = String::new();
+        if self.stdin.read_line(&mut line)? == 0 {
+            if self.interactive {
+                println!();
+            }
+            return Ok(None);
+        }
+        Ok(Some(line.trim_end_matches(['\n', '\r']).to_string()))
+    }
+}
+
+/// Splits a command line into words the way a shell would for simple cases:
+/// whitespace separates words, single and double quotes group them, and a
+/// backslash escapes the next character outside single quotes.
+pub fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('\''), c) => word.push(c),
+            (_, '\\') => {
+                let escaped = chars.next().ok_or("Trailing backslash")?;
+                word.push(escaped);
+                in_word = true;
+            }
+            (Some(_), c) => word.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if let Some(q) = quote {
+        return Err(format!("Unclosed {} quote", q));
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
@@ -0,0 +1,146 @@
+use crate::task::{Priority, TaskStatus};
+use clap::ValueEnum;
+use serde_json::{json, Value};
+
+/// Serialized names of an enum's variants, which serde writes as the variant
+/// names themselves.
+fn variant_names<T: ValueEnum + std::fmt::Debug>() -> Vec<String> {
+    T::value_variants().iter().map(|v| format!("{:?}", v)).collect()
+}
+
+/// The version of the `tasks.json` format, raised whenever a change would
+/// stop older versions from reading files written by newer ones.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A JSON Schema (draft 2020-12) for the array printed by `list --json`,
+/// which is also the format of `tasks.json`. Kept by hand next to `Task`;
+/// update both together.
+pub fn task_list_schema() -> Value {
+    let timestamp = json!({ "type": ["string", "null"], "format": "date-time" });
+    // Built separately: nested this deep inside the main literal it would
+    // exceed the `json!` macro's recursion limit.
+    let recurrence = json!({
+        "description": "Absent or null for tasks that don't repeat",
+        "oneOf": [
+            { "type": "null" },
+            { "enum": ["Daily", "Weekly"] },
+            {
+                "type": "object",
+                "required": ["Monthly"],
+                "properties": {
+                    "Monthly": {
+                        "type": "object",
+                        "required": ["day"],
+                        "properties": {
+                            "day": { "type": "integer", "minimum": 1, "maximum": 31 }
+                        }
+                    }
+                }
+            },
+            {
+                "type": "object",
+                "required": ["Weekdays"],
+                "properties": {
+                    "Weekdays": {
+                        "type": "object",
+                        "required": ["days"],
+                        "properties": {
+                            "days": {
+                                "type": "array",
+                                "items": {
+                                    "enum": ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"]
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        ]
+    });
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "todo_cli task list",
+        "type": "array",
+        "items": { "$ref": "#/$defs/Task" },
+        "$defs": {
+            "Task": {
+                "type": "object",
+                "required": ["id", "description", "status", "due_date"],
+                "properties": {
+                    "id": { "type": "integer", "minimum": 0 },
+                    "description": { "type": "string" },
+                    "status": { "enum": variant_names::<TaskStatus>() },
+                    "due_date": timestamp,
+                    "someday": { "type": "boolean", "default": false },
+                    "wait_until": {
+                        "description": "Hidden from `list` until then",
+                        "type": ["string", "null"],
+                        "format": "date-time"
+                    },
+                    "remind_before": {
+                        "description": "Reminder lead time in minutes",
+                        "type": ["integer", "null"]
+                    },
+                    "notified": { "type": "boolean", "default": false },
+                    "subtasks": {
+                        "type": "array",
+                        "items": { "$ref": "#/$defs/SubTask" },
+                        "default": []
+                    },
+                    "completed_at": timestamp,
+                    "completion_note": { "type": ["string", "null"] },
+                    "created_at": timestamp,
+                    "updated_at": timestamp,
+                    "tags": { "type": "array", "items": { "type": "string" }, "default": [] },
+                    "contexts": { "type": "array", "items": { "type": "string" }, "default": [] },
+                    "project": { "type": ["string", "null"] },
+                    "assignee": { "type": ["string", "null"] },
+                    "priority": {
+                        "enum": variant_names::<Priority>().into_iter()
+                            .map(Value::from)
+                            .chain([Value::Null])
+                            .collect::<Vec<_>>()
+                    },
+                    "recurrence": recurrence,
+                    "locked": { "type": "boolean", "default": false },
+                    "annotations": {
+                        "type": "array",
+                        "items": { "$ref": "#/$defs/Annotation" },
+                        "default": []
+                    },
+                    "url": { "type": ["string", "null"], "format": "uri" },
+                    "estimate": {
+                        "description": "Expected time in minutes",
+                        "type": ["integer", "null"]
+                    },
+                    "time_spent": {
+                        "description": "Minutes spent on the task under `focus`",
+                        "type": ["integer", "null"]
+                    },
+                    "depends_on": {
+                        "description": "Ids of tasks that must be done first",
+                        "type": "array",
+                        "items": { "type": "integer", "minimum": 0 },
+                        "default": []
+                    }
+                }
+            },
+            "Annotation": {
+                "type": "object",
+                "required": ["at", "text"],
+                "properties": {
+                    "at": { "type": "string", "format": "date-time" },
+                    "text": { "type": "string" }
+                }
+            },
+            "SubTask": {
+                "type": "object",
+                "required": ["text", "done"],
+                "properties": {
+                    "text": { "type": "string" },
+                    "done": { "type": "boolean" }
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,215 @@
+use crate::task::{self, Priority, Task};
+use crate::urgency::{self, Weights};
+use clap::ValueEnum;
+use std::cmp::{Ordering, Reverse};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum SortKey {
+    /// Task id
+    Id,
+    /// Own priority, highest first; tasks without one last unless
+    /// `--nulls first`
+    Priority,
+    /// Due date, earliest first; undated tasks last unless `--nulls first`
+    Due,
+    /// Creation time, oldest first; tasks from before creation times were
+    /// recorded come first
+    Created,
+    /// Most recently updated first
+    Updated,
+    /// Completion time, earliest first; tasks not done last unless
+    /// `--nulls first`
+    Completed,
+    /// First tag, alphabetically; untagged tasks last unless `--nulls first`
+    Tag,
+    /// Most urgent first: effective priority (a task blocking a high-priority
+    /// task counts as high priority), then due date, then id
+    Smart,
+    /// Highest urgency score first, weighing due date, priority, age, tags
+    /// and dependencies as the config's `urgency` coefficients say
+    Urgency,
+}
+
+/// Where tasks without a due date, tag or priority go when sorting by one, and where
+/// the "Someday" bucket goes when grouping.
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Nulls {
+    First,
+    #[default]
+    Last,
+}
+
+impl Nulls {
+    fn flipped(self) -> Nulls {
+        match self {
+            Nulls::First => Nulls::Last,
+            Nulls::Last => Nulls::First,
+        }
+    }
+}
+
+/// One key of `--sort`, e.g. `due` or, reversed, `due-`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SortSpec {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+impl std::str::FromStr for SortSpec {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (name, descending) = match text.strip_suffix('-') {
+            Some(name) => (name, true),
+            None => (text, false),
+        };
+        let key = SortKey::from_str(name, true).map_err(|_| {
+            let names: Vec<String> = SortKey::value_variants()
+                .iter()
+                .filter_map(SortKey::to_possible_value)
+                .map(|value| value.get_name().to_string())
+                .collect();
+            format!("unknown sort key '{}'; expected one of {}", name, names.join(", "))
+        })?;
+        Ok(SortSpec { key, descending })
+    }
+}
+
+impl SortSpec {
+    /// Compares by the key, reversed when descending. Tasks without the
+    /// value stay where `nulls` puts them either way.
+    fn compare(self, a: &Task, b: &Task, nulls: Nulls, derived: &Derived) -> Ordering {
+        if self.descending {
+            self.key.compare(a, b, nulls.flipped(), derived).reverse()
+        } else {
+            self.key.compare(a, b, nulls, derived)
+        }
+    }
+}
+
+/// Values some keys sort by that depend on the whole task list, worked out
+/// once per sort and only when one of those keys is used.
+#[derive(Default)]
+struct Derived {
+    effective: HashMap<usize, EffectivePriority>,
+    urgency: HashMap<usize, f64>,
+}
+
+/// A task's priority after inheritance, and the dependent task it was
+/// inherited from if that raised it above the task's own priority.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EffectivePriority {
+    pub priority: Option<Priority>,
+    pub inherited_from: Option<usize>,
+}
+
+impl SortKey {
+    fn compare(self, a: &Task, b: &Task, nulls: Nulls, derived: &Derived) -> Ordering {
+        match self {
+            SortKey::Id => a.id.cmp(&b.id),
+            SortKey::Priority => {
+                compare_present(a.priority.map(Reverse), b.priority.map(Reverse), nulls)
+            }
+            SortKey::Due => compare_present(a.due_date, b.due_date, nulls),
+            SortKey::Tag => compare_present(a.tags.first(), b.tags.first(), nulls),
+            SortKey::Created => a.created_at.cmp(&b.created_at),
+            SortKey::Updated => b.updated_at.cmp(&a.updated_at),
+            SortKey::Completed => compare_present(a.completed_at, b.completed_at, nulls),
+            SortKey::Smart => {
+                let priority =
+                    |t: &Task| derived.effective.get(&t.id).map_or(t.priority, |e| e.priority);
+                Reverse(priority(a))
+                    .cmp(&Reverse(priority(b)))
+                    .then_with(|| SortKey::Due.compare(a, b, Nulls::Last, derived))
+                    .then_with(|| a.id.cmp(&b.id))
+            }
+            SortKey::Urgency => {
+                let score = |t: &Task| derived.urgency.get(&t.id).copied().unwrap_or_default();
+                score(b).total_cmp(&score(a))
+            }
+        }
+    }
+}
+
+/// Orders present values among themselves, with missing ones at the end
+/// `nulls` picks.
+fn compare_present<T: Ord>(a: Option<T>, b: Option<T>, nulls: Nulls) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) if nulls == Nulls::Last => Ordering::Less,
+        (Some(_), None) => Ordering::Greater,
+        (None, Some(_)) if nulls == Nulls::Last => Ordering::Greater,
+        (None, Some(_)) => Ordering::Less,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Computes every pending task's effective priority: the highest of its own
+/// priority and those of the pending tasks that depend on it, directly or
+/// through a chain of dependencies. Cycles are tolerated.
+pub fn effective_priorities(tasks: &[Task]) -> HashMap<usize, EffectivePriority> {
+    let mut effective: HashMap<usize, EffectivePriority> = tasks
+        .iter()
+        .filter(|t| t.is_pending())
+        .map(|t| {
+            let own = EffectivePriority {
+                priority: t.priority,
+                inherited_from: None,
+            };
+            (t.id, own)
+        })
+        .collect();
+    // Each pass pushes priorities one dependency link further; a chain can't
+    // be longer than the number of tasks, so this always settles.
+    for _ in 0..tasks.len() {
+        let mut changed = false;
+        for task in tasks.iter().filter(|t| t.is_pending()) {
+            let Some(&dependent) = effective.get(&task.id) else {
+                continue;
+            };
+            for id in &task.depends_on {
+                if let Some(blocker) = effective.get_mut(id)
+                    && dependent.priority > blocker.priority
+                {
+                    blocker.priority = dependent.priority;
+                    blocker.inherited_from = Some(dependent.inherited_from.unwrap_or(task.id));
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    effective
+}
+
+/// Stable sort by `keys` in turn, each later key breaking ties left by the
+/// ones before it; tasks that still compare equal keep their file order.
+/// `all` is the full task list, used to find dependents for `SortKey::Smart`
+/// and `SortKey::Urgency`, which scores tasks with `weights`.
+pub fn sort_tasks(
+    tasks: &mut [&Task],
+    keys: &[SortSpec],
+    nulls: Nulls,
+    all: &[Task],
+    weights: &Weights,
+) {
+    let uses = |key: SortKey| keys.iter().any(|spec| spec.key == key);
+    let mut derived = Derived::default();
+    if uses(SortKey::Smart) {
+        derived.effective = effective_priorities(all);
+    }
+    if uses(SortKey::Urgency) {
+        let now = task::now_ist();
+        derived.urgency =
+            tasks.iter().map(|t| (t.id, urgency::score(t, all, now, weights))).collect();
+    }
+    tasks.sort_by(|a, b| {
+        keys.iter().fold(Ordering::Equal, |ordering, spec| {
+            ordering.then_with(|| spec.compare(a, b, nulls, &derived))
+        })
+    });
+}
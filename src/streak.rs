@@ -0,0 +1,49 @@
+use chrono::{Duration, NaiveDate};
+use std::collections::BTreeMap;
+
+/// Runs of consecutive days with at least one task completed, for
+/// `stats --streak`.
+#[derive(Debug, PartialEq)]
+pub struct Streaks {
+    /// Days in the run ending today, or yesterday while nothing has been
+    /// completed today yet; 0 when neither had a completion.
+    pub current: usize,
+    pub longest: usize,
+    /// Tasks completed on each of the last days, oldest first, today last.
+    pub per_day: Vec<(NaiveDate, usize)>,
+}
+
+/// `Streaks` from the days tasks were completed on, one entry per task, with
+/// `days` days in `per_day`.
+pub fn streaks(
+    completed: impl IntoIterator<Item = NaiveDate>,
+    today: NaiveDate,
+    days: usize,
+) -> Streaks {
+    let mut counts: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+    for day in completed {
+        *counts.entry(day).or_default() += 1;
+    }
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for &day in counts.keys() {
+        run = if previous.is_some_and(|p| p + Duration::days(1) == day) { run + 1 } else { 1 };
+        longest = longest.max(run);
+        previous = Some(day);
+    }
+    let mut current = 0;
+    let mut day = if counts.contains_key(&today) { today } else { today - Duration::days(1) };
+    while counts.contains_key(&day) {
+        current += 1;
+        day -= Duration::days(1);
+    }
+    let per_day = (0..days)
+        .rev()
+        .map(|back| {
+            let day = today - Duration::days(back as i64);
+            (day, counts.get(&day).copied().unwrap_or(0))
+        })
+        .collect();
+    Streaks { current, longest, per_day }
+}
@@ -0,0 +1,98 @@
+use crate::display;
+
+/// How the columns of `list --fields` and `stats --by-tag` are separated,
+/// chosen with `--separator`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum Separator {
+    /// Columns padded to line up, two spaces apart
+    #[default]
+    Space,
+    /// Padded columns between `|` borders, with a rule under any header
+    Pipe,
+    /// A single tab between unpadded columns, for other tools to split
+    Tab,
+}
+
+impl Separator {
+    /// Terminal columns a row of `columns` cells spends on anything but the
+    /// cells themselves. A tab counts as one, though terminals show it wider.
+    pub fn overhead(self, columns: usize) -> usize {
+        match self {
+            Separator::Space => 2 * columns.saturating_sub(1),
+            Separator::Pipe => 3 * columns + 1,
+            Separator::Tab => columns.saturating_sub(1),
+        }
+    }
+}
+
+/// A cell's text and the color to paint it, if any.
+pub type Cell<'a> = (String, Option<&'a str>);
+
+/// Lays out rows of cells with every column as wide as its widest cell,
+/// measured in terminal columns.
+#[derive(Debug, Default)]
+pub struct Table {
+    pub separator: Separator,
+    /// Printed above the rows, in the rows' layout.
+    pub header: Option<Vec<String>>,
+    /// Indices of columns aligned to the right, such as counts.
+    pub right_aligned: Vec<usize>,
+}
+
+impl Table {
+    /// The lines of the table; rows may be shorter than the header, the
+    /// missing cells being blank.
+    pub fn render(&self, rows: &[Vec<Cell>], use_color: bool) -> Vec<String> {
+        let header: Option<Vec<Cell>> = self
+            .header
+            .as_ref()
+            .map(|names| names.iter().map(|name| (name.clone(), None)).collect());
+        let all = || header.iter().chain(rows);
+        let columns = all().map(Vec::len).max().unwrap_or(0);
+        let widths: Vec<usize> = (0..columns)
+            .map(|col| {
+                all()
+                    .filter_map(|row| row.get(col))
+                    .map(|cell| display::width(&cell.0))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let mut lines = Vec::new();
+        if let Some(header) = &header {
+            lines.push(self.line(header, &widths, false));
+            if self.separator == Separator::Pipe {
+                let rule: Vec<String> = widths.iter().map(|w| "-".repeat(w + 2)).collect();
+                lines.push(format!("|{}|", rule.join("|")));
+            }
+        }
+        lines.extend(rows.iter().map(|row| self.line(row, &widths, use_color)));
+        lines
+    }
+
+    fn line(&self, row: &[Cell], widths: &[usize], use_color: bool) -> String {
+        let cells: Vec<String> = widths
+            .iter()
+            .enumerate()
+            .map(|(col, &width)| {
+                let (text, color) = row.get(col).map_or(("", None), |(t, c)| (t.as_str(), *c));
+                let text = if self.separator == Separator::Tab {
+                    text.to_string()
+                } else if self.right_aligned.contains(&col) {
+                    format!("{}{}", " ".repeat(width.saturating_sub(display::width(text))), text)
+                } else {
+                    display::pad(text, width)
+                };
+                match color {
+                    Some(color) => display::paint(&text, color, use_color),
+                    None => text,
+                }
+            })
+            .collect();
+        match self.separator {
+            Separator::Space => cells.join("  ").trim_end().to_string(),
+            Separator::Pipe => format!("| {} |", cells.join(" | ")),
+            Separator::Tab => cells.join("\t"),
+        }
+    }
+}
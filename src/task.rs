@@ -1,35 +1,332 @@
-use chrono::{DateTime, FixedOffset}; 
+use chrono::{DateTime, Duration, DurationRound, FixedOffset, NaiveDateTime, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+use crate::recurrence::Recurrence;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
 pub enum TaskStatus {
     Pending,
     Done,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Declared in ascending order, so `High` compares greatest.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, clap::ValueEnum,
+)]
+pub enum Priority {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubTask {
+    pub text: String,
+    pub done: bool,
+}
+
+/// A timestamped note added with `annotate`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub at: DateTime<FixedOffset>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Task {
     pub id: usize,
     pub description: String,
     pub status: TaskStatus,
     pub due_date: Option<DateTime<FixedOffset>>, 
+    /// Left undated on purpose ("someday/maybe"), as opposed to a task whose
+    /// due date was never set.
+    #[serde(default)]
+    pub someday: bool,
+    /// Kept out of `list` and `peek` until this moment ("tickler file").
+    #[serde(default)]
+    pub wait_until: Option<DateTime<FixedOffset>>,
+    #[serde(default, with = "duration_minutes")]
+    pub remind_before: Option<Duration>,
+    #[serde(default)]
+    pub notified: bool,
+    #[serde(default)]
+    pub subtasks: Vec<SubTask>,
+    #[serde(default)]
+    pub completed_at: Option<DateTime<FixedOffset>>,
+    /// Why or how the task was finished, from `done --note`.
+    #[serde(default)]
+    pub completion_note: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<DateTime<FixedOffset>>,
+    /// Last time the task was changed or `touch`ed. Filled in on load for
+    /// tasks saved before this was recorded.
+    #[serde(default)]
+    pub updated_at: Option<DateTime<FixedOffset>>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Where or how the task gets done, e.g. "phone" (todo.txt's `@phone`),
+    /// as opposed to tags, which say what it is about.
+    #[serde(default)]
+    pub contexts: Vec<String>,
+    /// The larger piece of work the task belongs to; see `projects`.
+    #[serde(default)]
+    pub project: Option<String>,
+    /// Who is to do the task, for task files a team shares.
+    #[serde(default)]
+    pub assignee: Option<String>,
+    #[serde(default)]
+    pub priority: Option<Priority>,
+    /// Ids of tasks that must be done before this one.
+    #[serde(default)]
+    pub depends_on: Vec<usize>,
+    /// Completing a repeating task moves its due date to the next occurrence
+    /// instead of marking it done.
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
+    /// Link to a ticket, doc or other resource the task is about.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Locked tasks are only deleted or marked done with `--force`.
+    #[serde(default)]
+    pub locked: bool,
+    /// Running log of notes, oldest first.
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+    /// How long the task is expected to take, from `--estimate`.
+    #[serde(default, with = "duration_minutes")]
+    pub estimate: Option<Duration>,
+    /// Time spent on the task under `focus`; `None` if it never had any.
+    #[serde(default, with = "duration_minutes")]
+    pub time_spent: Option<Duration>,
 }
 
 impl Task {
     pub fn new(id: usize, description: String, due_date: Option<DateTime<FixedOffset>>) -> Self {
+        let now = now_ist();
         Task {
             id,
             description,
             status: TaskStatus::Pending, 
             due_date,
+            someday: false,
+            wait_until: None,
+            remind_before: None,
+            notified: false,
+            subtasks: Vec::new(),
+            completed_at: None,
+            completion_note: None,
+            created_at: Some(now),
+            updated_at: Some(now),
+            tags: Vec::new(),
+            contexts: Vec::new(),
+            project: None,
+            assignee: None,
+            priority: None,
+            depends_on: Vec::new(),
+            recurrence: None,
+            url: None,
+            locked: false,
+            annotations: Vec::new(),
+            estimate: None,
+            time_spent: None,
         }
     }
 
+    /// Adds `spent` to the time recorded under `focus`.
+    pub fn add_time_spent(&mut self, spent: Duration) {
+        let total = self.time_spent.unwrap_or_else(Duration::zero) + spent.max(Duration::zero());
+        self.time_spent = Some(total);
+    }
+
     pub fn mark_done(&mut self) {
         self.status = TaskStatus::Done;
+        self.completed_at = Some(now_ist());
+        self.touch();
+    }
+
+    pub fn touch(&mut self) {
+        self.updated_at = Some(now_ist());
     }
 
     pub fn is_pending(&self) -> bool {
         self.status == TaskStatus::Pending
     }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    pub fn has_context(&self, context: &str) -> bool {
+        self.contexts.iter().any(|c| c == context)
+    }
+
+    /// Pending and more than `grace` past its due time.
+    pub fn is_overdue(&self, now: DateTime<FixedOffset>, grace: Duration) -> bool {
+        self.is_pending() && self.due_date.is_some_and(|due| due + grace < now)
+    }
+
+    /// Pending and deferred with `--wait` until after `now`.
+    pub fn is_waiting(&self, now: DateTime<FixedOffset>) -> bool {
+        self.is_pending() && self.wait_until.is_some_and(|until| until > now)
+    }
+
+    /// Pending and created more than `max_age` ago. Tasks from before
+    /// `created_at` was recorded are never stale.
+    pub fn is_stale(&self, now: DateTime<FixedOffset>, max_age: Duration) -> bool {
+        self.is_pending() && self.created_at.is_some_and(|created| created + max_age < now)
+    }
+
+    /// True while any task this one depends on is still pending. Ids that no
+    /// longer exist (deleted or archived) don't block.
+    pub fn is_blocked(&self, tasks: &[Task]) -> bool {
+        self.depends_on
+            .iter()
+            .any(|id| tasks.iter().any(|t| t.id == *id && t.is_pending()))
+    }
+
+    /// Returns `(done, total)` subtask counts.
+    pub fn subtask_progress(&self) -> (usize, usize) {
+        let done = self.subtasks.iter().filter(|s| s.done).count();
+        (done, self.subtasks.len())
+    }
+
+    /// The moment a reminder for this task becomes due: `remind_before` ahead
+    /// of the due date, or the due date itself when no lead time is set.
+    pub fn remind_at(&self) -> Option<DateTime<FixedOffset>> {
+        let due = self.due_date?;
+        Some(due - self.remind_before.unwrap_or_else(Duration::zero))
+    }
+}
+
+/// IST, the offset task timestamps are stored in.
+pub fn ist() -> FixedOffset {
+    FixedOffset::east_opt(5 * 3600 + 30 * 60).expect("IST offset is in range")
+}
+
+/// The current time in IST.
+pub fn now_ist() -> DateTime<FixedOffset> {
+    Utc::now().with_timezone(&ist())
+}
+
+/// Drops the seconds and sub-seconds from `dt`, so due times computed from
+/// the clock line up with typed ones and compare equal.
+pub fn whole_minute(dt: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+    dt.duration_trunc(Duration::minutes(1)).unwrap_or(dt)
+}
+
+/// The instant a wall-clock time names at the fixed offset `tz`, which is
+/// always exactly one; `None` only past the range chrono can represent.
+pub fn local_time(tz: &FixedOffset, local: NaiveDateTime) -> Option<DateTime<FixedOffset>> {
+    tz.from_local_datetime(&local).single()
+}
+
+/// Stores an optional `Duration` as a whole number of minutes.
+mod duration_minutes {
+    use chrono::Duration;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(duration) => serializer.serialize_some(&duration.num_minutes()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+        let minutes: Option<i64> = Option::deserialize(deserializer)?;
+        minutes
+            .map(|minutes| {
+                Duration::try_minutes(minutes)
+                    .ok_or_else(|| D::Error::custom(format!("{} minutes is out of range", minutes)))
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Weekday;
+
+    fn at(text: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(text).unwrap()
+    }
+
+    #[test]
+    fn whole_minute_drops_seconds_and_fractions() {
+        let dt = at("2026-03-01T17:42:59.999+05:30");
+        assert_eq!(whole_minute(dt), at("2026-03-01T17:42:00+05:30"));
+        let exact = at("2026-03-01T17:42:00+05:30");
+        assert_eq!(whole_minute(exact), exact);
+    }
+
+    #[test]
+    fn every_field_survives_a_json_round_trip() {
+        let due = at("2026-03-01T17:00:00+05:30");
+        let mut task = Task::new(7, "Write the report".to_string(), Some(due));
+        task.status = TaskStatus::Done;
+        task.someday = true;
+        task.wait_until = Some(at("2026-02-20T09:00:00+05:30"));
+        task.remind_before = Some(Duration::minutes(90));
+        task.notified = true;
+        task.subtasks = vec![
+            SubTask { text: "Outline".to_string(), done: true },
+            SubTask { text: "Draft".to_string(), done: false },
+        ];
+        task.completed_at = Some(at("2026-02-28T18:30:00+05:30"));
+        task.completion_note = Some("sent to the team".to_string());
+        task.created_at = Some(at("2026-02-01T10:00:00+05:30"));
+        task.updated_at = Some(at("2026-02-28T18:30:00+05:30"));
+        task.tags = vec!["work".to_string(), "writing".to_string()];
+        task.contexts = vec!["office".to_string()];
+        task.project = Some("quarterly".to_string());
+        task.assignee = Some("sam".to_string());
+        task.priority = Some(Priority::High);
+        task.depends_on = vec![3, 5];
+        task.recurrence = Some(Recurrence::Weekdays { days: vec![Weekday::Mon, Weekday::Thu] });
+        task.url = Some("https://example.com/ticket/42".to_string());
+        task.locked = true;
+        task.annotations = vec![Annotation {
+            at: at("2026-02-10T12:00:00+05:30"),
+            text: "Waiting on numbers".to_string(),
+        }];
+        task.estimate = Some(Duration::minutes(150));
+        task.time_spent = Some(Duration::minutes(95));
+
+        let json = serde_json::to_string(&task).unwrap();
+        let loaded: Task = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, task);
+    }
+
+    #[test]
+    fn minimal_json_loads_with_defaults() {
+        let json = r#"{"id": 1, "description": "Buy milk", "status": "Pending"}"#;
+        let task: Task = serde_json::from_str(json).unwrap();
+        assert_eq!(task.id, 1);
+        assert_eq!(task.description, "Buy milk");
+        assert!(task.is_pending());
+        assert_eq!(task.due_date, None);
+        assert!(!task.someday);
+        assert_eq!(task.wait_until, None);
+        assert_eq!(task.remind_before, None);
+        assert!(!task.notified);
+        assert!(task.subtasks.is_empty());
+        assert_eq!(task.completed_at, None);
+        assert_eq!(task.completion_note, None);
+        assert_eq!(task.created_at, None);
+        assert_eq!(task.updated_at, None);
+        assert!(task.tags.is_empty());
+        assert!(task.contexts.is_empty());
+        assert_eq!(task.project, None);
+        assert_eq!(task.assignee, None);
+        assert_eq!(task.priority, None);
+        assert!(task.depends_on.is_empty());
+        assert_eq!(task.recurrence, None);
+        assert_eq!(task.url, None);
+        assert!(!task.locked);
+        assert!(task.annotations.is_empty());
+        assert_eq!(task.estimate, None);
+        assert_eq!(task.time_spent, None);
+    }
 }
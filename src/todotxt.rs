@@ -0,0 +1,126 @@
+use crate::task::{self, Priority, Task};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// todo.txt has 26 priorities; the top three map onto ours and everything
+/// below C counts as low.
+fn letter(priority: Priority) -> char {
+    match priority {
+        Priority::High => 'A',
+        Priority::Medium => 'B',
+        Priority::Low => 'C',
+    }
+}
+
+fn priority(letter: &str) -> Option<Priority> {
+    match letter {
+        "A" => Some(Priority::High),
+        "B" => Some(Priority::Medium),
+        _ if letter.len() == 1 && letter.chars().all(|c| c.is_ascii_uppercase()) => {
+            Some(Priority::Low)
+        }
+        _ => None,
+    }
+}
+
+fn date(dt: DateTime<FixedOffset>) -> String {
+    dt.format(DATE_FORMAT).to_string()
+}
+
+fn parse_date(text: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(text, DATE_FORMAT).ok()
+}
+
+/// `date` at `time` in IST.
+fn at(date: NaiveDate, time: NaiveTime) -> Option<DateTime<FixedOffset>> {
+    task::local_time(&task::now_ist().timezone(), date.and_time(time))
+}
+
+/// One task as a todo.txt line, e.g. `(A) 2024-06-01 buy milk +home
+/// due:2024-06-05`. Done tasks start with `x` and their completion date and
+/// keep their priority as `pri:A`, since todo.txt has no place for it there.
+/// Tags are written as `+project`s and contexts as `@context`s; the task's
+/// own project, of which todo.txt has no notion, goes in `project:`.
+pub fn format_line(task: &Task) -> String {
+    let mut words = Vec::new();
+    if !task.is_pending() {
+        words.push("x".to_string());
+        // A lone date after `x` is the completion date, so the creation date
+        // can only follow one.
+        if let Some(completed_at) = task.completed_at {
+            words.push(date(completed_at));
+            words.extend(task.created_at.map(date));
+        }
+    } else {
+        words.extend(task.priority.map(|p| format!("({})", letter(p))));
+        words.extend(task.created_at.map(date));
+    }
+    words.push(task.description.clone());
+    words.extend(task.tags.iter().map(|tag| format!("+{}", tag)));
+    words.extend(task.contexts.iter().map(|context| format!("@{}", context)));
+    words.extend(task.project.as_ref().map(|project| format!("project:{}", project)));
+    words.extend(task.due_date.map(|due| format!("due:{}", date(due))));
+    if !task.is_pending() {
+        words.extend(task.priority.map(|p| format!("pri:{}", letter(p))));
+    }
+    words.join(" ")
+}
+
+/// Reads one todo.txt line as a task numbered `id`: completion mark and
+/// dates, priority, `+project` words (which become tags), `@context` words
+/// and the `due:`, `pri:` and `project:` extensions. Dates count from midnight, except due
+/// dates, which get `due_time` like a `--due` written without a time.
+pub fn parse_line(line: &str, id: usize, due_time: NaiveTime) -> Result<Task, String> {
+    let mut words = line.split_whitespace().peekable();
+    let mut task = Task::new(id, String::new(), None);
+    let marked = |word: &&str| priority(word.strip_prefix('(')?.strip_suffix(')')?);
+    let mut created = None;
+    if words.next_if_eq(&"x").is_some() {
+        task.mark_done();
+        if let Some(completed) = words.peek().and_then(|word| parse_date(word)) {
+            words.next();
+            task.completed_at = at(completed, NaiveTime::MIN);
+            created = words.next_if(|word| parse_date(word).is_some());
+        }
+        // Some tools leave the priority in place after the dates.
+        if let Some(marked) = words.peek().and_then(marked) {
+            words.next();
+            task.priority = Some(marked);
+        }
+    } else {
+        if let Some(marked) = words.peek().and_then(marked) {
+            words.next();
+            task.priority = Some(marked);
+        }
+        created = words.next_if(|word| parse_date(word).is_some());
+    }
+    if let Some(created) = created.and_then(parse_date) {
+        task.created_at = at(created, NaiveTime::MIN);
+        task.updated_at = task.created_at;
+    }
+
+    let mut description = Vec::new();
+    for word in words {
+        if let Some(tag) = word.strip_prefix('+').filter(|tag| !tag.is_empty()) {
+            if !task.has_tag(tag) {
+                task.tags.push(tag.to_string());
+            }
+        } else if let Some(context) = word.strip_prefix('@').filter(|c| !c.is_empty()) {
+            if !task.has_context(context) {
+                task.contexts.push(context.to_string());
+            }
+        } else if let Some(due) = word.strip_prefix("due:") {
+            let due = parse_date(due).ok_or_else(|| format!("invalid due date '{}'", due))?;
+            task.due_date = at(due, due_time);
+        } else if let Some(marked) = word.strip_prefix("pri:").and_then(priority) {
+            task.priority = Some(marked);
+        } else if let Some(project) = word.strip_prefix("project:").filter(|p| !p.is_empty()) {
+            task.project = Some(project.to_string());
+        } else {
+            description.push(word);
+        }
+    }
+    task.description = description.join(" ");
+    Ok(task)
+}
@@ -0,0 +1,96 @@
+use crate::task::{Priority, Task};
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+
+/// How much each signal adds to a task's urgency, set under `urgency` in the
+/// config. Each coefficient multiplies a factor between 0 and 1 (due, age
+/// and tags) or is added as is (priority, blocking, blocked). The defaults
+/// are taskwarrior's.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Weights {
+    pub due: f64,
+    pub priority_high: f64,
+    pub priority_medium: f64,
+    pub priority_low: f64,
+    /// Reached by tasks a year old or older.
+    pub age: f64,
+    /// Reached by tasks with three tags or more.
+    pub tags: f64,
+    /// For a task other pending tasks depend on.
+    pub blocking: f64,
+    /// For a task still waiting on others; negative to push it down.
+    pub blocked: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            due: 12.0,
+            priority_high: 6.0,
+            priority_medium: 3.9,
+            priority_low: 1.8,
+            age: 2.0,
+            tags: 1.0,
+            blocking: 8.0,
+            blocked: -5.0,
+        }
+    }
+}
+
+/// Days over which a task's age counts towards its urgency.
+const AGE_DAYS: f64 = 365.0;
+
+/// How near its due date a task is, from 0.2 for two weeks or more away up
+/// to 1 for a week or more overdue; 0 when it has none.
+fn due_factor(task: &Task, now: DateTime<FixedOffset>) -> f64 {
+    let Some(due) = task.due_date else {
+        return 0.0;
+    };
+    let overdue_days = (now - due).num_seconds() as f64 / 86_400.0;
+    if overdue_days >= 7.0 {
+        1.0
+    } else if overdue_days >= -14.0 {
+        (overdue_days + 14.0) * 0.8 / 21.0 + 0.2
+    } else {
+        0.2
+    }
+}
+
+fn age_factor(task: &Task, now: DateTime<FixedOffset>) -> f64 {
+    task.created_at.map_or(0.0, |created| {
+        let days = (now - created).num_seconds() as f64 / 86_400.0;
+        (days / AGE_DAYS).clamp(0.0, 1.0)
+    })
+}
+
+fn tags_factor(task: &Task) -> f64 {
+    match task.tags.len() {
+        0 => 0.0,
+        1 => 0.8,
+        2 => 0.9,
+        _ => 1.0,
+    }
+}
+
+/// A pending task's urgency, combining how near it is due, its priority,
+/// age and tags, and whether it blocks or waits on other tasks in `all`.
+/// Done tasks score 0.
+pub fn score(task: &Task, all: &[Task], now: DateTime<FixedOffset>, weights: &Weights) -> f64 {
+    if !task.is_pending() {
+        return 0.0;
+    }
+    let priority = match task.priority {
+        Some(Priority::High) => weights.priority_high,
+        Some(Priority::Medium) => weights.priority_medium,
+        Some(Priority::Low) => weights.priority_low,
+        None => 0.0,
+    };
+    let blocking = all.iter().any(|t| t.is_pending() && t.depends_on.contains(&task.id));
+    weights.due * due_factor(task, now)
+        + priority
+        + weights.age * age_factor(task, now)
+        + weights.tags * tags_factor(task)
+        + if blocking { weights.blocking } else { 0.0 }
+        + if task.is_blocked(all) { weights.blocked } else { 0.0 }
+}